@@ -0,0 +1,165 @@
+//! Compares the buffer kernels of `pcm_flow::simd` with scalar loops:
+//! the loop a Graph mixed with before the kernels were added, and the loops
+//! the kernels fall back to without AVX, which the compiler may vectorize itself.
+//! Both sides of a comparison work on the same planar buffers,
+//! which go through `black_box` so the loops can not be merged or removed.
+//!
+//! Run with `cargo bench --bench mixing`.
+
+extern crate pcm_flow;
+extern crate sample;
+
+use pcm_flow::graph::{empty_buffer, Graph};
+use pcm_flow::processor::Processor;
+use pcm_flow::processors::util::Splitter;
+use pcm_flow::simd::{self, fallback, Kernel};
+use sample::{Frame, Sample};
+use std::hint::black_box;
+use std::time::Instant;
+
+const SAMPLES: usize = 512;
+const SOURCES: usize = 32;
+const ROUNDS: usize = 2000;
+
+// runs `f` ROUNDS times and returns the nanoseconds per round
+fn time<M>(mut f: M) -> f64
+where
+    M: FnMut(),
+{
+    // warm up
+    for _ in 0..ROUNDS / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    (elapsed.as_secs() as f64 * 1e9 + elapsed.subsec_nanos() as f64) / ROUNDS as f64
+}
+
+fn report(name: &str, baseline: &str, scalar: f64, kernel: f64) {
+    println!(
+        "{:<24} {:<9} {:>8.0} ns   kernel {:>8.0} ns   speedup {:.2}x",
+        name,
+        baseline,
+        scalar,
+        kernel,
+        scalar / kernel
+    );
+}
+
+// SOURCES buffers with `channels` channels each, every channel is one planar slice
+fn sources<S>(channels: usize) -> Vec<Vec<S>>
+where
+    S: Sample + Kernel,
+{
+    (0..SOURCES * channels)
+        .map(|i| {
+            (0..SAMPLES)
+                .map(|n| (((i + n) % 16) as f64 / 64.0).to_sample())
+                .collect()
+        })
+        .collect()
+}
+
+// sums every source into the channel of `dest` it belongs to,
+// the same data is summed by both sides
+fn sum<S, M>(dest: &mut [Vec<S>], sources: &[Vec<S>], add: M)
+where
+    S: Sample,
+    M: Fn(&mut [S], &[S]),
+{
+    for destination in dest.iter_mut() {
+        for sample in destination.iter_mut() {
+            *sample = S::equilibrium();
+        }
+    }
+    let channels = dest.len();
+    for (i, source) in sources.iter().enumerate() {
+        add(black_box(&mut dest[i % channels][..]), black_box(&source[..]));
+    }
+    black_box(dest);
+}
+
+// the sum of all channels, both sides have to agree on it
+fn checksum<S>(dest: &[Vec<S>]) -> f64
+where
+    S: Sample + Kernel,
+{
+    dest.iter()
+        .flat_map(|channel| channel.iter())
+        .map(|sample| sample.to_sample::<f64>())
+        .sum()
+}
+
+// sums the sources with the `baseline` loop and with the `kernel`
+fn compare<S, B, K>(name: &str, channels: usize, baseline: (&str, B), kernel: K)
+where
+    S: Sample + Kernel,
+    B: Fn(&mut [S], &[S]),
+    K: Fn(&mut [S], &[S]),
+{
+    let sources = sources::<S>(channels);
+    let mut dest = vec![vec![S::equilibrium(); SAMPLES]; channels];
+    let scalar = time(|| sum(&mut dest, &sources, &baseline.1));
+    let expected = checksum(&dest);
+    let kernels = time(|| sum(&mut dest, &sources, &kernel));
+    assert_eq!(checksum(&dest), expected);
+    report(name, baseline.0, scalar, kernels);
+}
+
+// a whole Graph with the kernels, every splitter output goes to the same input
+// of the last node. The "before" rows compare the kernels with its earlier mixing.
+fn graph<F>(name: &str)
+where
+    F: Frame,
+    F::Sample: Kernel,
+{
+    let mut graph = Graph::<F>::new(SAMPLES, 48_000);
+    let split = graph.add_processor(Box::new(Splitter::new(SOURCES)));
+    let out = graph.add_processor(Box::new(Splitter::new(1)));
+    graph.set_input_amt(1);
+    graph.set_output_amt(1);
+    graph.connect_input(0, (split, 0)).unwrap();
+    graph.connect_output(0, (out, 0)).unwrap();
+    for port in 0..SOURCES {
+        graph.add_connection(&(split, port), &(out, 0)).unwrap();
+    }
+    let inputs = vec![vec![F::from_fn(|_| 0.01.to_sample())]; SAMPLES];
+    let mut outputs = empty_buffer(1, SAMPLES);
+    let nanos = time(|| {
+        graph.process(black_box(&inputs), black_box(&mut outputs));
+        black_box(&outputs);
+    });
+    println!("{:<24} {:>8.0} ns per block", name, nanos);
+}
+
+fn main() {
+    println!(
+        "{} samples, {} sources, AVX {}",
+        SAMPLES,
+        SOURCES,
+        if simd::accelerated() { "on" } else { "off" }
+    );
+    // a Graph summed its connections with add_scalar before the kernels were added
+    compare("sum f32", 1, ("before", simd::add_scalar), f32::add);
+    compare("sum f64", 1, ("before", simd::add_scalar), f64::add);
+    compare("sum stereo f32", 2, ("before", simd::add_scalar), f32::add);
+    compare("sum f32", 1, ("fallback", fallback::add), f32::add);
+    compare("sum f64", 1, ("fallback", fallback::add), f64::add);
+    compare(
+        "sum with gain f32",
+        1,
+        ("fallback", |dest: &mut [f32], source: &[f32]| fallback::add_scaled(dest, source, 0.5)),
+        |dest, source| f32::add_scaled(dest, source, 0.5),
+    );
+    compare(
+        "sum with gain f64",
+        1,
+        ("fallback", |dest: &mut [f64], source: &[f64]| fallback::add_scaled(dest, source, 0.5)),
+        |dest, source| f64::add_scaled(dest, source, 0.5),
+    );
+    graph::<[f32; 2]>("graph stereo f32");
+    graph::<[f64; 2]>("graph stereo f64");
+}
@@ -1,24 +1,28 @@
 extern crate pcm_flow;
 
-use pcm_flow::graph::Graph;
+use pcm_flow::builder::GraphBuilder;
 use pcm_flow::processor::Processor;
+use pcm_flow::processors::delay::{Delay, DelayTime};
+use pcm_flow::processors::util::Mixer;
 
 fn main() {
-    // create a new graph Struct, it is the main container for our Processors
-    let mut graph = Graph::new(1, 41_000);
-    let mixer = graph.add_processor(Box::new(Mixer {}));
-    let distortion = graph.add_processor(Box::new(Distortion {}));
-    let delay = graph.add_processor(Box::new(Delay {
-        ringbuffer: vec![vec![0.0; 2]; 10],
-        index: 0,
-    }));
-    graph.add_connection(&(distortion, 0), &(mixer, 0)).unwrap();
-    graph.add_connection(&(delay, 0), &(mixer, 1)).unwrap();
-    graph.set_input_amt(1);
-    graph.set_output_amt(1);
-    graph.connect_input(0, (distortion, 0)).unwrap();
-    graph.connect_input(0, (delay, 0)).unwrap();
-    graph.connect_output(0, (mixer, 0)).unwrap();
+    // delay the input by 8 samples
+    let mut delay = Delay::new(0.01);
+    delay.set_time(DelayTime::Seconds(8.0 / 41_000.0));
+    // build the graph, it is the main container for our Processors
+    let mut graph = GraphBuilder::new()
+        .buffersize(1)
+        .samplerate(41_000)
+        .node("mixer", Mixer::new(2))
+        .node("distortion", Distortion {})
+        .node("delay", delay)
+        .connect("distortion:0", "mixer:0")
+        .connect("delay:0", "mixer:1")
+        .input(0, "distortion:0")
+        .input(0, "delay:0")
+        .output(0, "mixer:0")
+        .build()
+        .unwrap();
     let input = vec![vec![[3.1, 3.1]]];
     let mut output = vec![vec![[0.0, 0.0]]];
     graph.process(&input, &mut output);
@@ -29,26 +33,8 @@ fn main() {
     assert_eq!(output[0][0][0], 0.5 + 3.1);
 }
 
-// The Mixer struct we define here, takes two inputs and outputs the sum
-struct Mixer {}
-
-impl Processor<[f32; 2]> for Mixer {
-    fn process(&mut self, inputs: &Vec<Vec<[f32; 2]>>, outputs: &mut Vec<Vec<[f32; 2]>>) {
-        for channel in 0..2 {
-            for sample in 0..inputs.len() {
-                outputs[sample][0][channel] = inputs[sample][0][channel] + inputs[sample][1][channel];
-            }
-        }
-    }
-    fn inputs_amt(&self) -> usize {
-        2
-    }
-    fn outputs_amt(&self) -> usize {
-        1
-    }
-}
-
 // The Distortion struct defined here takes a input and clips the signal at 0.5 and -0.5
+// (processors::distortion::Waveshaper does this without aliasing, by oversampling)
 struct Distortion {}
 
 impl Processor<[f32; 2]> for Distortion {
@@ -66,30 +52,3 @@ impl Processor<[f32; 2]> for Distortion {
         1
     }
 }
-
-// The Delay struct takes a input and delays its output by a fixed amounth of samples
-struct Delay {
-    ringbuffer: Vec<Vec<f32>>,
-    index: usize,
-}
-
-impl Processor<[f32; 2]> for Delay {
-    fn process(&mut self, inputs: &Vec<Vec<[f32; 2]>>, outputs: &mut Vec<Vec<[f32; 2]>>) {
-        for channel in 0..2 {
-            for sample in 0..inputs.len() {
-                outputs[sample][0][channel] = self.ringbuffer[self.index][channel];
-                self.ringbuffer[self.index][channel] = inputs[sample][0][channel];
-                self.index += 1;
-                if self.index >= self.ringbuffer.len() {
-                    self.index = 0;
-                }
-            }
-        }
-    }
-    fn inputs_amt(&self) -> usize {
-        1
-    }
-    fn outputs_amt(&self) -> usize {
-        1
-    }
-}
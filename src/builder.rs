@@ -0,0 +1,224 @@
+extern crate sample;
+
+use graph::Graph;
+use processor::Processor;
+use self::sample::Frame;
+use std::collections::HashSet;
+
+/// Builds a Graph from named nodes and connections written as `"node:port"`,
+/// the port is the name of its `PortInfo` or its index.
+/// A port written as just `"node"` means port 0 of that node.
+/// All errors are collected until `build` gets called, so calls can be chained:
+///
+/// ```ignore
+/// let graph = GraphBuilder::new()
+///     .node("dist", Distortion {})
+///     .node("mix", Mixer {})
+///     .connect("dist:0", "mix:0")
+///     .input(0, "dist:0")
+///     .output(0, "mix:0")
+///     .build()?;
+/// ```
+pub struct GraphBuilder<F> {
+    buffersize: usize,
+    samplerate: usize,
+    nodes: Vec<(String, Box<Processor<F>>)>,
+    connections: Vec<(String, String)>,
+    // (graph input, destination port)
+    inputs: Vec<(usize, String)>,
+    // (graph output, source port)
+    outputs: Vec<(usize, String)>,
+    input_amt: Option<usize>,
+    output_amt: Option<usize>,
+}
+
+impl<F> GraphBuilder<F>
+where
+    F: Frame,
+{
+    /// Create a new GraphBuilder for a graph with a buffersize of 64 at 44100Hz
+    pub fn new() -> Self {
+        GraphBuilder {
+            buffersize: 64,
+            samplerate: 44_100,
+            nodes: Vec::new(),
+            connections: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            input_amt: None,
+            output_amt: None,
+        }
+    }
+
+    /// set the amount of Frames processed for one process()
+    pub fn buffersize(mut self, buffersize: usize) -> Self {
+        self.buffersize = buffersize;
+        self
+    }
+
+    /// set the samplerate passed to all processors
+    pub fn samplerate(mut self, samplerate: usize) -> Self {
+        self.samplerate = samplerate;
+        self
+    }
+
+    /// set the amount of graph inputs.
+    /// By default it is the highest input index used plus one.
+    pub fn input_amt(mut self, inputs: usize) -> Self {
+        self.input_amt = Some(inputs);
+        self
+    }
+
+    /// set the amount of graph outputs.
+    /// By default it is the highest output index used plus one.
+    pub fn output_amt(mut self, outputs: usize) -> Self {
+        self.output_amt = Some(outputs);
+        self
+    }
+
+    /// add a processor under the given name
+    pub fn node<P>(self, name: &str, processor: P) -> Self
+    where
+        P: Processor<F> + 'static,
+    {
+        self.boxed_node(name, Box::new(processor))
+    }
+
+    /// add an already boxed processor under the given name
+    pub fn boxed_node(mut self, name: &str, processor: Box<Processor<F>>) -> Self {
+        self.nodes.push((name.to_string(), processor));
+        self
+    }
+
+    /// connect an output port to an input port
+    pub fn connect(mut self, source: &str, dest: &str) -> Self {
+        self.connections.push((source.to_string(), dest.to_string()));
+        self
+    }
+
+    /// connect a graph input to an input port
+    pub fn input(mut self, input: usize, dest: &str) -> Self {
+        self.inputs.push((input, dest.to_string()));
+        self
+    }
+
+    /// connect an output port to a graph output
+    pub fn output(mut self, output: usize, source: &str) -> Self {
+        self.outputs.push((output, source.to_string()));
+        self
+    }
+
+    /// Build the Graph, or return a description of the first error.
+    pub fn build(self) -> Result<Graph<F>, String> {
+        let mut graph = Graph::new(self.buffersize, self.samplerate);
+        let mut names = HashSet::new();
+        for (name, processor) in self.nodes {
+            if !names.insert(name.clone()) {
+                return Err(format!("node '{}' is added twice", name));
+            }
+            let id = graph.add_processor(processor);
+            graph.set_processor_name(id, &name)?;
+        }
+
+        let input_amt = self.inputs.iter().map(|&(i, _)| i + 1).max().unwrap_or(0);
+        let output_amt = self.outputs.iter().map(|&(o, _)| o + 1).max().unwrap_or(0);
+        graph.set_input_amt(self.input_amt.unwrap_or(input_amt));
+        graph.set_output_amt(self.output_amt.unwrap_or(output_amt));
+
+        for (source, dest) in self.connections {
+            let source_id = graph.output_port(&source)?;
+            let dest_id = graph.input_port(&dest)?;
+            graph
+                .add_connection(&source_id, &dest_id)
+                .map_err(|e| format!("{} -> {}: {}", source, dest, e))?;
+        }
+        for (input, dest) in self.inputs {
+            let dest_id = graph.input_port(&dest)?;
+            graph
+                .connect_input(input, dest_id)
+                .map_err(|e| format!("input {} -> {}: {}", input, dest, e))?;
+        }
+        for (output, source) in self.outputs {
+            let source_id = graph.output_port(&source)?;
+            graph
+                .connect_output(output, source_id)
+                .map_err(|e| format!("{} -> output {}: {}", source, output, e))?;
+        }
+        Ok(graph)
+    }
+}
+
+/// Builds a Graph in place, returning a `Result<Graph<F>, String>`.
+/// Nodes become local variables, so a misspelled node name
+/// is a compile error. The indices of graph inputs and outputs
+/// are checked against the declared amounts at compile time.
+/// The port counts of processors are only known at run time, so a port
+/// a processor does not have, like a cycle, makes the macro return an error
+/// naming the connection instead of panicking.
+///
+/// ```ignore
+/// let graph = graph! {
+///     frame: [f32; 2],
+///     buffersize: 64,
+///     samplerate: 44_100,
+///     nodes {
+///         dist = Distortion {},
+///         mix = Mixer {},
+///     }
+///     connections {
+///         dist.0 -> mix.0,
+///     }
+///     inputs(1) {
+///         0 -> dist.0,
+///     }
+///     outputs(1) {
+///         mix.0 -> 0,
+///     }
+/// }?;
+/// ```
+#[macro_export]
+macro_rules! graph {
+    (
+        frame: $frame:ty,
+        buffersize: $buffersize:expr,
+        samplerate: $samplerate:expr,
+        nodes { $($name:ident = $processor:expr),* $(,)* }
+        connections { $($src:ident . $src_port:tt -> $dest:ident . $dest_port:tt),* $(,)* }
+        inputs($inputs:expr) { $($input:tt -> $in_node:ident . $in_port:tt),* $(,)* }
+        outputs($outputs:expr) { $($out_node:ident . $out_port:tt -> $output:tt),* $(,)* }
+    ) => {
+        (|| -> Result<$crate::graph::Graph<$frame>, String> {
+            let mut graph = $crate::graph::Graph::<$frame>::new($buffersize, $samplerate);
+            $(
+                let $name = graph.add_processor(Box::new($processor));
+            )*
+            graph.set_input_amt($inputs);
+            graph.set_output_amt($outputs);
+            $(
+                graph
+                    .add_connection(&($src, $src_port), &($dest, $dest_port))
+                    .map_err(|e| format!(
+                        "{}.{} -> {}.{}: {}",
+                        stringify!($src), $src_port, stringify!($dest), $dest_port, e
+                    ))?;
+            )*
+            $(
+                const _: () = assert!($input < $inputs, "graph input index out of range");
+                graph
+                    .connect_input($input, ($in_node, $in_port))
+                    .map_err(|e| format!(
+                        "input {} -> {}.{}: {}", $input, stringify!($in_node), $in_port, e
+                    ))?;
+            )*
+            $(
+                const _: () = assert!($output < $outputs, "graph output index out of range");
+                graph
+                    .connect_output($output, ($out_node, $out_port))
+                    .map_err(|e| format!(
+                        "{}.{} -> output {}: {}", stringify!($out_node), $out_port, $output, e
+                    ))?;
+            )*
+            Ok(graph)
+        })()
+    };
+}
@@ -1,17 +1,166 @@
 extern crate petgraph;
 extern crate sample;
 
-use processor::Processor;
+use io::wav::{SampleFormat, WavError, WavSpec, WavWriter};
+use planar::PlanarBuffer;
+use processor::{Event, PortType, Processor};
+use simd::Kernel;
+use self::sample::conv::Duplex;
 use self::sample::{Frame, Sample};
 use self::petgraph::graph::Graph as PetGraph;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 
 pub type PortId = (usize, usize);
 pub type Buffer<F> = Vec<F>;
 pub type FrameSet<F> = Vec<F>;
 pub type BufferSet<F> = Vec<FrameSet<F>>;
 
+// the default time in seconds a changed connection takes to reach its new gain
+const CONNECTION_SMOOTHING: f64 = 0.005;
+
+/// The attributes of a connection between two processors.
+/// The graph multiplies the signal with them while summing it into the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Connection {
+    pub gain: f64,
+    /// Inverts the polarity of the signal.
+    pub inverted: bool,
+    pub muted: bool,
+}
+
+impl Connection {
+    /// Create a Connection at unity gain
+    pub fn new() -> Self {
+        Connection::with_gain(1.0)
+    }
+
+    pub fn with_gain(gain: f64) -> Self {
+        Connection {
+            gain: gain,
+            inverted: false,
+            muted: false,
+        }
+    }
+
+    /// returns the factor the signal gets multiplied with
+    pub fn factor(&self) -> f64 {
+        if self.muted {
+            0.0
+        } else if self.inverted {
+            -self.gain
+        } else {
+            self.gain
+        }
+    }
+}
+
+// a connection and the gain it currently applies, which ramps to the
+// factor of the connection after it was changed
+struct Link {
+    connection: Connection,
+    gain: f64,
+    // samples left until the gain reaches the factor
+    remaining: usize,
+}
+
+impl Link {
+    fn new(connection: Connection) -> Self {
+        Link {
+            connection: connection,
+            gain: connection.factor(),
+            remaining: 0,
+        }
+    }
+
+    // adds port `source_port` of `source` to port `port` of `dest`
+    fn mix<S>(
+        &mut self,
+        dest: &mut PlanarBuffer<S>,
+        port: usize,
+        source: &PlanarBuffer<S>,
+        source_port: usize,
+    ) where
+        S: Kernel,
+    {
+        let target = self.connection.factor();
+        if self.remaining > 0 {
+            let len = self.remaining.min(dest.samples());
+            let end = self.gain + (target - self.gain) * len as f64 / self.remaining as f64;
+            dest.add_port_ramp(port, source, source_port, self.gain, end, len);
+            self.gain = end;
+            self.remaining -= len;
+        } else if target == 1.0 {
+            dest.add_port(port, source, source_port);
+        } else if target != 0.0 {
+            dest.add_port_scaled(port, source, source_port, target);
+        }
+    }
+
+    // adds `value` to the value of a control input,
+    // with the gain the link reaches after `samples` samples
+    fn mix_control<F>(&mut self, dest: &mut F, value: F, samples: usize)
+    where
+        F: Frame,
+        F::Sample: Kernel,
+    {
+        let target = self.connection.factor();
+        if self.remaining > 0 {
+            let len = self.remaining.min(samples);
+            self.gain += (target - self.gain) * len as f64 / self.remaining as f64;
+            self.remaining -= len;
+        } else {
+            self.gain = target;
+        }
+        let gain = self.gain;
+        *dest = dest.zip_map(value, |dest, value| {
+            (dest.to_sample::<f64>() + value.to_sample::<f64>() * gain).to_sample()
+        });
+    }
+}
+
+// BufferSets big enough for the processor with the most ports,
+// they are cut to the ports of the processor using them
+struct Interleaved<F> {
+    inputs: BufferSet<F>,
+    outputs: BufferSet<F>,
+}
+
+impl<F> Interleaved<F>
+where
+    F: Frame,
+{
+    fn new(samples: usize) -> Self {
+        Interleaved {
+            inputs: vec![Vec::new(); samples],
+            outputs: vec![Vec::new(); samples],
+        }
+    }
+
+    // makes room for a processor with these amounts of ports,
+    // so fit does not allocate
+    fn reserve(&mut self, inputs: usize, outputs: usize) {
+        for frames in self.inputs.iter_mut() {
+            frames.reserve(inputs.saturating_sub(frames.len()));
+        }
+        for frames in self.outputs.iter_mut() {
+            frames.reserve(outputs.saturating_sub(frames.len()));
+        }
+    }
+
+    // cuts the BufferSets to the ports of a processor and silences the outputs
+    fn fit(&mut self, inputs: usize, outputs: usize) {
+        for frames in self.inputs.iter_mut() {
+            frames.resize(inputs, F::equilibrium());
+        }
+        for frames in self.outputs.iter_mut() {
+            frames.clear();
+            frames.resize(outputs, F::equilibrium());
+        }
+    }
+}
+
 /// The main container struct for Processors.
 /// Processors can be added and connected in arbitrary
 /// ways as long there are no cyclic connections.
@@ -19,20 +168,33 @@ pub type BufferSet<F> = Vec<FrameSet<F>>;
 /// A graph has an arbitrary number of inputs and outputs
 /// which can be connected to processors.
 /// These inputs and outputs are called graph inputs and graph outputs.
-pub struct Graph<F> {
+/// Inside the graph the signals are kept in PlanarBuffers.
+/// They are mixed with the kernels of `simd`, so processing needs
+/// `F::Sample: Kernel`, which every sample type of the sample crate implements.
+/// Graph inputs and outputs are audio ports, or event ports while they are
+/// connected to event ports of processors. See `PortType` for how
+/// signals are converted between audio and control ports.
+pub struct Graph<F: Frame> {
     samplerate: usize,
     // contains all processors
     processors: Vec<Box<Processor<F>>>,
+    // optional names of the processors, used in descriptions
+    names: Vec<Option<String>>,
     // buffers that contains the graph inputs
-    graph_input_buffers: BufferSet<F>,
+    graph_input_buffers: PlanarBuffer<F::Sample>,
     // buffers that contain the graph outputs
-    graph_output_buffers: BufferSet<F>,
-    // input buffer sets for all processors
-    input_buffers: Vec<BufferSet<F>>,
-    // output buffer sets for all processors
-    output_buffers: Vec<BufferSet<F>>,
+    graph_output_buffers: PlanarBuffer<F::Sample>,
+    // input buffers for all processors
+    input_buffers: Vec<PlanarBuffer<F::Sample>>,
+    // output buffers for all processors
+    output_buffers: Vec<PlanarBuffer<F::Sample>>,
+    // the buffers of a processor which does not process planar buffers,
+    // converted to BufferSets. It is shared by all of them.
+    interleaved: Interleaved<F>,
     // a hash map describing all connections from port to port
-    connections: HashMap<PortId, HashSet<PortId>>,
+    connections: HashMap<PortId, HashMap<PortId, Link>>,
+    // the time in seconds changes of connections are smoothed over
+    connection_smoothing: f64,
     // a list of connections from the inputs to nodes
     input_connections: HashMap<usize, HashSet<PortId>>,
     // a list of connections from nodes to the outputs
@@ -41,6 +203,21 @@ pub struct Graph<F> {
     topological_sorting: Vec<usize>,
     // amount of Frames processed for one process()
     buffersize: usize,
+    // the values of the control outputs in the last and in this block
+    control_values: HashMap<PortId, (F, F)>,
+    // the value of every control input in this block, indexed like input_buffers
+    control_inputs: Vec<Vec<F>>,
+    // the events waiting for every input of every processor
+    events: Vec<Vec<Vec<Event>>>,
+    // the events of every graph output in this block
+    output_events: Vec<Vec<Event>>,
+    // holds a control output ramped for an audio input or the events of one output
+    scratch: PlanarBuffer<F::Sample>,
+    scratch_events: Vec<Event>,
+    // inputs with a default value other than 0.0, it is used while they are unconnected
+    defaults: Vec<(PortId, f64)>,
+    // the defaults of the inputs without a connection, updated when connections change
+    unconnected_defaults: Vec<(PortId, f64)>,
 }
 
 impl<F> Graph<F>
@@ -52,29 +229,71 @@ where
         Graph {
             samplerate: samplerate,
             processors: Vec::new(),
-            graph_input_buffers: vec![],
-            graph_output_buffers: vec![],
+            names: Vec::new(),
+            graph_input_buffers: PlanarBuffer::new(0, F::n_channels(), buffersize),
+            graph_output_buffers: PlanarBuffer::new(0, F::n_channels(), buffersize),
             input_connections: HashMap::new(),
             output_connections: HashMap::new(),
             connections: HashMap::new(),
+            connection_smoothing: CONNECTION_SMOOTHING,
             topological_sorting: Vec::new(),
             input_buffers: Vec::new(),
             output_buffers: Vec::new(),
+            interleaved: Interleaved::new(buffersize),
             buffersize: buffersize,
+            control_values: HashMap::new(),
+            control_inputs: Vec::new(),
+            events: Vec::new(),
+            output_events: Vec::new(),
+            scratch: PlanarBuffer::new(1, F::n_channels(), buffersize),
+            scratch_events: Vec::new(),
+            defaults: Vec::new(),
+            unconnected_defaults: Vec::new(),
         }
     }
 
+    /// returns the amount of Frames processed for one process()
+    pub fn buffersize(&self) -> usize {
+        self.buffersize
+    }
+
+    /// returns the samplerate passed to the processors
+    pub fn samplerate(&self) -> usize {
+        self.samplerate
+    }
+
     /// Add a new processor to the Graph. Its ID gets returned.
     pub fn add_processor(&mut self, processor: Box<Processor<F>>) -> usize {
         let index = self.processors.len();
-        self.input_buffers
-            .push(empty_buffer(processor.inputs_amt(), self.buffersize));
-        self.output_buffers
-            .push(empty_buffer(processor.outputs_amt(), self.buffersize));
+        self.input_buffers.push(PlanarBuffer::new(
+            processor.inputs_amt(),
+            F::n_channels(),
+            self.buffersize,
+        ));
+        self.output_buffers.push(PlanarBuffer::new(
+            processor.outputs_amt(),
+            F::n_channels(),
+            self.buffersize,
+        ));
+        self.interleaved
+            .reserve(processor.inputs_amt(), processor.outputs_amt());
         for i in 0..processor.outputs_amt() {
-            self.connections.insert((index, i), HashSet::new());
+            self.connections.insert((index, i), HashMap::new());
+        }
+        self.events.push(vec![Vec::new(); processor.inputs_amt()]);
+        self.control_inputs
+            .push(vec![F::equilibrium(); processor.inputs_amt()]);
+        for i in 0..processor.inputs_amt() {
+            let default = processor.input_info(i).default;
+            if default != 0.0 {
+                self.defaults.push(((index, i), default));
+            }
         }
         self.processors.push(processor);
+        self.names.push(None);
+        self.update_defaults();
+        // a processor without connections can be processed last
+        self.topological_sorting.push(index);
         self.processors[index].set_samplerate(self.samplerate);
         return index;
     }
@@ -82,21 +301,38 @@ where
     /// Connect an input to a processor
     pub fn connect_input(&mut self, input: usize, port: PortId) -> Result<(), String> {
         if !self.inport_exists(port) {
-            return Err(format!("port {} does not exist on node {}", port.0, port.1));
+            return Err(format!("port {} does not exist on node {}", port.1, port.0));
+        }
+        let events = self.processors[port.0].input_type(port.1) == PortType::Event;
+        if self.input_connections.get(&input).map_or(false, |ports| {
+            ports.iter().any(|&(processor, port)| {
+                (self.processors[processor].input_type(port) == PortType::Event) != events
+            })
+        }) {
+            return Err(format!("graph input {} can not carry events and signals", input));
         }
         match self.input_connections.get_mut(&input) {
             Some(x) => {
                 x.insert(port);
-                Ok(())
             }
-            None => Err(format!("input {} does not exist", input)),
+            None => return Err(format!("input {} does not exist", input)),
         }
+        self.update_defaults();
+        Ok(())
     }
 
     /// connect an output to a processor
     pub fn connect_output(&mut self, output: usize, port: PortId) -> Result<(), String> {
         if !self.outport_exists(port) {
-            return Err(format!("port {} does not exist on node {}", port.0, port.1));
+            return Err(format!("port {} does not exist on node {}", port.1, port.0));
+        }
+        let events = self.processors[port.0].output_type(port.1) == PortType::Event;
+        if self.output_connections.get(&output).map_or(false, |ports| {
+            ports.iter().any(|&(processor, port)| {
+                (self.processors[processor].output_type(port) == PortType::Event) != events
+            })
+        }) {
+            return Err(format!("graph output {} can not carry events and signals", output));
         }
         match self.output_connections.get_mut(&output) {
             Some(x) => {
@@ -109,20 +345,22 @@ where
 
     /// set the amount of inputs
     pub fn set_input_amt(&mut self, inputs: usize) {
-        self.graph_input_buffers = empty_buffer(inputs, self.buffersize);
+        self.graph_input_buffers.reset(inputs, self.buffersize);
         self.input_connections = HashMap::new();
         for i in 0..inputs {
             self.input_connections.insert(i, HashSet::new());
         }
+        self.update_defaults();
     }
 
     /// set the amount of outputs
     pub fn set_output_amt(&mut self, outputs: usize) {
-        self.graph_output_buffers = empty_buffer(outputs, self.buffersize);
+        self.graph_output_buffers.reset(outputs, self.buffersize);
         self.output_connections = HashMap::new();
         for i in 0..outputs {
             self.output_connections.insert(i, HashSet::new());
         }
+        self.output_events = vec![Vec::new(); outputs];
     }
 
     /// add aconnection between two ports
@@ -141,20 +379,34 @@ where
                         if dest_processor.inputs_amt() <= dest_id.1 {
                             return Err("Destination Port does not Exist".to_string());
                         }
+                        // events only go from event ports to event ports,
+                        // audio and control ports are converted into each other
+                        let source_type = self.processors[source_id.0].output_type(source_id.1);
+                        let dest_type = dest_processor.input_type(dest_id.1);
+                        if (source_type == PortType::Event) != (dest_type == PortType::Event) {
+                            return Err(format!(
+                                "can not connect {:?} output to {:?} input",
+                                source_type,
+                                dest_type
+                            ).to_lowercase());
+                        }
                     }
                     // dest processor does not exist
                     _ => {
                         return Err("Destination Processor does not exist".to_string());
                     }
                 }
-                // connection is added
-                dest_connections.insert(dest_id);
+                // connection is added, an existing one keeps its attributes
+                dest_connections
+                    .entry(dest_id)
+                    .or_insert_with(|| Link::new(Connection::new()));
             }
             // src port does not exist
             None => {
                 return Err("Source Processor or Processor Port does not exist".to_string());
             }
         }
+        self.update_defaults();
         match self.get_topological_sorting() {
             Some(sorted) => {
                 self.topological_sorting = sorted;
@@ -167,65 +419,69 @@ where
         Ok(())
     }
 
-    /// Values get passed along in the graph.
-    fn process_graph(&mut self) {
-        // clear input and output buffers
-        for i in 0..self.processors.len() {
-            self.input_buffers[i] = 
-                empty_buffer(self.processors[i].inputs_amt(), self.buffersize);
-            self.output_buffers[i] =
-                empty_buffer(self.processors[i].outputs_amt(), self.buffersize);
-        }
+    /// Add a connection between two ports with attributes.
+    pub fn add_connection_with(
+        &mut self,
+        source: PortId,
+        dest: PortId,
+        connection: Connection,
+    ) -> Result<(), String> {
+        self.add_connection(&source, &dest)?;
+        *self.link(source, dest)? = Link::new(connection);
+        Ok(())
+    }
 
-        // pass graph input buffers to connected Processors
-        // iterate over all graph input connections
-        for (src, dest) in &self.input_connections {
-            // iterate over all destination input ports
-            for &(dest_proc, dest_port) in dest {
-                // iterate over all samples
-                for sample in 0..self.buffersize {
-                    self.input_buffers[dest_proc][sample][dest_port] =
-                        self.graph_input_buffers[sample][*src];
-                }
-            }
+    /// returns the attributes of the connection between two ports
+    pub fn connection(&self, source: PortId, dest: PortId) -> Option<Connection> {
+        self.connections
+            .get(&source)
+            .and_then(|dests| dests.get(&dest))
+            .map(|link| link.connection)
+    }
+
+    /// Change the attributes of a connection while the graph is running.
+    /// The gain moves to the new value smoothly, see `set_connection_smoothing`.
+    pub fn set_connection(
+        &mut self,
+        source: PortId,
+        dest: PortId,
+        connection: Connection,
+    ) -> Result<(), String> {
+        let smoothing = (self.connection_smoothing * self.samplerate as f64).round() as usize;
+        let link = self.link(source, dest)?;
+        link.connection = connection;
+        link.remaining = smoothing;
+        if smoothing == 0 {
+            link.gain = connection.factor();
         }
+        Ok(())
+    }
 
-        // go through the sorted processors and pass the Frames on
-        for src_processor in &self.topological_sorting {
-            self.processors[*src_processor].process(
-                &self.input_buffers[*src_processor],
-                &mut self.output_buffers[*src_processor],
-            );
-            // iterate over output ports
-            for src_port in 0..self.processors[*src_processor].outputs_amt() {
-                // match for connected inputs
-                if let Some(connected_ports) = self.connections.get(&(*src_processor, src_port)) {
-                    // iterate over connected inputs
-                    for &(dest_processor, dest_port) in connected_ports {
-                        // iterate over samples
-                        for sample in 0..self.buffersize {
-                            self.input_buffers[dest_processor][sample][dest_port] =
-                                self.input_buffers[dest_processor][sample][dest_port].zip_map(
-                                    self.output_buffers[*src_processor][sample][src_port],
-                                    |x, y| x.add_amp(y.to_sample()),
-                                );
-                        }
-                    }
-                }
-            }
+    /// Set the time in seconds changes of connections take, 5ms by default.
+    pub fn set_connection_smoothing(&mut self, seconds: f64) {
+        self.connection_smoothing = seconds.max(0.0);
+    }
+
+    /// Send an event to an event input, it arrives in the next block.
+    pub fn send_event(&mut self, port: PortId, event: Event) -> Result<(), String> {
+        if !self.inport_exists(port) || self.processors[port.0].input_type(port.1) != PortType::Event
+        {
+            return Err(format!("node {} has no event input {}", port.0, port.1));
         }
+        self.events[port.0][port.1].push(event);
+        Ok(())
+    }
 
-        // pass data to graph output buffers
-        for (dest, src) in &self.output_connections {
-            for &(src_proc, src_port) in src {
-                for sample in 0..self.buffersize {
-                    self.graph_output_buffers[sample][*dest] =
-                        self.graph_output_buffers[sample][*dest].zip_map(
-                            self.output_buffers[src_proc][sample][src_port],
-                            |x, y| x.add_amp(y.to_sample()),
-                        );
-                }
-            }
+    fn link(&mut self, source: PortId, dest: PortId) -> Result<&mut Link, String> {
+        match self.connections.get_mut(&source).and_then(|dests| dests.get_mut(&dest)) {
+            Some(link) => Ok(link),
+            None => Err(format!(
+                "there is no connection from port {} of node {} to port {} of node {}",
+                source.1,
+                source.0,
+                dest.1,
+                dest.0
+            )),
         }
     }
 
@@ -241,7 +497,7 @@ where
         }
 
         for (&(src_processor, _), in_port_ids) in &self.connections {
-            for &(dest_processor, _) in in_port_ids {
+            for &(dest_processor, _) in in_port_ids.keys() {
                 petgraph.add_edge(
                     graph_ix_to_pet_ix[&src_processor],
                     graph_ix_to_pet_ix[&dest_processor],
@@ -262,13 +518,180 @@ where
         }
     }
 
+    /// give a processor a name which is used in descriptions of the graph
+    pub fn set_processor_name(&mut self, processor: usize, name: &str) -> Result<(), String> {
+        match self.names.get_mut(processor) {
+            Some(x) => {
+                *x = Some(name.to_string());
+                Ok(())
+            }
+            None => Err(format!("processor {} does not exist", processor)),
+        }
+    }
+
+    /// returns the name of a processor, if it has one
+    pub fn processor_name(&self, processor: usize) -> Option<&str> {
+        self.names.get(processor).and_then(|name| name.as_ref().map(|name| name.as_str()))
+    }
+
+    /// Returns the index of the input of a processor with the given name,
+    /// the name of a port is the one of its `PortInfo` or its index.
+    /// Indices the processor has no input for are not found.
+    pub fn input_index(&self, processor: usize, name: &str) -> Option<usize> {
+        let processor = self.processors.get(processor)?;
+        (0..processor.inputs_amt())
+            .find(|&input| processor.input_info(input).name == name)
+            .or_else(|| name.parse().ok().filter(|&i| i < processor.inputs_amt()))
+    }
+
+    /// Returns the index of the output of a processor with the given name.
+    pub fn output_index(&self, processor: usize, name: &str) -> Option<usize> {
+        let processor = self.processors.get(processor)?;
+        (0..processor.outputs_amt())
+            .find(|&output| processor.output_info(output).name == name)
+            .or_else(|| name.parse().ok().filter(|&i| i < processor.outputs_amt()))
+    }
+
+    /// Returns the input port written as `"node:port"`, the node is the name
+    /// given with `set_processor_name`. A bare `"node"` means port 0.
+    pub fn input_port(&self, port: &str) -> Result<PortId, String> {
+        self.find_port(port, true)
+    }
+
+    /// Returns the output port written as `"node:port"`.
+    pub fn output_port(&self, port: &str) -> Result<PortId, String> {
+        self.find_port(port, false)
+    }
+
+    /// Like `add_connection`, with the ports written as `"node:port"`.
+    pub fn connect_named(&mut self, source: &str, dest: &str) -> Result<(), String> {
+        let source_id = self.output_port(source)?;
+        let dest_id = self.input_port(dest)?;
+        self.add_connection(&source_id, &dest_id)
+    }
+
+    /// Like `connect_input`, with the port written as `"node:port"`.
+    pub fn connect_input_named(&mut self, input: usize, dest: &str) -> Result<(), String> {
+        let dest_id = self.input_port(dest)?;
+        self.connect_input(input, dest_id)
+    }
+
+    /// Like `connect_output`, with the port written as `"node:port"`.
+    pub fn connect_output_named(&mut self, output: usize, source: &str) -> Result<(), String> {
+        let source_id = self.output_port(source)?;
+        self.connect_output(output, source_id)
+    }
+
+    // turns "node:port" into a PortId
+    fn find_port(&self, port: &str, input: bool) -> Result<PortId, String> {
+        let mut parts = port.splitn(2, ':');
+        let node = parts.next().unwrap_or("");
+        let found = (0..self.names.len()).find(|&i| self.processor_name(i) == Some(node));
+        let processor = match found {
+            Some(processor) => processor,
+            None => return Err(format!("node '{}' does not exist", node)),
+        };
+        let name = match parts.next() {
+            Some(name) => name,
+            None => return Ok((processor, 0)),
+        };
+        let index = if input {
+            self.input_index(processor, name)
+        } else {
+            self.output_index(processor, name)
+        };
+        match index {
+            Some(index) => Ok((processor, index)),
+            None => Err(format!(
+                "node '{}' has no {} named '{}'",
+                node,
+                if input { "input" } else { "output" },
+                name
+            )),
+        }
+    }
+
+    // true if a graph input or an output of a processor is connected to the input port
+    fn is_connected(&self, port: PortId) -> bool {
+        let from_input = self.input_connections.values().any(|dests| dests.contains(&port));
+        from_input || self.connections.values().any(|dests| dests.contains_key(&port))
+    }
+
+    // collects the defaults of the inputs without a connection
+    fn update_defaults(&mut self) {
+        let unconnected: Vec<(PortId, f64)> = self
+            .defaults
+            .iter()
+            .filter(|&&(port, _)| !self.is_connected(port))
+            .cloned()
+            .collect();
+        self.unconnected_defaults = unconnected;
+    }
+
+    /// returns the graph in the dot format of graphviz
+    pub fn get_dot_string(&self) -> String {
+        let mut string = String::from("digraph {\n\trankdir=LR;\n");
+        for i in 0..self.processors.len() {
+            let label = match self.processor_name(i) {
+                Some(name) => name.to_string(),
+                None => format!("processor {}", i),
+            };
+            string += &format!(
+                "\tp{} [label=\"{}\", shape=box];\n",
+                i,
+                label.replace('"', "\\\"")
+            );
+        }
+        let mut inputs: Vec<_> = self.input_connections.iter().collect();
+        inputs.sort_by_key(|&(input, _)| *input);
+        for (input, dest) in inputs {
+            string += &format!("\tin{} [label=\"in[{}]\", shape=circle];\n", input, input);
+            for &(dest_proc, dest_port) in sorted(dest).iter() {
+                string += &format!(
+                    "\tin{} -> p{} [headlabel=\"{}\"];\n",
+                    input,
+                    dest_proc,
+                    dest_port
+                );
+            }
+        }
+        let mut connections: Vec<_> = self.connections.iter().collect();
+        connections.sort_by_key(|&(src, _)| *src);
+        for (&(src_proc, src_port), dest) in connections {
+            for &(dest_proc, dest_port) in sorted(dest.keys()).iter() {
+                string += &format!(
+                    "\tp{} -> p{} [taillabel=\"{}\", headlabel=\"{}\"];\n",
+                    src_proc,
+                    dest_proc,
+                    src_port,
+                    dest_port
+                );
+            }
+        }
+        let mut outputs: Vec<_> = self.output_connections.iter().collect();
+        outputs.sort_by_key(|&(output, _)| *output);
+        for (output, src) in outputs {
+            string += &format!("\tout{} [label=\"out[{}]\", shape=circle];\n", output, output);
+            for &(src_proc, src_port) in sorted(src).iter() {
+                string += &format!(
+                    "\tp{} -> out{} [taillabel=\"{}\"];\n",
+                    src_proc,
+                    output,
+                    src_port
+                );
+            }
+        }
+        string += "}\n";
+        string
+    }
+
     pub fn get_description_string(&self) -> String {
         let mut string = String::new();
         string += &format!("Processors: {}\n", self.processors.len());
         string += "Connections: \n";
         for (&(src_proc, src_port), dest_procs) in &self.connections {
             string += &format!("\tsrc Processor: {}, src Port: {}\n", src_proc, src_port);
-            for &(dest_proc, dest_port) in dest_procs {
+            for &(dest_proc, dest_port) in dest_procs.keys() {
                 string += &format!(
                     "\t\tdest Processor: {}, dest Port: {}\n",
                     dest_proc,
@@ -279,6 +702,37 @@ where
         string
     }
 
+    /// returns the longest tail along any path through the graph
+    fn longest_tail(&self) -> usize {
+        self.longest_path(|processor| processor.tail_length())
+    }
+
+    /// returns the longest latency along any path through the graph
+    fn longest_latency(&self) -> usize {
+        self.longest_path(|processor| processor.latency())
+    }
+
+    // returns the largest sum of `length` along any path through the graph
+    fn longest_path<L>(&self, length: L) -> usize
+    where
+        L: Fn(&Box<Processor<F>>) -> usize,
+    {
+        // the length accumulated up to the output of each processor
+        let mut lengths = vec![0; self.processors.len()];
+        for &src_processor in &self.topological_sorting {
+            lengths[src_processor] += length(&self.processors[src_processor]);
+            for src_port in 0..self.processors[src_processor].outputs_amt() {
+                if let Some(connected_ports) = self.connections.get(&(src_processor, src_port)) {
+                    for &(dest_processor, _) in connected_ports.keys() {
+                        lengths[dest_processor] =
+                            lengths[dest_processor].max(lengths[src_processor]);
+                    }
+                }
+            }
+        }
+        lengths.into_iter().max().unwrap_or(0)
+    }
+
     fn inport_exists(&self, port: PortId) -> bool {
         if port.0 < self.processors.len() {
             if port.1 < self.processors[port.0].inputs_amt() {
@@ -298,45 +752,425 @@ where
     }
 }
 
+impl<F> Graph<F>
+where
+    F: Frame,
+    F::Sample: Kernel,
+{
+    /// Values get passed along in the graph.
+    fn process_graph(&mut self) {
+        // clear input and output buffers
+        for i in 0..self.processors.len() {
+            self.input_buffers[i].reset(self.processors[i].inputs_amt(), self.buffersize);
+            self.output_buffers[i].reset(self.processors[i].outputs_amt(), self.buffersize);
+        }
+        let outputs = self.output_connections.len();
+        self.graph_output_buffers.reset(outputs, self.buffersize);
+        for events in self.output_events.iter_mut() {
+            events.clear();
+        }
+        for values in self.control_inputs.iter_mut() {
+            for value in values.iter_mut() {
+                *value = F::equilibrium();
+            }
+        }
+        for &((processor, port), default) in &self.unconnected_defaults {
+            let frame = F::from_fn(|_| default.to_sample());
+            if self.processors[processor].input_type(port) == PortType::Control {
+                self.control_inputs[processor][port] = frame;
+            } else {
+                self.input_buffers[processor].fill_ramp(port, frame, frame);
+            }
+        }
+
+        // pass graph input buffers to connected Processors
+        // iterate over all graph input connections
+        for (src, dest) in &self.input_connections {
+            // iterate over all destination input ports
+            for &(dest_proc, dest_port) in dest {
+                let source = &self.graph_input_buffers;
+                // a control input gets the average of the block,
+                // the events of a graph input were passed on in handle_events
+                match self.processors[dest_proc].input_type(dest_port) {
+                    PortType::Audio => {
+                        self.input_buffers[dest_proc].copy_port(dest_port, source, *src)
+                    }
+                    PortType::Control => {
+                        self.control_inputs[dest_proc][dest_port] = source.mean(*src)
+                    }
+                    PortType::Event => {}
+                }
+            }
+        }
+
+        // go through the sorted processors and pass the Frames on
+        for src_processor in &self.topological_sorting {
+            {
+                let processor = &mut self.processors[*src_processor];
+                for (input, events) in self.events[*src_processor].iter_mut().enumerate() {
+                    if !events.is_empty() {
+                        events.sort_by_key(|event| event.time);
+                        processor.handle_events(input, events);
+                        events.clear();
+                    }
+                }
+                // a control input only gets its first Frame
+                for (input, &value) in self.control_inputs[*src_processor].iter().enumerate() {
+                    if processor.input_type(input) == PortType::Control {
+                        self.input_buffers[*src_processor].set_frame(input, 0, value);
+                    }
+                }
+                let inputs = &self.input_buffers[*src_processor];
+                let outputs = &mut self.output_buffers[*src_processor];
+                if processor.processes_planar() {
+                    processor.process_planar(inputs, outputs);
+                } else {
+                    let interleaved = &mut self.interleaved;
+                    interleaved.fit(processor.inputs_amt(), processor.outputs_amt());
+                    inputs.copy_to_interleaved(&mut interleaved.inputs);
+                    processor.process(&interleaved.inputs, &mut interleaved.outputs);
+                    outputs.copy_from_interleaved(&interleaved.outputs);
+                }
+            }
+            // iterate over output ports
+            for src_port in 0..self.processors[*src_processor].outputs_amt() {
+                let output = &self.output_buffers[*src_processor];
+                let control = match self.processors[*src_processor].output_type(src_port) {
+                    PortType::Audio => None,
+                    PortType::Control => {
+                        let current: F = output.frame(src_port, 0);
+                        let entry = self
+                            .control_values
+                            .entry((*src_processor, src_port))
+                            .or_insert((current, current));
+                        *entry = (entry.1, current);
+                        Some(*entry)
+                    }
+                    PortType::Event => {
+                        self.scratch_events.clear();
+                        self.processors[*src_processor]
+                            .take_events(src_port, &mut self.scratch_events);
+                        for dest in self.connections[&(*src_processor, src_port)].keys() {
+                            self.events[dest.0][dest.1].extend_from_slice(&self.scratch_events);
+                        }
+                        for (output, sources) in &self.output_connections {
+                            if sources.contains(&(*src_processor, src_port)) {
+                                self.output_events[*output].extend_from_slice(&self.scratch_events);
+                            }
+                        }
+                        continue;
+                    }
+                };
+                // match for connected inputs
+                if let Some(connected_ports) = self.connections.get_mut(&(*src_processor, src_port))
+                {
+                    // iterate over connected inputs
+                    for (&(dest_processor, dest_port), link) in connected_ports.iter_mut() {
+                        let dest_type = self.processors[dest_processor].input_type(dest_port);
+                        if dest_type == PortType::Control {
+                            // an audio output is averaged over the block
+                            let value = match control {
+                                Some((_, current)) => current,
+                                None => output.mean(src_port),
+                            };
+                            let dest = &mut self.control_inputs[dest_processor][dest_port];
+                            link.mix_control(dest, value, self.buffersize);
+                        } else {
+                            let (source, port) =
+                                convert::<F>(output, src_port, control, &mut self.scratch);
+                            let dest = &mut self.input_buffers[dest_processor];
+                            link.mix(dest, dest_port, source, port);
+                        }
+                    }
+                }
+            }
+        }
+
+        // pass data to graph output buffers
+        for (dest, src) in &self.output_connections {
+            for &(src_proc, src_port) in src {
+                if self.processors[src_proc].output_type(src_port) == PortType::Event {
+                    continue;
+                }
+                let (source, port) = convert::<F>(
+                    &self.output_buffers[src_proc],
+                    src_port,
+                    self.control_values.get(&(src_proc, src_port)).cloned(),
+                    &mut self.scratch,
+                );
+                self.graph_output_buffers.add_port(*dest, source, port);
+            }
+        }
+    }
+}
+
 impl<F> Processor<F> for Graph<F>
 where
     F: Frame,
+    F::Sample: Kernel,
 {
     /// takes an list of input Frames and output Frames,
     /// processes the input and writes it to the outputs list.
     fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
-        self.graph_output_buffers = empty_buffer(self.graph_output_buffers[0].len(), self.buffersize);
-        for i in 0..inputs.len() {
-            for j in 0..inputs[i].len() {
-                self.graph_input_buffers[i][j] = inputs[i][j];
-            }
+        self.graph_input_buffers.copy_from_interleaved(inputs);
+        self.process_graph();
+        self.graph_output_buffers.copy_to_interleaved(outputs);
+    }
+
+    /// like process, without converting the buffers
+    fn process_planar(
+        &mut self,
+        inputs: &PlanarBuffer<F::Sample>,
+        outputs: &mut PlanarBuffer<F::Sample>,
+    ) {
+        for port in 0..inputs.ports().min(self.graph_input_buffers.ports()) {
+            self.graph_input_buffers.copy_port(port, inputs, port);
         }
         self.process_graph();
-        for i in 0..outputs.len() {
-            for j in 0..outputs[i].len() {
-                outputs[i][j] = self.graph_output_buffers[i][j];
+        for port in 0..outputs.ports().min(self.graph_output_buffers.ports()) {
+            outputs.copy_port(port, &self.graph_output_buffers, port);
+        }
+    }
+
+    fn processes_planar(&self) -> bool {
+        true
+    }
+
+    /// a graph input connected to event inputs is an event input
+    fn input_type(&self, input: usize) -> PortType {
+        let events = self.input_connections.get(&input).map_or(false, |ports| {
+            ports.iter().any(|&(processor, port)| {
+                self.processors[processor].input_type(port) == PortType::Event
+            })
+        });
+        if events {
+            PortType::Event
+        } else {
+            PortType::Audio
+        }
+    }
+
+    /// a graph output connected to event outputs is an event output
+    fn output_type(&self, output: usize) -> PortType {
+        let events = self.output_connections.get(&output).map_or(false, |ports| {
+            ports.iter().any(|&(processor, port)| {
+                self.processors[processor].output_type(port) == PortType::Event
+            })
+        });
+        if events {
+            PortType::Event
+        } else {
+            PortType::Audio
+        }
+    }
+
+    /// passes the events on to the event inputs connected to the graph input
+    fn handle_events(&mut self, input: usize, events: &[Event]) {
+        if let Some(ports) = self.input_connections.get(&input) {
+            for &(processor, port) in ports {
+                if self.processors[processor].input_type(port) == PortType::Event {
+                    self.events[processor][port].extend_from_slice(events);
+                }
             }
         }
     }
 
+    /// moves the events of the event outputs connected to the graph output
+    fn take_events(&mut self, output: usize, events: &mut Vec<Event>) {
+        if let Some(taken) = self.output_events.get_mut(output) {
+            events.extend(taken.drain(..));
+        }
+    }
+
     /// returns the amount of inputs
     fn inputs_amt(&self) -> usize {
-        self.input_buffers.len()
+        self.input_connections.len()
     }
 
     /// returns the amount of outputs
     fn outputs_amt(&self) -> usize {
-        self.output_buffers.len()
+        self.output_connections.len()
+    }
+
+    /// returns the longest tail of all paths through the graph
+    fn tail_length(&self) -> usize {
+        self.longest_tail()
     }
 
+    /// returns the longest latency of all paths through the graph
+    fn latency(&self) -> usize {
+        self.longest_latency()
+    }
+
+    /// sets the samplerate of the graph and of all its processors
     fn set_samplerate(&mut self, samplerate: usize) {
         self.samplerate = samplerate;
+        for processor in &mut self.processors {
+            processor.set_samplerate(samplerate);
+        }
     }
 }
 
-fn empty_buffer<F>(inner_size: usize, outer_size: usize) -> BufferSet<F>
+/// Runs a graph offline over a stream of inputs and passes every processed
+/// block to `sink`. Each input item holds one Frame per graph input.
+/// `len` items are taken from `inputs`, missing items are replaced by silence.
+/// Afterwards silence is fed until the tails of all processors have finished.
+/// The first Frames are dropped to compensate the latency of the graph and the
+/// last block is shortened, so `sink` receives `len` plus tail Frames in total.
+///
+/// Panics if the buffersize of the graph is 0.
+pub fn render_blocks<F, I, S>(graph: &mut Graph<F>, inputs: I, len: usize, mut sink: S)
+where
+    F: Frame,
+    F::Sample: Kernel,
+    I: IntoIterator<Item = FrameSet<F>>,
+    S: FnMut(&[FrameSet<F>]),
+{
+    assert!(graph.buffersize() > 0, "can not render a graph with a buffersize of 0");
+    let latency = graph.latency();
+    let total = len + latency + graph.tail_length();
+    let input_amt = graph.inputs_amt();
+    let mut inputs = inputs.into_iter().take(len);
+    let mut input_buffer = empty_buffer(input_amt, graph.buffersize());
+    let mut output_buffer = empty_buffer(graph.outputs_amt(), graph.buffersize());
+    let mut rendered = 0;
+    while rendered < total {
+        for frames in input_buffer.iter_mut() {
+            match inputs.next() {
+                Some(ref input) => {
+                    for (frame, &input) in frames.iter_mut().zip(input.iter()) {
+                        *frame = input;
+                    }
+                }
+                None => {
+                    for frame in frames.iter_mut() {
+                        *frame = F::equilibrium();
+                    }
+                }
+            }
+        }
+        graph.process(&input_buffer, &mut output_buffer);
+        // only the last block is shortened
+        let block = (total - rendered).min(graph.buffersize());
+        // frames that are still inside the latency
+        let skip = latency.saturating_sub(rendered).min(block);
+        if skip < block {
+            sink(&output_buffer[skip..block]);
+        }
+        rendered += block;
+    }
+}
+
+/// Runs a graph offline, feeding `len` Frames from `inputs` into graph input 0.
+/// Returns graph output 0, including the tails of the processors.
+pub fn render<F, I>(graph: &mut Graph<F>, inputs: I, len: usize) -> Vec<F>
+where
+    F: Frame,
+    F::Sample: Kernel,
+    I: IntoIterator<Item = F>,
+{
+    let inputs = first_input(inputs, graph.inputs_amt());
+    let mut result = Vec::new();
+    render_blocks(graph, inputs, len, |outputs| {
+        for frames in outputs {
+            result.push(frames.get(0).cloned().unwrap_or(F::equilibrium()));
+        }
+    });
+    result
+}
+
+/// Like `render`, but writes graph output 0 to a WAV file instead
+/// of keeping it in memory. Returns the amount of Frames written.
+pub fn render_to_wav<F, I, P>(
+    graph: &mut Graph<F>,
+    inputs: I,
+    len: usize,
+    path: P,
+    bits_per_sample: usize,
+    format: SampleFormat,
+) -> Result<usize, WavError>
+where
+    F: Frame,
+    F::Sample: Duplex<f64> + Kernel,
+    I: IntoIterator<Item = F>,
+    P: AsRef<Path>,
+{
+    let spec = WavSpec {
+        channels: F::n_channels(),
+        samplerate: graph.samplerate(),
+        bits_per_sample: bits_per_sample,
+        format: format,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    let inputs = first_input(inputs, graph.inputs_amt());
+    let mut written = 0;
+    let mut result = Ok(());
+    render_blocks(graph, inputs, len, |outputs| {
+        for frames in outputs {
+            if result.is_ok() {
+                let frame = frames.get(0).cloned().unwrap_or(F::equilibrium());
+                result = writer.write_frame(frame);
+                written += 1;
+            }
+        }
+    });
+    result?;
+    writer.finalize()?;
+    Ok(written)
+}
+
+// turns a stream of Frames into FrameSets for a graph with `input_amt` inputs,
+// the Frames go to the first input
+fn first_input<F, I>(inputs: I, input_amt: usize) -> impl Iterator<Item = FrameSet<F>>
+where
+    F: Frame,
+    I: IntoIterator<Item = F>,
+{
+    inputs.into_iter().map(move |frame| {
+        let mut frames = vec![F::equilibrium(); input_amt];
+        if input_amt > 0 {
+            frames[0] = frame;
+        }
+        frames
+    })
+}
+
+/// Create a BufferSet of `outer_size` FrameSets with `inner_size` silent Frames each.
+pub fn empty_buffer<F>(inner_size: usize, outer_size: usize) -> BufferSet<F>
 where
     F: Frame,
 {
     vec![vec![F::equilibrium(); inner_size]; outer_size]
 }
+
+// returns port `port` of `source` in the form an audio input expects,
+// either unchanged or ramped into `scratch` if `control` holds the last
+// and the current value of a control output.
+fn convert<'a, F>(
+    source: &'a PlanarBuffer<F::Sample>,
+    port: usize,
+    control: Option<(F, F)>,
+    scratch: &'a mut PlanarBuffer<F::Sample>,
+) -> (&'a PlanarBuffer<F::Sample>, usize)
+where
+    F: Frame,
+    F::Sample: Kernel,
+{
+    match control {
+        Some((last, current)) => {
+            scratch.fill_ramp(0, last, current);
+            (scratch, 0)
+        }
+        None => (source, port),
+    }
+}
+
+// returns ports in a stable order
+fn sorted<'a, I>(ports: I) -> Vec<PortId>
+where
+    I: IntoIterator<Item = &'a PortId>,
+{
+    let mut ports: Vec<PortId> = ports.into_iter().cloned().collect();
+    ports.sort();
+    ports
+}
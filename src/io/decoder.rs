@@ -0,0 +1,232 @@
+//! Decoders turning audio files into interleaved samples.
+//!
+//! WAV files are always supported, FLAC needs the `flac` feature
+//! and Ogg Vorbis the `vorbis` feature.
+
+#[cfg(feature = "flac")]
+extern crate claxon;
+#[cfg(feature = "vorbis")]
+extern crate lewton;
+extern crate sample;
+
+use self::sample::conv::Duplex;
+use self::sample::{Frame, Sample};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use super::wav::WavReader;
+
+/// Something delivering frames of interleaved samples in the range -1.0 to 1.0.
+pub trait Decoder: Send {
+    /// return the amount of channels
+    fn channels(&self) -> usize;
+
+    /// return the samplerate
+    fn samplerate(&self) -> usize;
+
+    /// Read the next frame into `samples`, which has room for one sample per channel.
+    /// Returns false at the end of the stream.
+    fn read_samples(&mut self, samples: &mut [f64]) -> Result<bool, String>;
+}
+
+/// Open a decoder for the file at the given path, chosen by its extension.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<Decoder>, String> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .unwrap_or(String::new());
+    let file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    match extension.as_str() {
+        "wav" | "wave" => Ok(Box::new(WavReader::new(file).map_err(|e| e.to_string())?)),
+        #[cfg(feature = "flac")]
+        "flac" => Ok(Box::new(FlacDecoder::new(file)?)),
+        #[cfg(feature = "vorbis")]
+        "ogg" | "oga" => Ok(Box::new(VorbisDecoder::new(file)?)),
+        _ => Err(format!("files of type '{}' are not supported", extension)),
+    }
+}
+
+impl<R> Decoder for WavReader<R>
+where
+    R: Read + Send,
+{
+    fn channels(&self) -> usize {
+        self.spec().channels
+    }
+
+    fn samplerate(&self) -> usize {
+        self.spec().samplerate
+    }
+
+    fn read_samples(&mut self, samples: &mut [f64]) -> Result<bool, String> {
+        WavReader::read_samples(self, samples).map_err(|e| e.to_string())
+    }
+}
+
+/// Decodes FLAC files.
+#[cfg(feature = "flac")]
+pub struct FlacDecoder<R>
+where
+    R: Read,
+{
+    reader: claxon::FlacReader<R>,
+    block: claxon::Block,
+    // position in the current block
+    position: u32,
+    scale: f64,
+}
+
+#[cfg(feature = "flac")]
+impl<R> FlacDecoder<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self, String> {
+        let reader = claxon::FlacReader::new(reader).map_err(|e| e.to_string())?;
+        let scale = (1u64 << (reader.streaminfo().bits_per_sample - 1)) as f64;
+        Ok(FlacDecoder {
+            reader: reader,
+            block: claxon::Block::empty(),
+            position: 0,
+            scale: 1.0 / scale,
+        })
+    }
+}
+
+#[cfg(feature = "flac")]
+impl<R> Decoder for FlacDecoder<R>
+where
+    R: Read + Send,
+{
+    fn channels(&self) -> usize {
+        self.reader.streaminfo().channels as usize
+    }
+
+    fn samplerate(&self) -> usize {
+        self.reader.streaminfo().sample_rate as usize
+    }
+
+    fn read_samples(&mut self, samples: &mut [f64]) -> Result<bool, String> {
+        while self.position >= self.block.duration() {
+            let block = ::std::mem::replace(&mut self.block, claxon::Block::empty());
+            let buffer = block.into_buffer();
+            match self.reader.blocks().read_next_or_eof(buffer) {
+                Ok(Some(block)) => self.block = block,
+                Ok(None) => return Ok(false),
+                Err(e) => return Err(e.to_string()),
+            }
+            self.position = 0;
+        }
+        for channel in 0..self.block.channels() {
+            let sample = self.block.sample(channel, self.position);
+            samples[channel as usize] = sample as f64 * self.scale;
+        }
+        self.position += 1;
+        Ok(true)
+    }
+}
+
+/// Decodes Ogg Vorbis files.
+#[cfg(feature = "vorbis")]
+pub struct VorbisDecoder<R>
+where
+    R: Read + ::std::io::Seek,
+{
+    reader: lewton::inside_ogg::OggStreamReader<R>,
+    packet: Vec<f32>,
+    // position in the current packet
+    position: usize,
+}
+
+#[cfg(feature = "vorbis")]
+impl<R> VorbisDecoder<R>
+where
+    R: Read + ::std::io::Seek,
+{
+    pub fn new(reader: R) -> Result<Self, String> {
+        let reader = lewton::inside_ogg::OggStreamReader::new(reader).map_err(|e| e.to_string())?;
+        Ok(VorbisDecoder {
+            reader: reader,
+            packet: Vec::new(),
+            position: 0,
+        })
+    }
+}
+
+#[cfg(feature = "vorbis")]
+impl<R> Decoder for VorbisDecoder<R>
+where
+    R: Read + ::std::io::Seek + Send,
+{
+    fn channels(&self) -> usize {
+        self.reader.ident_hdr.audio_channels as usize
+    }
+
+    fn samplerate(&self) -> usize {
+        self.reader.ident_hdr.audio_sample_rate as usize
+    }
+
+    fn read_samples(&mut self, samples: &mut [f64]) -> Result<bool, String> {
+        let channels = self.channels();
+        while self.position >= self.packet.len() {
+            let packet = self
+                .reader
+                .read_dec_packet_generic::<lewton::samples::InterleavedSamples<f32>>();
+            match packet {
+                Ok(Some(packet)) => self.packet = packet.samples,
+                Ok(None) => return Ok(false),
+                Err(e) => return Err(e.to_string()),
+            }
+            self.position = 0;
+        }
+        for channel in 0..channels {
+            samples[channel] = self.packet[self.position + channel] as f64;
+        }
+        self.position += channels;
+        Ok(true)
+    }
+}
+
+/// Decode a whole file into memory. Returns the frames and the samplerate.
+/// Channel `i` of the frame gets channel `i % channels` of the file.
+pub fn read_file<F, P>(path: P) -> Result<(Vec<F>, usize), String>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+    P: AsRef<Path>,
+{
+    let mut decoder = open(path)?;
+    let channels = decoder.channels();
+    let mut samples = vec![0.0; channels];
+    let mut frames = Vec::new();
+    while decoder.read_samples(&mut samples)? {
+        frames.push(to_frame(&samples));
+    }
+    Ok((frames, decoder.samplerate()))
+}
+
+/// Decode a whole file into one Vec of samples per channel,
+/// for files whose channel count does not fit a Frame type, like impulse responses.
+/// Returns the channels and the samplerate.
+pub fn read_channels<P: AsRef<Path>>(path: P) -> Result<(Vec<Vec<f64>>, usize), String> {
+    let mut decoder = open(path)?;
+    let mut samples = vec![0.0; decoder.channels()];
+    let mut channels = vec![Vec::new(); decoder.channels()];
+    while decoder.read_samples(&mut samples)? {
+        for (channel, &sample) in channels.iter_mut().zip(samples.iter()) {
+            channel.push(sample);
+        }
+    }
+    Ok((channels, decoder.samplerate()))
+}
+
+// channel `i` of the frame gets sample `i % samples.len()`
+fn to_frame<F>(samples: &[f64]) -> F
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    F::from_fn(|channel| F::Sample::from_sample(samples[channel % samples.len()]))
+}
@@ -0,0 +1,48 @@
+//! Reading and writing audio files and streams.
+
+pub mod decoder;
+pub mod player;
+pub mod raw;
+pub mod ringbuffer;
+pub mod wav;
+mod tests;
+
+// decodes one little endian sample to the range -1.0 to 1.0
+fn decode_sample(raw: &[u8], float: bool) -> f64 {
+    match (float, raw.len()) {
+        (false, 1) => (raw[0] as f64 - 128.0) / 128.0,
+        (false, 2) => i16::from_le_bytes([raw[0], raw[1]]) as f64 / 32_768.0,
+        (false, 3) => {
+            // shift up to sign extend the 24 bit value
+            (i32::from_le_bytes([0, raw[0], raw[1], raw[2]]) >> 8) as f64 / 8_388_608.0
+        }
+        (false, _) => {
+            i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64 / 2_147_483_648.0
+        }
+        (true, 4) => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+        (true, _) => f64::from_le_bytes([
+            raw[0], raw[1], raw[2], raw[3], raw[4], raw[5], raw[6], raw[7],
+        ]),
+    }
+}
+
+// encodes one sample in the range -1.0 to 1.0 as little endian bytes,
+// integer samples get clipped
+fn encode_sample(sample: f64, float: bool, bits: usize, out: &mut Vec<u8>) {
+    if float {
+        if bits == 32 {
+            out.extend_from_slice(&(sample as f32).to_le_bytes());
+        } else {
+            out.extend_from_slice(&sample.to_le_bytes());
+        }
+        return;
+    }
+    let max = (1i64 << (bits - 1)) as f64;
+    let value = (sample * max).round().max(-max).min(max - 1.0) as i64;
+    match bits {
+        8 => out.push((value + 128) as u8),
+        16 => out.extend_from_slice(&(value as i16).to_le_bytes()),
+        24 => out.extend_from_slice(&(value as i32).to_le_bytes()[..3]),
+        _ => out.extend_from_slice(&(value as i32).to_le_bytes()),
+    }
+}
@@ -0,0 +1,206 @@
+//! Playing files which are decoded while playing.
+
+extern crate sample;
+
+use graph::BufferSet;
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::{Frame, Sample};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use super::decoder::{self, Decoder};
+use super::ringbuffer::{ring_buffer, Consumer, Producer};
+
+// about one and a half seconds at 44100Hz
+const BUFFER_FRAMES: usize = 1 << 16;
+
+// state shared between the processor and the disk thread
+struct State {
+    stop: AtomicBool,
+    finished: AtomicBool,
+    error: Mutex<Option<String>>,
+}
+
+/// A Processor playing a file which gets decoded by a disk thread while playing.
+/// The decoded frames are passed on through a lock free ring buffer,
+/// so `process` never waits for the disk. If the disk thread can not keep up,
+/// silence is played. It has no inputs and one output.
+///
+/// Which files can be played depends on the enabled features, see `io::decoder`.
+/// For offline rendering, which runs faster than the disk thread,
+/// decode the whole file with `decoder::read_file` and play it with a `WavPlayer`.
+/// The file is not resampled, a `WavPlayer` also resamples it to the samplerate of the Graph.
+pub struct FilePlayer<F> {
+    consumer: Consumer<F>,
+    state: Arc<State>,
+    thread: Option<JoinHandle<()>>,
+    samplerate: usize,
+}
+
+impl<F> FilePlayer<F>
+where
+    F: Frame + Send + 'static,
+    F::Sample: Duplex<f64>,
+{
+    /// Open a file and start decoding it. If `looping` is true,
+    /// the file starts again after its end.
+    /// Returns after the buffer has been filled for the first time.
+    pub fn open<P: AsRef<Path>>(path: P, looping: bool) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let decoder = decoder::open(&path)?;
+        let path = if looping { Some(path) } else { None };
+        Ok(FilePlayer::start(decoder, path))
+    }
+
+    /// Play from a decoder. Looping is not possible, because
+    /// the decoder can not be restarted.
+    pub fn from_decoder(decoder: Box<Decoder>) -> Self {
+        FilePlayer::start(decoder, None)
+    }
+
+    // `path` is only given for looping
+    fn start(decoder: Box<Decoder>, path: Option<PathBuf>) -> Self {
+        let samplerate = decoder.samplerate();
+        let (producer, consumer) = ring_buffer(BUFFER_FRAMES, F::equilibrium());
+        let state = Arc::new(State {
+            stop: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            error: Mutex::new(None),
+        });
+        // fill the buffer before playing starts
+        let mut disk = Disk {
+            decoder: decoder,
+            path: path,
+            decoded: false,
+            producer: producer,
+            state: state.clone(),
+        };
+        let running = disk.fill();
+        let thread = if running {
+            Some(thread::spawn(move || disk.run()))
+        } else {
+            None
+        };
+        FilePlayer {
+            consumer: consumer,
+            state: state,
+            thread: thread,
+            samplerate: samplerate,
+        }
+    }
+
+    /// returns the samplerate of the file
+    pub fn samplerate(&self) -> usize {
+        self.samplerate
+    }
+
+    /// returns true if the whole file has been played
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::Acquire) && self.consumer.len() == 0
+    }
+
+    /// returns the error which stopped decoding, if there was one
+    pub fn error(&self) -> Option<String> {
+        self.state.error.lock().unwrap().clone()
+    }
+}
+
+impl<F> Drop for FilePlayer<F> {
+    fn drop(&mut self) {
+        self.state.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<F> Processor<F> for FilePlayer<F>
+where
+    F: Frame,
+{
+    fn process(&mut self, _inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        for output in outputs.iter_mut() {
+            output[0] = self.consumer.pop().unwrap_or(F::equilibrium());
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        0
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+// the part of the player living in the disk thread
+struct Disk<F> {
+    decoder: Box<Decoder>,
+    // the file is opened again at its end if a path is given
+    path: Option<PathBuf>,
+    // an empty file must not be opened again and again,
+    // so it is only reopened if a frame was decoded since the last (re)open
+    decoded: bool,
+    producer: Producer<F>,
+    state: Arc<State>,
+}
+
+impl<F> Disk<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn run(mut self) {
+        while !self.state.stop.load(Ordering::Relaxed) {
+            if !self.fill() {
+                return;
+            }
+            thread::park_timeout(Duration::from_millis(10));
+        }
+    }
+
+    // decodes until the buffer is full, returns false once decoding has finished
+    fn fill(&mut self) -> bool {
+        let mut samples = vec![0.0; self.decoder.channels()];
+        while self.producer.free() > 0 {
+            match self.decoder.read_samples(&mut samples) {
+                Ok(true) => {
+                    self.decoded = true;
+                    let channels = samples.len();
+                    let frame =
+                        F::from_fn(|channel| F::Sample::from_sample(samples[channel % channels]));
+                    let _ = self.producer.push(frame);
+                }
+                Ok(false) => {
+                    let reopened = match self.path {
+                        Some(ref path) if self.decoded => decoder::open(path),
+                        _ => return self.finish(None),
+                    };
+                    self.decoded = false;
+                    match reopened {
+                        Ok(decoder) => self.decoder = decoder,
+                        Err(message) => return self.finish(Some(message)),
+                    }
+                }
+                Err(message) => return self.finish(Some(message)),
+            }
+        }
+        true
+    }
+
+    fn finish(&self, error: Option<String>) -> bool {
+        if error.is_some() {
+            *self.state.error.lock().unwrap() = error;
+        }
+        self.state.finished.store(true, Ordering::Release);
+        false
+    }
+}
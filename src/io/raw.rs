@@ -0,0 +1,210 @@
+//! Streaming interleaved raw PCM, e.g. over stdin and stdout.
+//! There is no header, so the format, the samplerate and the amount
+//! of channels have to be known by both sides.
+
+extern crate sample;
+
+use graph::{empty_buffer, Graph};
+use processor::Processor;
+use self::sample::conv::Duplex;
+use simd::Kernel;
+use self::sample::{Frame, Sample};
+use std::io::{self, Read, Write};
+use super::{decode_sample, encode_sample};
+
+/// The sample formats of raw PCM streams, named like in sox and ffmpeg.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawFormat {
+    S16Le,
+    S24Le,
+    F32Le,
+}
+
+impl RawFormat {
+    /// Parse a format name like "s16le".
+    pub fn from_name(name: &str) -> Option<RawFormat> {
+        match name {
+            "s16le" => Some(RawFormat::S16Le),
+            "s24le" => Some(RawFormat::S24Le),
+            "f32le" => Some(RawFormat::F32Le),
+            _ => None,
+        }
+    }
+
+    fn bits(&self) -> usize {
+        match *self {
+            RawFormat::S16Le => 16,
+            RawFormat::S24Le => 24,
+            RawFormat::F32Le => 32,
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        *self == RawFormat::F32Le
+    }
+}
+
+/// Reads interleaved frames from a raw PCM stream.
+/// Every channel of the frame is read from the stream.
+pub struct RawReader<R> {
+    reader: R,
+    format: RawFormat,
+    frame_bytes: Vec<u8>,
+}
+
+impl<R> RawReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R, format: RawFormat) -> Self {
+        RawReader {
+            reader: reader,
+            format: format,
+            frame_bytes: Vec::new(),
+        }
+    }
+
+    /// Read the next frame, or None at the end of the stream.
+    /// A stream ending in the middle of a frame is an error.
+    pub fn read_frame<F>(&mut self) -> io::Result<Option<F>>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let bytes = self.format.bits() / 8;
+        self.frame_bytes.resize(F::n_channels() * bytes, 0);
+        let mut filled = 0;
+        while filled < self.frame_bytes.len() {
+            match self.reader.read(&mut self.frame_bytes[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "stream ended in the middle of a frame",
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let float = self.format.is_float();
+        let frame_bytes = &self.frame_bytes;
+        Ok(Some(F::from_fn(|channel| {
+            let raw = &frame_bytes[channel * bytes..(channel + 1) * bytes];
+            F::Sample::from_sample(decode_sample(raw, float))
+        })))
+    }
+}
+
+/// Writes interleaved frames to a raw PCM stream.
+pub struct RawWriter<W> {
+    writer: W,
+    format: RawFormat,
+    buffer: Vec<u8>,
+}
+
+impl<W> RawWriter<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W, format: RawFormat) -> Self {
+        RawWriter {
+            writer: writer,
+            format: format,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Write one frame. Integer samples outside -1.0 to 1.0 get clipped.
+    pub fn write_frame<F>(&mut self, frame: F) -> io::Result<()>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        self.buffer.clear();
+        for sample in frame.channels() {
+            encode_sample(
+                sample.to_sample::<f64>(),
+                self.format.is_float(),
+                self.format.bits(),
+                &mut self.buffer,
+            );
+        }
+        self.writer.write_all(&self.buffer)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Streams raw PCM from `reader` through graph input 0 and writes
+/// graph output 0 to `writer`, one block at a time, so memory stays constant
+/// no matter how long the stream is. The writer gets flushed after every block.
+/// After the end of the input the tails of the processors are written too.
+/// The first Frames are dropped to compensate the latency of the graph.
+/// Returns the amount of Frames written.
+pub fn stream<F, R, W>(
+    graph: &mut Graph<F>,
+    reader: R,
+    writer: W,
+    format: RawFormat,
+) -> io::Result<usize>
+where
+    F: Frame,
+    F::Sample: Duplex<f64> + Kernel,
+    R: Read,
+    W: Write,
+{
+    let mut reader = RawReader::new(reader, format);
+    let mut writer = RawWriter::new(writer, format);
+    let mut inputs = empty_buffer(graph.inputs_amt(), graph.buffersize());
+    let mut outputs = empty_buffer(graph.outputs_amt(), graph.buffersize());
+    let mut written = 0;
+    // frames of the output which are still inside the latency
+    let mut skip = graph.latency();
+    // frames of the tail which still have to be written after the end of the input
+    let mut tail = None;
+    while tail != Some(0) {
+        let mut block = 0;
+        for frames in inputs.iter_mut() {
+            let frame = if tail.is_none() { reader.read_frame()? } else { None };
+            match frame {
+                Some(frame) => {
+                    block += 1;
+                    if let Some(input) = frames.get_mut(0) {
+                        *input = frame;
+                    }
+                }
+                None => {
+                    if tail.is_none() {
+                        tail = Some(graph.latency() + graph.tail_length());
+                    }
+                    for input in frames.iter_mut() {
+                        *input = F::equilibrium();
+                    }
+                }
+            }
+        }
+        if let Some(remaining) = tail {
+            let from_tail = remaining.min(graph.buffersize() - block);
+            block += from_tail;
+            tail = Some(remaining - from_tail);
+        }
+        graph.process(&inputs, &mut outputs);
+        let skipped = skip.min(block);
+        skip -= skipped;
+        for frames in &outputs[skipped..block] {
+            writer.write_frame(frames.get(0).cloned().unwrap_or(F::equilibrium()))?;
+        }
+        writer.flush()?;
+        written += block - skipped;
+    }
+    Ok(written)
+}
@@ -0,0 +1,101 @@
+//! A lock free ring buffer for passing values from one thread to another.
+//! There is exactly one Producer and one Consumer, neither of them ever blocks.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Shared<T> {
+    slots: Box<[UnsafeCell<T>]>,
+    // total amount of values popped, only written by the Consumer
+    read: AtomicUsize,
+    // total amount of values pushed, only written by the Producer
+    written: AtomicUsize,
+}
+
+// the Producer only writes to slots the Consumer does not read and vice versa
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn len(&self) -> usize {
+        self.written
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.read.load(Ordering::Acquire))
+    }
+}
+
+/// The writing end of a ring buffer.
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The reading end of a ring buffer.
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Create a ring buffer with room for `capacity` values.
+/// `initial` is used to fill the slots, it is never popped.
+pub fn ring_buffer<T>(capacity: usize, initial: T) -> (Producer<T>, Consumer<T>)
+where
+    T: Copy + Send,
+{
+    let slots: Vec<UnsafeCell<T>> = (0..capacity).map(|_| UnsafeCell::new(initial)).collect();
+    let shared = Arc::new(Shared {
+        slots: slots.into_boxed_slice(),
+        read: AtomicUsize::new(0),
+        written: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared: shared },
+    )
+}
+
+impl<T> Producer<T>
+where
+    T: Copy,
+{
+    /// Add a value, or give it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let shared = &self.shared;
+        if shared.len() == shared.slots.len() {
+            return Err(value);
+        }
+        let written = shared.written.load(Ordering::Relaxed);
+        unsafe {
+            *shared.slots[written % shared.slots.len()].get() = value;
+        }
+        shared.written.store(written.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// returns the amount of values which can be pushed right now
+    pub fn free(&self) -> usize {
+        self.shared.slots.len() - self.shared.len()
+    }
+}
+
+impl<T> Consumer<T>
+where
+    T: Copy,
+{
+    /// Remove the oldest value, or None if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        let shared = &self.shared;
+        if shared.len() == 0 {
+            return None;
+        }
+        let read = shared.read.load(Ordering::Relaxed);
+        let value = unsafe { *shared.slots[read % shared.slots.len()].get() };
+        shared.read.store(read.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// returns the amount of values which can be popped right now
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+}
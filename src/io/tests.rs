@@ -0,0 +1,283 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::super::builder::GraphBuilder;
+    use super::super::super::processor::Processor;
+    use super::super::player::FilePlayer;
+    use super::super::ringbuffer::ring_buffer;
+    use super::super::raw::{stream, RawFormat, RawReader, RawWriter};
+    use super::super::wav::{SampleFormat, WavPlayer, WavReader, WavRecorder, WavSpec, WavWriter};
+    use std::env;
+    use std::io::Cursor;
+    use std::thread;
+    use std::time::Duration;
+
+    fn round_trip(
+        bits_per_sample: usize,
+        format: SampleFormat,
+        channels: usize,
+    ) -> (WavSpec, Vec<[f64; 2]>) {
+        let spec = WavSpec {
+            channels: channels,
+            samplerate: 48_000,
+            bits_per_sample: bits_per_sample,
+            format: format,
+        };
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), spec).unwrap();
+        for &value in &[0.0, 0.5, -0.5, -1.0, 0.25] {
+            let samples: Vec<f64> = (0..channels)
+                .map(|c| if c == 1 { -value } else { value })
+                .collect();
+            writer.write_samples(&samples).unwrap();
+        }
+        let bytes = writer.finalize().unwrap().into_inner();
+        let mut reader = WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.remaining(), 5);
+        let spec = reader.spec();
+        (spec, reader.read_frames::<[f64; 2]>().unwrap())
+    }
+
+    #[test]
+    fn wav_round_trip_test() {
+        let formats = [
+            (8, SampleFormat::Int),
+            (16, SampleFormat::Int),
+            (24, SampleFormat::Int),
+            (32, SampleFormat::Int),
+            (32, SampleFormat::Float),
+            (64, SampleFormat::Float),
+        ];
+        for &(bits, format) in &formats {
+            let (spec, frames) = round_trip(bits, format, 2);
+            assert_eq!(spec.bits_per_sample, bits);
+            assert_eq!(spec.format, format);
+            assert_eq!(frames[1], [0.5, -0.5]);
+            // positive full scale can not be represented by integers
+            let max = match format {
+                SampleFormat::Int => 1.0 - 1.0 / (1u64 << (bits - 1)) as f64,
+                SampleFormat::Float => 1.0,
+            };
+            assert_eq!(frames[3], [-1.0, max]);
+            assert_eq!(frames[4][0], 0.25);
+        }
+    }
+
+    #[test]
+    fn wav_extensible_test() {
+        // more than two channels are written with an extensible header
+        let (spec, frames) = round_trip(24, SampleFormat::Int, 6);
+        assert_eq!(spec.channels, 6);
+        assert_eq!(frames[2], [-0.5, 0.5]);
+        // a mono file fills all channels of the frame
+        let (spec, frames) = round_trip(16, SampleFormat::Int, 1);
+        assert_eq!(spec.channels, 1);
+        assert_eq!(frames[1], [0.5, 0.5]);
+    }
+
+    #[test]
+    fn wav_invalid_test() {
+        assert!(WavReader::new(Cursor::new(b"RIFX\0\0\0\0WAVE".to_vec())).is_err());
+        let spec = WavSpec {
+            channels: 2,
+            samplerate: 48_000,
+            bits_per_sample: 12,
+            format: SampleFormat::Int,
+        };
+        assert!(WavWriter::new(Cursor::new(Vec::new()), spec).is_err());
+    }
+
+    #[test]
+    fn player_recorder_test() {
+        let frames = vec![[0.1, 0.2], [0.3, 0.4], [0.5, 0.6]];
+        let recorder = WavRecorder::new();
+        let recording = recorder.recording();
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(2)
+            .samplerate(48_000)
+            .node("player", WavPlayer::from_frames(frames.clone(), 48_000))
+            .node("recorder", recorder)
+            .connect("player", "recorder")
+            .output(0, "recorder")
+            .build()
+            .unwrap();
+        let input = vec![vec![], vec![]];
+        let mut output = vec![vec![[0.0; 2]]; 2];
+        graph.process(&input, &mut output);
+        assert_eq!(output, vec![vec![[0.1, 0.2]], vec![[0.3, 0.4]]]);
+        graph.process(&input, &mut output);
+        assert_eq!(output, vec![vec![[0.5, 0.6]], vec![[0.0, 0.0]]]);
+        assert_eq!(recording.len(), 4);
+        assert_eq!(recording.frames()[..3], frames[..]);
+    }
+
+    #[test]
+    fn recorder_capacity_test() {
+        let mut recorder = WavRecorder::with_capacity(3);
+        let recording = recorder.recording();
+        let inputs: Vec<Vec<[f32; 1]>> = (0..4).map(|i| vec![[i as f32]]).collect();
+        let mut outputs = vec![vec![[0.0]]; 4];
+        recorder.process(&inputs, &mut outputs);
+        // the input is passed on even if it does not fit into the buffer
+        assert_eq!(outputs, inputs);
+        assert_eq!(recording.dropped(), 1);
+        assert_eq!(recording.take(), vec![[0.0], [1.0], [2.0]]);
+        // reading the recording makes room again
+        recorder.process(&inputs[..2].to_vec(), &mut outputs);
+        assert_eq!(recording.frames(), vec![[0.0], [1.0]]);
+        assert_eq!(recording.dropped(), 1);
+    }
+
+    #[test]
+    fn player_resample_test() {
+        let frames: Vec<[f32; 1]> = (0..2205).map(|i| [(i as f32 * 0.05).sin()]).collect();
+        let mut player = WavPlayer::from_frames(frames.clone(), 22_050);
+        player.set_samplerate(44_100);
+        assert_eq!(player.len(), 4410);
+        let mut output = vec![vec![[0.0]]; 4410];
+        player.process(&vec![vec![]; 4410], &mut output);
+        // every second frame is one of the file
+        assert!((output[2000][0][0] - frames[1000][0]).abs() < 1e-3);
+        // back to the samplerate of the file, the original frames are played again
+        player.set_samplerate(22_050);
+        assert_eq!(player.len(), 2205);
+    }
+
+    #[test]
+    fn raw_round_trip_test() {
+        for &format in &[RawFormat::S16Le, RawFormat::S24Le, RawFormat::F32Le] {
+            let mut writer = RawWriter::new(Vec::new(), format);
+            writer.write_frame([0.5f32, -0.25]).unwrap();
+            writer.write_frame([-1.0f32, 0.0]).unwrap();
+            let bytes = writer.into_inner();
+            let mut reader = RawReader::new(Cursor::new(bytes), format);
+            assert_eq!(reader.read_frame::<[f32; 2]>().unwrap(), Some([0.5, -0.25]));
+            assert_eq!(reader.read_frame::<[f32; 2]>().unwrap(), Some([-1.0, 0.0]));
+            assert_eq!(reader.read_frame::<[f32; 2]>().unwrap(), None);
+        }
+        // a stream ending in the middle of a frame
+        let mut reader = RawReader::new(Cursor::new(vec![0u8; 3]), RawFormat::S16Le);
+        assert!(reader.read_frame::<[f32; 2]>().is_err());
+    }
+
+    #[test]
+    fn raw_stream_test() {
+        let mut graph = GraphBuilder::<[f32; 1]>::new()
+            .buffersize(4)
+            .node("recorder", WavRecorder::new())
+            .input(0, "recorder")
+            .output(0, "recorder")
+            .build()
+            .unwrap();
+        let mut input = RawWriter::new(Vec::new(), RawFormat::S16Le);
+        for i in 0..6 {
+            input.write_frame([i as f32 / 8.0]).unwrap();
+        }
+        let input = input.into_inner();
+        let mut output = Vec::new();
+        let written = stream(&mut graph, Cursor::new(&input), &mut output, RawFormat::S16Le).unwrap();
+        assert_eq!(written, 6);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn ring_buffer_test() {
+        let (mut producer, mut consumer) = ring_buffer(3, 0);
+        assert_eq!(consumer.pop(), None);
+        for i in 1..4 {
+            producer.push(i).unwrap();
+        }
+        assert_eq!(producer.push(4), Err(4));
+        assert_eq!(consumer.pop(), Some(1));
+        producer.push(4).unwrap();
+        assert_eq!(consumer.len(), 3);
+        let values: Vec<i32> = (0..4).map(|_| consumer.pop().unwrap_or(0)).collect();
+        assert_eq!(values, vec![2, 3, 4, 0]);
+        assert_eq!(producer.free(), 3);
+
+        let (mut producer, mut consumer) = ring_buffer(16, 0usize);
+        let thread = thread::spawn(move || {
+            for i in 0..10_000 {
+                while producer.push(i).is_err() {
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < 10_000 {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn file_player_test() {
+        let path = env::temp_dir().join("pcm_flow_file_player_test.wav");
+        let spec = WavSpec {
+            channels: 1,
+            samplerate: 22_050,
+            bits_per_sample: 16,
+            format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        for i in 0..100 {
+            writer.write_samples(&[i as f64 / 128.0]).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut player = FilePlayer::<[f32; 2]>::open(&path, false).unwrap();
+        assert_eq!(player.samplerate(), 22_050);
+        let input = vec![vec![]; 64];
+        let mut output = vec![vec![[0.0; 2]]; 64];
+        player.process(&input, &mut output);
+        assert_eq!(output[10][0], [10.0 / 128.0; 2]);
+        player.process(&input, &mut output);
+        assert_eq!(output[99 - 64][0], [99.0 / 128.0; 2]);
+        assert_eq!(output[100 - 64][0], [0.0; 2]);
+        assert!(player.is_finished());
+
+        // a looping player starts from the beginning again
+        let mut player = FilePlayer::<[f32; 2]>::open(&path, true).unwrap();
+        player.process(&input, &mut output);
+        player.process(&input, &mut output);
+        assert_eq!(output[100 - 64][0], [0.0; 2]);
+        assert_eq!(output[101 - 64][0], [1.0 / 128.0; 2]);
+        assert!(!player.is_finished());
+    }
+
+    #[test]
+    fn file_player_loop_full_buffer_test() {
+        // the file fills the ring buffer exactly, so its end is only
+        // reached by a later fill of the disk thread
+        let path = env::temp_dir().join("pcm_flow_file_player_loop_test.wav");
+        let spec = WavSpec {
+            channels: 1,
+            samplerate: 22_050,
+            bits_per_sample: 16,
+            format: SampleFormat::Int,
+        };
+        let frames = 1 << 16;
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        for i in 0..frames {
+            writer.write_samples(&[(i % 128) as f64 / 128.0]).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut player = FilePlayer::<[f32; 2]>::open(&path, true).unwrap();
+        let input = vec![vec![]; 64];
+        let mut output = vec![vec![[0.0; 2]]; 64];
+        player.process(&input, &mut output);
+        // give the disk thread time to reach the end of the file and open it again
+        thread::sleep(Duration::from_millis(100));
+        let input = vec![vec![]; frames];
+        let mut output = vec![vec![[0.0; 2]]; frames];
+        player.process(&input, &mut output);
+        for i in 0..64 {
+            assert_eq!(output[frames - 64 + i][0], [(i % 128) as f32 / 128.0; 2]);
+        }
+        assert!(!player.is_finished());
+        assert_eq!(player.error(), None);
+    }
+}
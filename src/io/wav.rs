@@ -0,0 +1,743 @@
+//! Reading and writing PCM WAV files.
+//!
+//! Supported are 8, 16, 24 and 32 bit integer samples, 32 and 64 bit
+//! float samples and the WAVE_FORMAT_EXTENSIBLE header.
+//! Samples are converted to and from any `sample::Frame` type.
+
+extern crate sample;
+
+use graph::BufferSet;
+use processor::{PortInfo, PortKind, Processor};
+use processors::resample::{resample_frames, Quality};
+use super::{decode_sample, encode_sample};
+use super::ringbuffer::{ring_buffer, Consumer, Producer};
+use self::sample::conv::Duplex;
+use self::sample::{Frame, Sample};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// about 24 seconds at 44100Hz
+const RECORD_FRAMES: usize = 1 << 20;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+// the part of the KSDATAFORMAT_SUBTYPE GUIDs following the format tag
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Whether the samples are stored as integers or floats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleFormat {
+    Int,
+    Float,
+}
+
+/// The format of a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavSpec {
+    pub channels: usize,
+    pub samplerate: usize,
+    pub bits_per_sample: usize,
+    pub format: SampleFormat,
+}
+
+impl WavSpec {
+    fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample / 8
+    }
+
+    fn validate(&self) -> Result<(), WavError> {
+        let valid = match self.format {
+            SampleFormat::Int => [8, 16, 24, 32].contains(&self.bits_per_sample),
+            SampleFormat::Float => [32, 64].contains(&self.bits_per_sample),
+        };
+        if !valid {
+            return Err(WavError::Format(format!(
+                "{} bit {:?} samples are not supported",
+                self.bits_per_sample,
+                self.format
+            )));
+        }
+        if self.channels == 0 || self.channels > u16::max_value() as usize {
+            return Err(WavError::Format(format!("{} channels are not supported", self.channels)));
+        }
+        Ok(())
+    }
+}
+
+/// An error while reading or writing a WAV file.
+#[derive(Debug)]
+pub enum WavError {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WavError::Io(ref e) => write!(f, "io error: {}", e),
+            WavError::Format(ref message) => write!(f, "invalid wav file: {}", message),
+        }
+    }
+}
+
+impl Error for WavError {}
+
+impl From<io::Error> for WavError {
+    fn from(e: io::Error) -> Self {
+        WavError::Io(e)
+    }
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn skip<R: Read>(reader: &mut R, amount: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(amount), &mut io::sink())?;
+    if skipped < amount {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "chunk is truncated"));
+    }
+    Ok(())
+}
+
+/// Reads frames from a WAV file.
+pub struct WavReader<R> {
+    reader: R,
+    spec: WavSpec,
+    // amount of frames not read yet
+    remaining: usize,
+    // one interleaved frame of raw sample bytes
+    frame_bytes: Vec<u8>,
+}
+
+impl WavReader<BufReader<File>> {
+    /// Open the WAV file at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WavError> {
+        WavReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> WavReader<R>
+where
+    R: Read,
+{
+    /// Read the header from the reader. Afterwards the reader is positioned
+    /// at the first sample.
+    pub fn new(mut reader: R) -> Result<Self, WavError> {
+        let mut id = [0; 4];
+        reader.read_exact(&mut id)?;
+        if &id != b"RIFF" {
+            return Err(WavError::Format("no RIFF header".to_string()));
+        }
+        read_u32(&mut reader)?;
+        reader.read_exact(&mut id)?;
+        if &id != b"WAVE" {
+            return Err(WavError::Format("no WAVE header".to_string()));
+        }
+
+        let mut spec = None;
+        loop {
+            reader.read_exact(&mut id)?;
+            let size = read_u32(&mut reader)? as u64;
+            // chunks are padded to an even size
+            let padded = size + (size & 1);
+            match &id {
+                b"fmt " => {
+                    if size < 16 {
+                        return Err(WavError::Format("fmt chunk is too short".to_string()));
+                    }
+                    let mut tag = read_u16(&mut reader)?;
+                    let channels = read_u16(&mut reader)? as usize;
+                    let samplerate = read_u32(&mut reader)? as usize;
+                    read_u32(&mut reader)?;
+                    read_u16(&mut reader)?;
+                    let bits_per_sample = read_u16(&mut reader)? as usize;
+                    let mut read = 16;
+                    if tag == WAVE_FORMAT_EXTENSIBLE {
+                        if size < 40 {
+                            return Err(WavError::Format(
+                                "extensible fmt chunk is too short".to_string(),
+                            ));
+                        }
+                        // cbSize, valid bits per sample and channel mask
+                        read_u16(&mut reader)?;
+                        read_u16(&mut reader)?;
+                        read_u32(&mut reader)?;
+                        let mut guid = [0; 16];
+                        reader.read_exact(&mut guid)?;
+                        if guid[2..] != SUBFORMAT_GUID_TAIL {
+                            return Err(WavError::Format("unknown sub format".to_string()));
+                        }
+                        tag = u16::from_le_bytes([guid[0], guid[1]]);
+                        read = 40;
+                    }
+                    let format = match tag {
+                        WAVE_FORMAT_PCM => SampleFormat::Int,
+                        WAVE_FORMAT_IEEE_FLOAT => SampleFormat::Float,
+                        _ => {
+                            return Err(WavError::Format(format!(
+                                "format tag {:#x} is not supported",
+                                tag
+                            )))
+                        }
+                    };
+                    skip(&mut reader, padded - read)?;
+                    let new_spec = WavSpec {
+                        channels: channels,
+                        samplerate: samplerate,
+                        bits_per_sample: bits_per_sample,
+                        format: format,
+                    };
+                    new_spec.validate()?;
+                    spec = Some(new_spec);
+                }
+                b"data" => {
+                    let spec = match spec {
+                        Some(spec) => spec,
+                        None => {
+                            return Err(WavError::Format(
+                                "data chunk before fmt chunk".to_string(),
+                            ))
+                        }
+                    };
+                    let frame_size = spec.channels * spec.bytes_per_sample();
+                    return Ok(WavReader {
+                        reader: reader,
+                        spec: spec,
+                        remaining: size as usize / frame_size,
+                        frame_bytes: vec![0; frame_size],
+                    });
+                }
+                _ => skip(&mut reader, padded)?,
+            }
+        }
+    }
+
+    /// returns the format of the file
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// returns the amount of frames which have not been read yet
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Read the next frame as interleaved samples in the range -1.0 to 1.0.
+    /// `samples` must have room for one sample per channel of the file.
+    /// Returns false at the end of the file.
+    pub fn read_samples(&mut self, samples: &mut [f64]) -> Result<bool, WavError> {
+        if self.remaining == 0 {
+            return Ok(false);
+        }
+        self.reader.read_exact(&mut self.frame_bytes)?;
+        self.remaining -= 1;
+        let bytes = self.spec.bytes_per_sample();
+        for (channel, raw) in self.frame_bytes.chunks(bytes).enumerate() {
+            samples[channel] = decode_sample(raw, self.spec.format == SampleFormat::Float);
+        }
+        Ok(true)
+    }
+
+    /// Read the next frame, or None at the end of the file.
+    /// Channel `i` of the frame gets channel `i % channels` of the file,
+    /// so a mono file fills all channels of the frame.
+    pub fn read_frame<F>(&mut self) -> Result<Option<F>, WavError>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let mut samples = vec![0.0; self.spec.channels];
+        if !self.read_samples(&mut samples)? {
+            return Ok(None);
+        }
+        let channels = self.spec.channels;
+        Ok(Some(F::from_fn(|channel| {
+            F::Sample::from_sample(samples[channel % channels])
+        })))
+    }
+
+    /// Read all remaining frames.
+    pub fn read_frames<F>(&mut self) -> Result<Vec<F>, WavError>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let mut frames = Vec::with_capacity(self.remaining);
+        while let Some(frame) = self.read_frame()? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+}
+
+// the default speaker positions for common channel counts
+fn channel_mask(channels: usize) -> u32 {
+    match channels {
+        1 => 0x4,
+        2 => 0x3,
+        4 => 0x33,
+        6 => 0x3F,
+        8 => 0x63F,
+        _ => 0,
+    }
+}
+
+/// Writes frames to a WAV file.
+/// `finalize` has to be called after the last frame,
+/// otherwise the header contains wrong sizes.
+pub struct WavWriter<W>
+where
+    W: Write + Seek,
+{
+    writer: W,
+    spec: WavSpec,
+    // position of the RIFF size field and the data size field
+    header_sizes: (u64, u64),
+    data_bytes: u64,
+    buffer: Vec<u8>,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Create a WAV file at the given path.
+    pub fn create<P: AsRef<Path>>(path: P, spec: WavSpec) -> Result<Self, WavError> {
+        WavWriter::new(BufWriter::new(File::create(path)?), spec)
+    }
+}
+
+impl<W> WavWriter<W>
+where
+    W: Write + Seek,
+{
+    /// Write the header to the writer.
+    /// The WAVE_FORMAT_EXTENSIBLE header is used for more than two channels
+    /// and for integer samples with more than 16 bits.
+    pub fn new(mut writer: W, spec: WavSpec) -> Result<Self, WavError> {
+        spec.validate()?;
+        let extensible =
+            spec.channels > 2 || (spec.format == SampleFormat::Int && spec.bits_per_sample > 16);
+        let tag = match spec.format {
+            SampleFormat::Int => WAVE_FORMAT_PCM,
+            SampleFormat::Float => WAVE_FORMAT_IEEE_FLOAT,
+        };
+        let block_align = (spec.channels * spec.bytes_per_sample()) as u16;
+
+        let start = writer.seek(SeekFrom::Current(0))?;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&(if extensible { 40u32 } else { 16u32 }).to_le_bytes())?;
+        writer.write_all(&(if extensible { WAVE_FORMAT_EXTENSIBLE } else { tag }).to_le_bytes())?;
+        writer.write_all(&(spec.channels as u16).to_le_bytes())?;
+        writer.write_all(&(spec.samplerate as u32).to_le_bytes())?;
+        writer.write_all(&(spec.samplerate as u32 * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(spec.bits_per_sample as u16).to_le_bytes())?;
+        if extensible {
+            writer.write_all(&22u16.to_le_bytes())?;
+            writer.write_all(&(spec.bits_per_sample as u16).to_le_bytes())?;
+            writer.write_all(&channel_mask(spec.channels).to_le_bytes())?;
+            writer.write_all(&tag.to_le_bytes())?;
+            writer.write_all(&SUBFORMAT_GUID_TAIL)?;
+        }
+        writer.write_all(b"data")?;
+        let data_size_position = writer.seek(SeekFrom::Current(0))?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter {
+            writer: writer,
+            spec: spec,
+            header_sizes: (start + 4, data_size_position),
+            data_bytes: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// returns the format of the file
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Write one frame of interleaved samples in the range -1.0 to 1.0.
+    /// Samples outside that range get clipped for integer formats.
+    pub fn write_samples(&mut self, samples: &[f64]) -> Result<(), WavError> {
+        if samples.len() != self.spec.channels {
+            return Err(WavError::Format(format!(
+                "got {} samples for a file with {} channels",
+                samples.len(),
+                self.spec.channels
+            )));
+        }
+        self.buffer.clear();
+        for &sample in samples {
+            encode_sample(
+                sample,
+                self.spec.format == SampleFormat::Float,
+                self.spec.bits_per_sample,
+                &mut self.buffer,
+            );
+        }
+        self.writer.write_all(&self.buffer)?;
+        self.data_bytes += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Write one frame. The frame must have as many channels as the file.
+    pub fn write_frame<F>(&mut self, frame: F) -> Result<(), WavError>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let samples: Vec<f64> = frame.channels().map(|s| s.to_sample::<f64>()).collect();
+        self.write_samples(&samples)
+    }
+
+    /// Write all frames of a slice.
+    pub fn write_frames<F>(&mut self, frames: &[F]) -> Result<(), WavError>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        for &frame in frames {
+            self.write_frame(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Write the sizes to the header and flush the writer.
+    pub fn finalize(mut self) -> Result<W, WavError> {
+        if self.data_bytes & 1 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        let end = self.writer.seek(SeekFrom::Current(0))?;
+        let riff_size = end - self.header_sizes.0 - 4;
+        if riff_size > u32::max_value() as u64 {
+            return Err(WavError::Format("file is larger than 4GB".to_string()));
+        }
+        self.writer.seek(SeekFrom::Start(self.header_sizes.0))?;
+        self.writer.write_all(&(riff_size as u32).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(self.header_sizes.1))?;
+        self.writer.write_all(&(self.data_bytes as u32).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Read all frames of a WAV file, together with its format.
+pub fn read<F, P>(path: P) -> Result<(Vec<F>, WavSpec), WavError>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+    P: AsRef<Path>,
+{
+    let mut reader = WavReader::open(path)?;
+    let frames = reader.read_frames()?;
+    Ok((frames, reader.spec()))
+}
+
+/// Write frames to a WAV file.
+pub fn write<F, P>(path: P, frames: &[F], spec: WavSpec) -> Result<(), WavError>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+    P: AsRef<Path>,
+{
+    let mut writer = WavWriter::create(path, spec)?;
+    writer.write_frames(frames)?;
+    writer.finalize()?;
+    Ok(())
+}
+
+/// A Processor playing frames from a WAV file.
+/// It has no inputs and one output. After the end of the file it outputs silence,
+/// unless looping is enabled.
+/// If the file has another samplerate than the Graph, it gets resampled.
+/// Frames which can not be resampled are played unchanged.
+pub struct WavPlayer<F> {
+    frames: Vec<F>,
+    // the frames of the file, if `frames` are resampled
+    original: Option<Vec<F>>,
+    position: usize,
+    looping: bool,
+    samplerate: usize,
+    quality: Quality,
+}
+
+impl<F> WavPlayer<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    /// Load the WAV file at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WavError> {
+        let (frames, spec) = read(path)?;
+        Ok(WavPlayer::from_frames(frames, spec.samplerate))
+    }
+
+    /// Create a player for frames which are already in memory.
+    pub fn from_frames(frames: Vec<F>, samplerate: usize) -> Self {
+        WavPlayer {
+            frames: frames,
+            original: None,
+            position: 0,
+            looping: false,
+            samplerate: samplerate,
+            quality: Quality::Medium,
+        }
+    }
+
+    /// returns the samplerate of the file
+    pub fn samplerate(&self) -> usize {
+        self.samplerate
+    }
+
+    /// returns the amount of frames played, at the samplerate of the Graph
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// start playing from the beginning again
+    pub fn restart(&mut self) {
+        self.position = 0;
+    }
+
+    /// if looping is enabled, the file starts again after its end
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// sets the quality of the resampling, it is used when the samplerate is set
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+    }
+
+    /// returns true if all frames have been played and looping is disabled
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.position >= self.frames.len()
+    }
+}
+
+impl<F> Processor<F> for WavPlayer<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, _inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        for output in outputs.iter_mut() {
+            if self.looping && self.position >= self.frames.len() {
+                self.position = 0;
+            }
+            output[0] = match self.frames.get(self.position) {
+                Some(&frame) => frame,
+                None => F::equilibrium(),
+            };
+            self.position += 1;
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        let original = match self.original.take() {
+            Some(original) => original,
+            None => mem::replace(&mut self.frames, Vec::new()),
+        };
+        self.frames = if samplerate == self.samplerate || samplerate == 0 || self.samplerate == 0 {
+            original
+        } else {
+            match resample_frames(&original, self.samplerate, samplerate, self.quality) {
+                Ok(frames) => {
+                    self.original = Some(original);
+                    frames
+                }
+                // played at the wrong speed rather than not at all
+                Err(_) => original,
+            }
+        };
+        self.position = 0;
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        0
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+// the recorded frames, only locked by the Recordings
+struct Recorded<F> {
+    consumer: Consumer<F>,
+    frames: Vec<F>,
+}
+
+// state shared between the recorder and its Recordings
+struct State {
+    samplerate: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+/// A handle to the frames captured by a WavRecorder.
+/// It stays usable after the recorder has been moved into a Graph.
+#[derive(Clone)]
+pub struct Recording<F> {
+    recorded: Arc<Mutex<Recorded<F>>>,
+    state: Arc<State>,
+}
+
+impl<F> Recording<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    // moves the frames waiting in the ring buffer to the recorded frames
+    fn collect<'a>(&'a self) -> MutexGuard<'a, Recorded<F>> {
+        let mut recorded = self.recorded.lock().unwrap();
+        let waiting = recorded.consumer.len();
+        recorded.frames.reserve(waiting);
+        while let Some(frame) = recorded.consumer.pop() {
+            recorded.frames.push(frame);
+        }
+        recorded
+    }
+
+    /// returns a copy of all frames recorded so far
+    pub fn frames(&self) -> Vec<F> {
+        self.collect().frames.clone()
+    }
+
+    /// removes all frames recorded so far and returns them
+    pub fn take(&self) -> Vec<F> {
+        let mut recorded = self.collect();
+        ::std::mem::replace(&mut recorded.frames, Vec::new())
+    }
+
+    /// returns the amount of frames recorded so far
+    pub fn len(&self) -> usize {
+        self.collect().frames.len()
+    }
+
+    /// returns the amount of frames the recorder had no room for
+    pub fn dropped(&self) -> usize {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Write all frames recorded so far to a WAV file,
+    /// at the samplerate of the graph the recorder runs in.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        bits_per_sample: usize,
+        format: SampleFormat,
+    ) -> Result<(), WavError> {
+        let recorded = self.collect();
+        let spec = WavSpec {
+            channels: F::n_channels(),
+            samplerate: self.state.samplerate.load(Ordering::Relaxed),
+            bits_per_sample: bits_per_sample,
+            format: format,
+        };
+        write(path, &recorded.frames, spec)
+    }
+}
+
+/// A Processor recording everything passing through it.
+/// It has one input which is passed on to its one output unchanged.
+/// The frames are passed to its Recordings through a lock free ring buffer,
+/// so `process` never waits for them. Frames that do not fit into the
+/// buffer are dropped, a Recording has to be read before it is full.
+pub struct WavRecorder<F> {
+    producer: Producer<F>,
+    recording: Recording<F>,
+}
+
+impl<F> WavRecorder<F>
+where
+    F: Frame + Send,
+{
+    /// Create a recorder with room for about 24 seconds at 44100Hz.
+    pub fn new() -> Self {
+        WavRecorder::with_capacity(RECORD_FRAMES)
+    }
+
+    /// Create a recorder with room for `frames` frames between two reads of its Recordings.
+    pub fn with_capacity(frames: usize) -> Self {
+        let (producer, consumer) = ring_buffer(frames, F::equilibrium());
+        WavRecorder {
+            producer: producer,
+            recording: Recording {
+                recorded: Arc::new(Mutex::new(Recorded {
+                    consumer: consumer,
+                    frames: Vec::new(),
+                })),
+                state: Arc::new(State {
+                    samplerate: AtomicUsize::new(44_100),
+                    dropped: AtomicUsize::new(0),
+                }),
+            },
+        }
+    }
+
+    /// returns a handle to the recorded frames
+    pub fn recording(&self) -> Recording<F> {
+        self.recording.clone()
+    }
+}
+
+impl<F> Processor<F> for WavRecorder<F>
+where
+    F: Frame,
+{
+    fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        for i in 0..inputs.len() {
+            outputs[i][0] = inputs[i][0];
+            if self.producer.push(inputs[i][0]).is_err() {
+                self.recording.state.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.recording
+            .state
+            .samplerate
+            .store(samplerate, Ordering::Relaxed);
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
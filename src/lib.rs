@@ -1,3 +1,10 @@
 pub mod processor;
 pub mod graph;
+pub mod planar;
+pub mod simd;
+pub mod processors;
+pub mod patch;
+pub mod io;
+#[macro_use]
+pub mod builder;
 mod tests;
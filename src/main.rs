@@ -0,0 +1,428 @@
+//! Renders a patch file over WAV files.
+//!
+//! ```text
+//! pcm-flow patch.pf -i in.wav -o out.wav --set filt.cutoff=800
+//! ```
+
+extern crate pcm_flow;
+extern crate sample;
+
+use pcm_flow::graph::{render_blocks, FrameSet, Graph};
+use pcm_flow::io::raw::{self, RawFormat};
+use pcm_flow::io::decoder;
+use pcm_flow::io::wav::{SampleFormat, WavSpec, WavWriter};
+use pcm_flow::patch::{self, Registry, Value};
+use pcm_flow::processor::Processor;
+use pcm_flow::processors::resample::{self, Quality};
+use pcm_flow::simd::Kernel;
+use sample::conv::Duplex;
+use sample::Frame;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process;
+
+const USAGE: &'static str = "usage: pcm-flow PATCH [options]
+
+options:
+    -i, --input FILE          audio file for the next graph input, can be repeated.
+                              FLAC and Ogg Vorbis need the flac and vorbis features
+    -o, --output FILE         WAV file for the next graph output, can be repeated
+    -f, --format FORMAT       stream raw PCM instead of WAV files: s16le, s24le or f32le.
+                              Reads from stdin and writes to stdout unless a file is given
+    -r, --samplerate RATE     samplerate of the graph (default: of the first input or 44100)
+    -q, --quality QUALITY     quality of converting inputs with another samplerate:
+                              fast, medium or best (default: medium)
+    -b, --block-size FRAMES   frames processed at once (default: 256)
+    -c, --channels N          channels of the processed frames: 1, 2, 4, 6 or 8 (default: 2)
+    -l, --length SECONDS      length to render if there are no inputs
+    -s, --set NODE.PARAM=VALUE
+                              override a parameter of a node, can be repeated
+        --bits BITS           bits per sample of the outputs (default: 16)
+        --float               write float samples instead of integers
+        --describe            print a description of the graph and exit
+        --dot                 print the graph in the graphviz dot format and exit
+    -h, --help                print this help";
+
+struct Options {
+    patch: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    samplerate: Option<usize>,
+    quality: Quality,
+    block_size: usize,
+    channels: usize,
+    length: Option<f64>,
+    overrides: Vec<(String, String, Value)>,
+    bits_per_sample: usize,
+    format: SampleFormat,
+    raw_format: Option<RawFormat>,
+    describe: bool,
+    dot: bool,
+}
+
+// what the command line asks for
+enum Command {
+    Help,
+    Render(Options),
+}
+
+fn parse_args(args: Vec<String>) -> Result<Command, String> {
+    let mut options = Options {
+        patch: String::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        samplerate: None,
+        quality: Quality::Medium,
+        block_size: 256,
+        channels: 2,
+        length: None,
+        overrides: Vec::new(),
+        bits_per_sample: 16,
+        format: SampleFormat::Int,
+        raw_format: None,
+        describe: false,
+        dot: false,
+    };
+    let mut patch = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<String, String> {
+            args.next().ok_or(format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-i" | "--input" => options.inputs.push(value(&arg)?),
+            "-o" | "--output" => options.outputs.push(value(&arg)?),
+            "-r" | "--samplerate" => options.samplerate = Some(parse_number(&arg, &value(&arg)?)?),
+            "-q" | "--quality" => options.quality = resample::parse_quality(&value(&arg)?)?,
+            "-b" | "--block-size" => options.block_size = parse_number(&arg, &value(&arg)?)?,
+            "-c" | "--channels" => options.channels = parse_number(&arg, &value(&arg)?)?,
+            "-l" | "--length" => options.length = Some(parse_number(&arg, &value(&arg)?)?),
+            "--bits" => options.bits_per_sample = parse_number(&arg, &value(&arg)?)?,
+            "--float" => options.format = SampleFormat::Float,
+            "-f" | "--format" => {
+                let name = value(&arg)?;
+                let format = RawFormat::from_name(&name);
+                options.raw_format = Some(format.ok_or(format!("unknown raw format '{}'", name))?);
+            }
+            "--describe" => options.describe = true,
+            "--dot" => options.dot = true,
+            "-s" | "--set" => {
+                let setting = value(&arg)?;
+                let invalid = || format!("invalid parameter override '{}'", setting);
+                let mut parts = setting.splitn(2, '=');
+                let target = parts.next().ok_or_else(&invalid)?;
+                let value = parts.next().ok_or_else(&invalid)?;
+                let mut target = target.splitn(2, '.');
+                let node = target.next().ok_or_else(&invalid)?;
+                let param = target.next().ok_or_else(&invalid)?;
+                options
+                    .overrides
+                    .push((node.to_string(), param.to_string(), Value::parse(value)));
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option '{}'", arg))
+            }
+            _ => {
+                if patch.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                patch = Some(arg);
+            }
+        }
+    }
+    options.patch = patch.ok_or("no patch file given".to_string())?;
+    if options.block_size == 0 {
+        return Err("the block size must be at least 1".to_string());
+    }
+    if options.bits_per_sample == 16 && options.format == SampleFormat::Float {
+        options.bits_per_sample = 32;
+    }
+    Ok(Command::Render(options))
+}
+
+fn parse_number<T: ::std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, option))
+}
+
+fn run<F>(options: &Options) -> Result<(), String>
+where
+    F: Frame + Send + 'static,
+    F::Sample: Duplex<f64> + Kernel,
+{
+    let source = fs::read_to_string(&options.patch)
+        .map_err(|e| format!("{}: {}", options.patch, e))?;
+    let mut patch = patch::parse(&source).map_err(|e| format!("{}: {}", options.patch, e))?;
+    for &(ref node, ref param, ref value) in &options.overrides {
+        patch.set_param(node, param, value.clone())?;
+    }
+
+    let mut files = Vec::new();
+    let wav_inputs = if options.raw_format.is_some() { &[][..] } else { &options.inputs[..] };
+    for path in wav_inputs {
+        let (frames, file_samplerate): (Vec<F>, usize) =
+            decoder::read_file(path).map_err(|e| format!("{}: {}", path, e))?;
+        files.push((path, frames, file_samplerate));
+    }
+    let input_samplerate = files.first().map(|&(_, _, file_samplerate)| file_samplerate);
+    let samplerate = options.samplerate.or(input_samplerate).unwrap_or(44_100);
+    let mut inputs = Vec::new();
+    for (path, frames, file_samplerate) in files {
+        if file_samplerate == samplerate {
+            inputs.push(frames);
+        } else {
+            let frames = resample::resample_frames(
+                &frames,
+                file_samplerate,
+                samplerate,
+                options.quality,
+            );
+            inputs.push(frames.map_err(|e| format!("{}: {}", path, e))?);
+        }
+    }
+
+    let registry = Registry::<F>::builtin();
+    let mut graph = patch
+        .load(&registry, options.block_size, samplerate)
+        .map_err(|e| format!("{}: {}", options.patch, e))?;
+
+    if options.describe {
+        for decl in &patch.nodes {
+            println!("{}: {}", decl.name, decl.kind);
+        }
+        print!("{}", graph.get_description_string());
+        return Ok(());
+    }
+    if options.dot {
+        print!("{}", graph.get_dot_string());
+        return Ok(());
+    }
+
+    if let Some(format) = options.raw_format {
+        return run_raw(&mut graph, options, format);
+    }
+    if inputs.len() != graph.inputs_amt() {
+        return Err(format!(
+            "the patch has {} inputs, but {} input files are given",
+            graph.inputs_amt(),
+            inputs.len()
+        ));
+    }
+    if options.outputs.len() != graph.outputs_amt() {
+        return Err(format!(
+            "the patch has {} outputs, but {} output files are given",
+            graph.outputs_amt(),
+            options.outputs.len()
+        ));
+    }
+    let len = match options.length {
+        Some(seconds) => (seconds * samplerate as f64) as usize,
+        None if inputs.is_empty() => {
+            return Err("--length is needed for patches without inputs".to_string())
+        }
+        None => inputs.iter().map(|frames| frames.len()).max().unwrap_or(0),
+    };
+
+    let spec = WavSpec {
+        channels: F::n_channels(),
+        samplerate: samplerate,
+        bits_per_sample: options.bits_per_sample,
+        format: options.format,
+    };
+    let mut writers = Vec::new();
+    for path in &options.outputs {
+        writers.push(WavWriter::create(path, spec).map_err(|e| format!("{}: {}", path, e))?);
+    }
+    let frame_sets = (0..len).map(|i| -> FrameSet<F> {
+        inputs
+            .iter()
+            .map(|frames| frames.get(i).cloned().unwrap_or(F::equilibrium()))
+            .collect()
+    });
+    let mut result = Ok(());
+    render_blocks(&mut graph, frame_sets, len, |outputs| {
+        for frames in outputs {
+            for (writer, &frame) in writers.iter_mut().zip(frames.iter()) {
+                if result.is_ok() {
+                    result = writer.write_frame(frame);
+                }
+            }
+        }
+    });
+    result.map_err(|e| e.to_string())?;
+    for (writer, path) in writers.into_iter().zip(options.outputs.iter()) {
+        writer.finalize().map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+// streams raw PCM through graph input 0 and output 0
+fn run_raw<F>(graph: &mut Graph<F>, options: &Options, format: RawFormat) -> Result<(), String>
+where
+    F: Frame,
+    F::Sample: Duplex<f64> + Kernel,
+{
+    if options.inputs.len() > 1 || options.outputs.len() > 1 {
+        return Err("raw streams support only one input and one output".to_string());
+    }
+    let input: Box<Read> = match options.inputs.get(0).map(|path| path.as_str()) {
+        None | Some("-") => Box::new(io::stdin()),
+        Some(path) => Box::new(BufReader::new(
+            File::open(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+    };
+    let output: Box<Write> = match options.outputs.get(0).map(|path| path.as_str()) {
+        None | Some("-") => Box::new(io::stdout()),
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+    };
+    match raw::stream(graph, input, output, format) {
+        Ok(_) => Ok(()),
+        // the reading end of the pipe was closed, e.g. by `head`
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = match options.channels {
+        1 => run::<[f32; 1]>(&options),
+        2 => run::<[f32; 2]>(&options),
+        4 => run::<[f32; 4]>(&options),
+        6 => run::<[f32; 6]>(&options),
+        8 => run::<[f32; 8]>(&options),
+        n => Err(format!("{} channels are not supported", n)),
+    };
+    if let Err(message) = result {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, run, Command, Options};
+    use pcm_flow::io::decoder;
+    use pcm_flow::io::wav::SampleFormat;
+    use pcm_flow::processors::resample::Quality;
+    use std::env;
+    use std::fs;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    fn render(args: &[&str]) -> Options {
+        match parse(args) {
+            Ok(Command::Render(options)) => options,
+            Ok(Command::Help) => panic!("{:?} asks for help", args),
+            Err(message) => panic!("{}", message),
+        }
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let options = render(&[
+            "patch.pf", "-i", "a.wav", "--input", "b.wav", "-o", "out.wav", "-r", "48000",
+            "-q", "best", "-b", "64", "-c", "1", "-s", "filt.cutoff=800", "--float",
+        ]);
+        assert_eq!(options.patch, "patch.pf");
+        assert_eq!(options.inputs, vec!["a.wav", "b.wav"]);
+        assert_eq!(options.outputs, vec!["out.wav"]);
+        assert_eq!(options.samplerate, Some(48_000));
+        assert_eq!(options.quality, Quality::Best);
+        assert_eq!((options.block_size, options.channels), (64, 1));
+        assert_eq!(options.overrides.len(), 1);
+        assert_eq!(options.overrides[0].0, "filt");
+        assert_eq!(options.overrides[0].1, "cutoff");
+        // float samples have 32 bits unless --bits is given
+        assert!(options.format == SampleFormat::Float);
+        assert_eq!(options.bits_per_sample, 32);
+        // the defaults
+        let options = render(&["patch.pf"]);
+        assert_eq!((options.block_size, options.channels), (256, 2));
+        assert_eq!(options.bits_per_sample, 16);
+        assert!(options.raw_format.is_none() && !options.describe && !options.dot);
+
+        // help is returned instead of exiting, whatever else is given
+        assert!(match parse(&["patch.pf", "-h", "--bogus"]) {
+            Ok(Command::Help) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn parse_args_error_test() {
+        let error = |args: &[&str]| match parse(args) {
+            Err(message) => message,
+            Ok(_) => panic!("{:?} is accepted", args),
+        };
+        assert_eq!(error(&["patch.pf", "-o"]), "-o needs a value");
+        assert_eq!(error(&["patch.pf", "--samplerate"]), "--samplerate needs a value");
+        assert_eq!(error(&["patch.pf", "-b", "many"]), "invalid value 'many' for -b");
+        assert_eq!(error(&["patch.pf", "-l", "1s"]), "invalid value '1s' for -l");
+        assert_eq!(error(&["patch.pf", "-b", "0"]), "the block size must be at least 1");
+        assert_eq!(error(&["patch.pf", "-q", "good"]), "unknown resampling quality 'good'");
+        assert_eq!(error(&["patch.pf", "-f", "u8"]), "unknown raw format 'u8'");
+        assert_eq!(
+            error(&["patch.pf", "-s", "cutoff=800"]),
+            "invalid parameter override 'cutoff=800'"
+        );
+        assert_eq!(error(&["patch.pf", "--bogus"]), "unknown option '--bogus'");
+        assert_eq!(error(&["a.pf", "b.pf"]), "unexpected argument 'b.pf'");
+        assert_eq!(error(&[]), "no patch file given");
+    }
+
+    #[test]
+    fn run_test() {
+        let dir = env::temp_dir();
+        let patch = dir.join("pcm_flow_run_test.pf");
+        let output = dir.join("pcm_flow_run_test.wav");
+        fs::write(
+            &patch,
+            "c = constant(value: 0.5)\ng = gain(gain: 0.5)\nc.out -> g.in\ng.out -> out[0]\n",
+        ).unwrap();
+        let options = render(&[
+            patch.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "-r",
+            "1000",
+            "-b",
+            "64",
+            "-l",
+            "0.1",
+            "--float",
+            "-s",
+            "c.value=0.25",
+        ]);
+        run::<[f32; 2]>(&options).unwrap();
+        let (frames, samplerate): (Vec<[f32; 2]>, usize) = decoder::read_file(&output).unwrap();
+        assert_eq!(samplerate, 1000);
+        assert_eq!(frames, vec![[0.125; 2]; 100]);
+
+        // the number of output files has to match the patch
+        let options = render(&[patch.to_str().unwrap(), "-l", "0.1"]);
+        assert_eq!(
+            run::<[f32; 2]>(&options),
+            Err("the patch has 1 outputs, but 0 output files are given".to_string())
+        );
+        fs::remove_file(&patch).unwrap();
+        fs::remove_file(&output).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+//! A small text format for describing graphs.
+//!
+//! A patch consists of node declarations and connections:
+//!
+//! ```text
+//! # a simple filtered oscillator
+//! osc = sine(frequency: 220)
+//! filt = lowpass(cutoff: 800, q: 0.7)
+//! in[0] -> filt.1
+//! osc.out -> filt.in; filt.out -> out[0]
+//! ```
+//!
+//! A node declaration names a processor type from a `Registry` and
+//! passes it parameters. A connection goes from an output port to an input port.
+//! Ports are written as `node.index` or `node.name`, a bare `node` means port 0.
+//! `in[n]` and `out[n]` are the graph inputs and outputs.
+//! A connection between two nodes can have a gain, either as a factor
+//! or in decibels, and can be inverted or muted:
+//! `osc.out -> filt.in (db: -6, invert: 1, mute: 0)`.
+//! Statements can be separated by newlines or semicolons.
+
+extern crate sample;
+
+mod parser;
+mod registry;
+mod tests;
+
+pub use self::parser::parse;
+pub use self::registry::{Constructor, Entry, Registry};
+
+use graph::{self, Graph};
+use processors::util::db_to_gain;
+use self::sample::Frame;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A position in the patch source. Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An error in a patch, either while parsing or while loading it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchError {
+    pub position: Position,
+    pub message: String,
+}
+
+impl PatchError {
+    pub fn new(position: Position, message: String) -> Self {
+        PatchError {
+            position: position,
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.position.line,
+            self.position.column,
+            self.message
+        )
+    }
+}
+
+impl Error for PatchError {}
+
+/// A parameter value, either a number or a text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    /// Parse a value the way it would be written in a patch.
+    pub fn parse(text: &str) -> Value {
+        match text.parse::<f64>() {
+            Ok(number) => Value::Number(number),
+            Err(_) => Value::Text(text.trim_matches('"').to_string()),
+        }
+    }
+}
+
+/// The parameters passed to a processor constructor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Params {
+    values: HashMap<String, Value>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Params {
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the numeric parameter `name`, or `default` if it is not set.
+    pub fn number(&self, name: &str, default: f64) -> Result<f64, String> {
+        match self.values.get(name) {
+            None => Ok(default),
+            Some(&Value::Number(number)) => Ok(number),
+            Some(&Value::Text(ref text)) => Err(format!(
+                "parameter '{}' must be a number, not \"{}\"",
+                name,
+                text
+            )),
+        }
+    }
+
+    /// Returns the text parameter `name`, or `default` if it is not set.
+    pub fn text(&self, name: &str, default: &str) -> Result<String, String> {
+        match self.values.get(name) {
+            None => Ok(default.to_string()),
+            Some(&Value::Text(ref text)) => Ok(text.clone()),
+            Some(&Value::Number(number)) => Err(format!(
+                "parameter '{}' must be a text, not {}",
+                name,
+                number
+            )),
+        }
+    }
+}
+
+/// The port of a node an endpoint refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortRef {
+    Index(usize),
+    Name(String),
+    Default,
+}
+
+/// One side of a connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    GraphInput(usize),
+    GraphOutput(usize),
+    Port { node: String, port: PortRef },
+}
+
+/// A node declaration, `name = kind(params)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeDecl {
+    pub name: String,
+    pub kind: String,
+    pub params: Params,
+    pub position: Position,
+}
+
+/// A connection statement, `source -> dest (params)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub source: Endpoint,
+    pub dest: Endpoint,
+    pub params: Params,
+    pub position: Position,
+}
+
+impl Connection {
+    /// returns the attributes the parameters describe:
+    /// `gain: 1` or `db: 0`, `invert: 0` and `mute: 0`
+    pub fn attributes(&self) -> Result<graph::Connection, String> {
+        let gain = match self.params.get("db") {
+            Some(_) => db_to_gain(self.params.number("db", 0.0)?),
+            None => self.params.number("gain", 1.0)?,
+        };
+        let mut connection = graph::Connection::with_gain(gain);
+        connection.inverted = self.params.number("invert", 0.0)? != 0.0;
+        connection.muted = self.params.number("mute", 0.0)? != 0.0;
+        Ok(connection)
+    }
+}
+
+/// A parsed patch. It can be modified before being loaded into a Graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub nodes: Vec<NodeDecl>,
+    pub connections: Vec<Connection>,
+}
+
+impl Patch {
+    /// Create a new empty Patch
+    pub fn new() -> Self {
+        Patch {
+            nodes: Vec::new(),
+            connections: Vec::new(),
+        }
+    }
+
+    /// Override a parameter of a node, e.g. from the command line.
+    pub fn set_param(&mut self, node: &str, name: &str, value: Value) -> Result<(), String> {
+        match self.nodes.iter_mut().find(|decl| decl.name == node) {
+            Some(decl) => {
+                decl.params.set(name, value);
+                Ok(())
+            }
+            None => Err(format!("node '{}' does not exist", node)),
+        }
+    }
+
+    /// Build a Graph from the patch, constructing the processors with the registry.
+    /// The amount of graph inputs and outputs is the highest index used plus one.
+    pub fn load<F>(
+        &self,
+        registry: &Registry<F>,
+        buffersize: usize,
+        samplerate: usize,
+    ) -> Result<Graph<F>, PatchError>
+    where
+        F: Frame,
+    {
+        let mut graph = Graph::new(buffersize, samplerate);
+        // node name -> (processor id, registry entry)
+        let mut nodes = HashMap::new();
+        for decl in &self.nodes {
+            if nodes.contains_key(decl.name.as_str()) {
+                return Err(PatchError::new(
+                    decl.position,
+                    format!("node '{}' is declared twice", decl.name),
+                ));
+            }
+            let entry = match registry.get(&decl.kind) {
+                Some(entry) => entry,
+                None => {
+                    return Err(PatchError::new(
+                        decl.position,
+                        format!("unknown processor type '{}'", decl.kind),
+                    ))
+                }
+            };
+            let processor = entry.construct(&decl.params).map_err(|message| {
+                PatchError::new(decl.position, format!("{}: {}", decl.name, message))
+            })?;
+            let id = graph.add_processor(processor);
+            graph
+                .set_processor_name(id, &decl.name)
+                .expect("the processor was just added");
+            nodes.insert(decl.name.as_str(), (id, entry));
+        }
+
+        let mut inputs = 0;
+        let mut outputs = 0;
+        for connection in &self.connections {
+            for endpoint in &[&connection.source, &connection.dest] {
+                match **endpoint {
+                    Endpoint::GraphInput(i) => inputs = inputs.max(i + 1),
+                    Endpoint::GraphOutput(o) => outputs = outputs.max(o + 1),
+                    _ => {}
+                }
+            }
+        }
+        graph.set_input_amt(inputs);
+        graph.set_output_amt(outputs);
+
+        for connection in &self.connections {
+            let error = |message: String| PatchError::new(connection.position, message);
+            let resolve = |graph: &Graph<F>,
+                           endpoint: &Endpoint,
+                           input: bool|
+             -> Result<(usize, usize), PatchError> {
+                match *endpoint {
+                    Endpoint::Port { ref node, ref port } => {
+                        let &(id, entry) = match nodes.get(node.as_str()) {
+                            Some(node) => node,
+                            None => return Err(error(format!("node '{}' does not exist", node))),
+                        };
+                        let name = match *port {
+                            PortRef::Index(index) => return Ok((id, index)),
+                            PortRef::Default => return Ok((id, 0)),
+                            PortRef::Name(ref name) => name,
+                        };
+                        // names given to the registry come first,
+                        // then the names the processor reports
+                        let index = if input {
+                            entry
+                                .input_index(name)
+                                .or_else(|| graph.input_index(id, name))
+                        } else {
+                            entry
+                                .output_index(name)
+                                .or_else(|| graph.output_index(id, name))
+                        };
+                        match index {
+                            Some(index) => Ok((id, index)),
+                            None => Err(error(format!(
+                                "node '{}' has no {} named '{}'",
+                                node,
+                                if input { "input" } else { "output" },
+                                name
+                            ))),
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            };
+            let between_nodes = match (&connection.source, &connection.dest) {
+                (&Endpoint::Port { .. }, &Endpoint::Port { .. }) => true,
+                _ => false,
+            };
+            if !between_nodes && !connection.params.is_empty() {
+                return Err(error(
+                    "only connections between nodes can have parameters".to_string(),
+                ));
+            }
+            let result = match (&connection.source, &connection.dest) {
+                (&Endpoint::GraphInput(_), &Endpoint::GraphOutput(_)) => Err(
+                    "graph inputs can not be connected directly to graph outputs".to_string(),
+                ),
+                (&Endpoint::GraphOutput(_), _) => {
+                    Err("graph outputs can not be used as a source".to_string())
+                }
+                (_, &Endpoint::GraphInput(_)) => {
+                    Err("graph inputs can not be used as a destination".to_string())
+                }
+                (&Endpoint::GraphInput(input), dest) => {
+                    let dest = resolve(&graph, dest, true)?;
+                    graph.connect_input(input, dest)
+                }
+                (source, &Endpoint::GraphOutput(output)) => {
+                    let source = resolve(&graph, source, false)?;
+                    graph.connect_output(output, source)
+                }
+                (source, dest) => {
+                    let source = resolve(&graph, source, false)?;
+                    let dest = resolve(&graph, dest, true)?;
+                    connection
+                        .attributes()
+                        .and_then(|attributes| graph.add_connection_with(source, dest, attributes))
+                }
+            };
+            result.map_err(&error)?;
+        }
+        Ok(graph)
+    }
+}
+
+/// Parse a patch and load it into a Graph in one step.
+pub fn load<F>(
+    source: &str,
+    registry: &Registry<F>,
+    buffersize: usize,
+    samplerate: usize,
+) -> Result<Graph<F>, PatchError>
+where
+    F: Frame,
+{
+    parse(source)?.load(registry, buffersize, samplerate)
+}
@@ -0,0 +1,341 @@
+use super::{Connection, Endpoint, NodeDecl, Params, Patch, PatchError, PortRef, Position, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Text(String),
+    Arrow,
+    Dot,
+    Comma,
+    Colon,
+    Equals,
+    Semicolon,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match *self {
+            Token::Ident(ref name) => format!("'{}'", name),
+            Token::Number(number) => format!("number {}", number),
+            Token::Text(ref text) => format!("string \"{}\"", text),
+            Token::Arrow => "'->'".to_string(),
+            Token::Dot => "'.'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::Colon => "':'".to_string(),
+            Token::Equals => "'='".to_string(),
+            Token::Semicolon => "';'".to_string(),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+            Token::LBracket => "'['".to_string(),
+            Token::RBracket => "']'".to_string(),
+        }
+    }
+}
+
+/// Splits the patch source into tokens, skipping whitespace and comments.
+/// A comment starts with '#' and ends at the end of the line.
+fn tokenize(source: &str) -> Result<Vec<(Token, Position)>, PatchError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut column = 1;
+    while i < chars.len() {
+        let c = chars[i];
+        let position = Position { line: line, column: column };
+        if c == '\n' {
+            line += 1;
+            column = 1;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            column += 1;
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let start = i;
+        let token = if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            Token::Ident(chars[start..i].iter().collect())
+        } else if c.is_digit(10)
+            || (c == '-' && i + 1 < chars.len() && (chars[i + 1].is_digit(10) || chars[i + 1] == '.'))
+        {
+            i += 1;
+            while i < chars.len() && (chars[i].is_digit(10) || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '-' || chars[i] == '+') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_digit(10) {
+                    i += 1;
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            match text.parse::<f64>() {
+                Ok(number) => Token::Number(number),
+                Err(_) => return Err(PatchError::new(position, format!("invalid number '{}'", text))),
+            }
+        } else if c == '"' {
+            i += 1;
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None | Some(&'\n') => {
+                        return Err(PatchError::new(position, "unterminated string".to_string()));
+                    }
+                    Some(&'"') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&'\\') if i + 1 < chars.len() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            Token::Text(text)
+        } else {
+            i += 1;
+            match c {
+                '-' if chars.get(i) == Some(&'>') => {
+                    i += 1;
+                    Token::Arrow
+                }
+                '.' => Token::Dot,
+                ',' => Token::Comma,
+                ':' => Token::Colon,
+                '=' => Token::Equals,
+                ';' => Token::Semicolon,
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                '[' => Token::LBracket,
+                ']' => Token::RBracket,
+                _ => return Err(PatchError::new(position, format!("unexpected character '{}'", c))),
+            }
+        };
+        column += i - start;
+        tokens.push((token, position));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+    // position right after the last token, used for errors at the end of the input
+    end: Position,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|&(ref token, _)| token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.index + offset).map(|&(ref token, _)| token)
+    }
+
+    fn position(&self) -> Position {
+        match self.tokens.get(self.index) {
+            Some(&(_, position)) => position,
+            None => self.end,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> PatchError {
+        let found = match self.peek() {
+            Some(token) => token.describe(),
+            None => "end of input".to_string(),
+        };
+        PatchError::new(self.position(), format!("expected {}, found {}", expected, found))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), PatchError> {
+        if self.peek() == Some(&token) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(&token.describe()))
+        }
+    }
+
+    fn ident(&mut self, expected: &str) -> Result<String, PatchError> {
+        match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                self.index += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn index_number(&mut self) -> Result<usize, PatchError> {
+        let position = self.position();
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                if number < 0.0 || number.fract() != 0.0 {
+                    return Err(PatchError::new(
+                        position,
+                        format!("{} is not a valid index", number),
+                    ));
+                }
+                self.index += 1;
+                Ok(number as usize)
+            }
+            _ => Err(self.unexpected("an index")),
+        }
+    }
+
+    fn patch(&mut self) -> Result<Patch, PatchError> {
+        let mut patch = Patch::new();
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Semicolon => {
+                    self.index += 1;
+                }
+                Token::Ident(_) if self.peek_at(1) == Some(&Token::Equals) => {
+                    let node = self.node_decl()?;
+                    patch.nodes.push(node);
+                }
+                _ => {
+                    let connection = self.connection()?;
+                    patch.connections.push(connection);
+                }
+            }
+        }
+        Ok(patch)
+    }
+
+    // name = kind(key: value, ...)
+    fn node_decl(&mut self) -> Result<NodeDecl, PatchError> {
+        let position = self.position();
+        let name = self.ident("a node name")?;
+        if name == "in" || name == "out" {
+            return Err(PatchError::new(
+                position,
+                format!("'{}' is reserved for the graph inputs and outputs", name),
+            ));
+        }
+        self.expect(Token::Equals)?;
+        let kind = self.ident("a processor type")?;
+        let params = self.params()?;
+        Ok(NodeDecl {
+            name: name,
+            kind: kind,
+            params: params,
+            position: position,
+        })
+    }
+
+    // an optional list of parameters, (key: value, ...)
+    fn params(&mut self) -> Result<Params, PatchError> {
+        let mut params = Params::new();
+        if self.peek() == Some(&Token::LParen) {
+            self.index += 1;
+            while self.peek() != Some(&Token::RParen) {
+                let key = self.ident("a parameter name")?;
+                match self.peek() {
+                    Some(&Token::Colon) | Some(&Token::Equals) => self.index += 1,
+                    _ => return Err(self.unexpected("':'")),
+                }
+                let value = match self.peek().cloned() {
+                    Some(Token::Number(number)) => Value::Number(number),
+                    Some(Token::Text(text)) | Some(Token::Ident(text)) => Value::Text(text),
+                    _ => return Err(self.unexpected("a parameter value")),
+                };
+                self.index += 1;
+                params.set(&key, value);
+                if self.peek() == Some(&Token::Comma) {
+                    self.index += 1;
+                } else if self.peek() != Some(&Token::RParen) {
+                    return Err(self.unexpected("',' or ')'"));
+                }
+            }
+            self.index += 1;
+        }
+        Ok(params)
+    }
+
+    // endpoint -> endpoint (key: value, ...)
+    fn connection(&mut self) -> Result<Connection, PatchError> {
+        let position = self.position();
+        let source = self.endpoint()?;
+        self.expect(Token::Arrow)?;
+        let dest = self.endpoint()?;
+        let params = self.params()?;
+        Ok(Connection {
+            source: source,
+            dest: dest,
+            params: params,
+            position: position,
+        })
+    }
+
+    // in[n] | out[n] | node | node.port
+    fn endpoint(&mut self) -> Result<Endpoint, PatchError> {
+        let name = self.ident("a node name, 'in' or 'out'")?;
+        if name == "in" || name == "out" {
+            self.expect(Token::LBracket)?;
+            let index = self.index_number()?;
+            self.expect(Token::RBracket)?;
+            return Ok(if name == "in" {
+                Endpoint::GraphInput(index)
+            } else {
+                Endpoint::GraphOutput(index)
+            });
+        }
+        let port = if self.peek() == Some(&Token::Dot) {
+            self.index += 1;
+            match self.peek().cloned() {
+                Some(Token::Number(_)) => PortRef::Index(self.index_number()?),
+                Some(Token::Ident(port)) => {
+                    self.index += 1;
+                    PortRef::Name(port)
+                }
+                _ => return Err(self.unexpected("a port name or index")),
+            }
+        } else {
+            PortRef::Default
+        };
+        Ok(Endpoint::Port { node: name, port: port })
+    }
+}
+
+/// Parse the source of a patch.
+pub fn parse(source: &str) -> Result<Patch, PatchError> {
+    let tokens = tokenize(source)?;
+    let end = match source.lines().count() {
+        0 => Position { line: 1, column: 1 },
+        lines => Position {
+            line: lines,
+            column: source.lines().last().unwrap_or("").chars().count() + 1,
+        },
+    };
+    let mut parser = Parser {
+        tokens: tokens,
+        index: 0,
+        end: end,
+    };
+    parser.patch()
+}
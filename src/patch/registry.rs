@@ -0,0 +1,144 @@
+extern crate sample;
+
+use io::player::FilePlayer;
+use io::wav::WavPlayer;
+use processor::Processor;
+use processors;
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use super::Params;
+use std::collections::HashMap;
+
+/// A function building a Processor from the parameters written in a patch.
+pub type Constructor<F> = Box<Fn(&Params) -> Result<Box<Processor<F>>, String>>;
+
+/// Everything the loader knows about one processor type.
+pub struct Entry<F> {
+    constructor: Constructor<F>,
+    // names of the input ports, in port order
+    inputs: Vec<String>,
+    // names of the output ports, in port order
+    outputs: Vec<String>,
+}
+
+impl<F> Entry<F> {
+    /// Build a new Processor of this type.
+    pub fn construct(&self, params: &Params) -> Result<Box<Processor<F>>, String> {
+        (self.constructor)(params)
+    }
+
+    /// Returns the index of the input port with the given name.
+    pub fn input_index(&self, name: &str) -> Option<usize> {
+        self.inputs.iter().position(|input| input == name)
+    }
+
+    /// Returns the index of the output port with the given name.
+    pub fn output_index(&self, name: &str) -> Option<usize> {
+        self.outputs.iter().position(|output| output == name)
+    }
+}
+
+/// Maps the processor type names used in patches to constructors.
+pub struct Registry<F> {
+    entries: HashMap<String, Entry<F>>,
+}
+
+impl<F> Registry<F>
+where
+    F: Frame,
+{
+    /// Create a new empty Registry
+    pub fn new() -> Self {
+        Registry {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Register a processor type, patches address its ports by index
+    /// or by the names of its `PortInfo`s.
+    pub fn register<C>(&mut self, name: &str, constructor: C)
+    where
+        C: Fn(&Params) -> Result<Box<Processor<F>>, String> + 'static,
+    {
+        self.register_with_ports(name, &[], &[], constructor);
+    }
+
+    /// Register a processor type together with names for its ports,
+    /// so patches can write `filter.cutoff` instead of `filter.1`.
+    /// They are looked up before the names the Processor reports.
+    /// A previously registered type with the same name gets replaced.
+    pub fn register_with_ports<C>(
+        &mut self,
+        name: &str,
+        inputs: &[&str],
+        outputs: &[&str],
+        constructor: C,
+    ) where
+        C: Fn(&Params) -> Result<Box<Processor<F>>, String> + 'static,
+    {
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                constructor: Box::new(constructor),
+                inputs: inputs.iter().map(|s| s.to_string()).collect(),
+                outputs: outputs.iter().map(|s| s.to_string()).collect(),
+            },
+        );
+    }
+
+    /// Returns the entry registered under the given name.
+    pub fn get(&self, name: &str) -> Option<&Entry<F>> {
+        self.entries.get(name)
+    }
+
+    /// Returns the names of all registered processor types, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.entries.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+}
+
+impl<F> Registry<F>
+where
+    F: Frame + Send + 'static,
+    F::Sample: Duplex<f64>,
+{
+    /// Create a Registry containing the processors of this crate:
+    ///
+    /// * `wav_player(file: "path.wav", loop: 0, quality: medium)`, the file is resampled
+    ///   with the quality of `processors::resample::parse_quality` if its samplerate differs
+    /// * `file_player(file: "path.flac", loop: 0)`
+    /// * the processors listed in the `register` function of every module in `processors`
+    pub fn builtin() -> Self {
+        let mut registry = Registry::new();
+        registry.register("wav_player", |params: &Params| {
+            let file = params.text("file", "")?;
+            let mut player = WavPlayer::open(&file).map_err(|e| format!("{}: {}", file, e))?;
+            player.set_looping(params.number("loop", 0.0)? != 0.0);
+            player.set_quality(processors::resample::parse_quality(
+                &params.text("quality", "medium")?,
+            )?);
+            Ok(Box::new(player) as Box<Processor<F>>)
+        });
+        registry.register("file_player", |params: &Params| {
+            let file = params.text("file", "")?;
+            let looping = params.number("loop", 0.0)? != 0.0;
+            let player = FilePlayer::open(&file, looping).map_err(|e| format!("{}: {}", file, e))?;
+            Ok(Box::new(player) as Box<Processor<F>>)
+        });
+        processors::util::register(&mut registry);
+        processors::channels::register(&mut registry);
+        processors::osc::register(&mut registry);
+        processors::filter::register(&mut registry);
+        processors::envelope::register(&mut registry);
+        processors::lfo::register(&mut registry);
+        processors::modfx::register(&mut registry);
+        processors::delay::register(&mut registry);
+        processors::dynamics::register(&mut registry);
+        processors::distortion::register(&mut registry);
+        processors::reverb::register(&mut registry);
+        processors::convolution::register(&mut registry);
+        registry
+    }
+}
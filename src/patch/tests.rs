@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::super::graph::BufferSet;
+    use super::super::super::processor::{PortInfo, PortKind, Processor};
+    use super::super::{load, parse, Endpoint, Params, PortRef, Registry, Value};
+
+    struct Scale {
+        factor: f32,
+    }
+
+    impl Processor<[f32; 1]> for Scale {
+        fn process(&mut self, inputs: &BufferSet<[f32; 1]>, outputs: &mut BufferSet<[f32; 1]>) {
+            for i in 0..inputs.len() {
+                outputs[i][0][0] = inputs[i][0][0] * self.factor;
+            }
+        }
+        fn output_info(&self, _: usize) -> PortInfo {
+            PortInfo::new("scaled", PortKind::Main)
+        }
+        fn inputs_amt(&self) -> usize {
+            1
+        }
+        fn outputs_amt(&self) -> usize {
+            1
+        }
+    }
+
+    fn registry() -> Registry<[f32; 1]> {
+        let mut registry = Registry::new();
+        registry.register_with_ports("scale", &["in"], &["out"], |params: &Params| {
+            let factor = params.number("factor", 1.0)?;
+            Ok(Box::new(Scale {
+                factor: factor as f32,
+            }) as Box<Processor<[f32; 1]>>)
+        });
+        registry
+    }
+
+    #[test]
+    fn parse_test() {
+        let patch = parse(
+            "# comment\n\
+             a = scale(factor: 2.5, name: \"x\")\n\
+             in[0] -> a.in; a.0 -> out[1]",
+        ).unwrap();
+        assert_eq!(patch.nodes.len(), 1);
+        assert_eq!(patch.nodes[0].params.number("factor", 0.0), Ok(2.5));
+        assert_eq!(patch.nodes[0].params.get("name"), Some(&Value::Text("x".to_string())));
+        assert_eq!(patch.connections.len(), 2);
+        assert_eq!(patch.connections[0].source, Endpoint::GraphInput(0));
+        assert_eq!(
+            patch.connections[1].source,
+            Endpoint::Port {
+                node: "a".to_string(),
+                port: PortRef::Index(0),
+            }
+        );
+        assert_eq!(patch.connections[1].dest, Endpoint::GraphOutput(1));
+    }
+
+    #[test]
+    fn parse_error_position_test() {
+        let error = parse("a = scale()\nin[0] -> a.in\na.out -> ").unwrap_err();
+        assert_eq!((error.position.line, error.position.column), (3, 10));
+        let error = parse("a = scale(factor 2)").unwrap_err();
+        assert_eq!((error.position.line, error.position.column), (1, 18));
+        let error = parse("a.out -> b.in\n  $").unwrap_err();
+        assert_eq!((error.position.line, error.position.column), (2, 3));
+        let error = parse("a = b(x:").unwrap_err();
+        assert_eq!((error.position.line, error.position.column), (1, 9));
+        assert_eq!(error.message, "expected a parameter value, found end of input");
+    }
+
+    #[test]
+    fn load_test() {
+        let mut graph = load(
+            "a = scale(factor: 2)\nb = scale(factor: 3)\nin[0] -> a.in; a.out -> b; b.scaled -> out[0]",
+            &registry(),
+            1,
+            44_100,
+        ).unwrap();
+        let input = vec![vec![[1.5]]];
+        let mut output = vec![vec![[0.0]]];
+        graph.process(&input, &mut output);
+        assert_eq!(output[0][0][0], 9.0);
+    }
+
+    #[test]
+    fn connection_params_test() {
+        let patch = parse("a = scale()\nb = scale()\na -> b (db: -6, invert: 1)").unwrap();
+        let attributes = patch.connections[0].attributes().unwrap();
+        assert!((attributes.gain - 0.501187).abs() < 1e-6);
+        assert!(attributes.inverted && !attributes.muted);
+        let mut graph = load(
+            "a = scale()\nb = scale(factor: 2)\nin[0] -> a; a -> b (gain: 0.25, invert: 1); b -> out[0]",
+            &registry(),
+            1,
+            44_100,
+        ).unwrap();
+        let input = vec![vec![[2.0]]];
+        let mut output = vec![vec![[0.0]]];
+        graph.process(&input, &mut output);
+        assert_eq!(output[0][0][0], -1.0);
+    }
+
+    #[test]
+    fn load_error_test() {
+        let registry = registry();
+        let error = load("a = scale()\nin[0] -> b.in", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.position.line, 2);
+        let error = load("a = filter()", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.message, "unknown processor type 'filter'");
+        let error = load("a = scale()\na.out -> a.in", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.message, "Cycle detected");
+        let error = load("a = scale(factor: x)", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.position.column, 1);
+        let error = load("a = scale()\nin[0] -> a.cutoff", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.message, "node 'a' has no input named 'cutoff'");
+        let error = load("a = scale()\nin[0] -> a (gain: 2)", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.message, "only connections between nodes can have parameters");
+    }
+
+    #[test]
+    fn port_names_test() {
+        // the built in processors name their own ports
+        let registry = Registry::<[f32; 1]>::builtin();
+        let graph = load(
+            "c = compressor()\nf = svf()\n\
+             in[0] -> c.in; in[1] -> c.sidechain; c.out -> f.cutoff; f.highpass -> out[0]",
+            &registry,
+            1,
+            44_100,
+        ).unwrap();
+        assert_eq!(graph.input_port("c:sidechain"), Ok((0, 1)));
+        assert_eq!(graph.output_port("f:highpass"), Ok((1, 2)));
+        let error = load("o = sine()\nin[0] -> o.pitch", &registry, 1, 44_100).err().unwrap();
+        assert_eq!(error.message, "node 'o' has no input named 'pitch'");
+    }
+}
@@ -0,0 +1,280 @@
+//! A planar buffer layout, the main format a Graph processes in.
+//!
+//! A `BufferSet` holds one interleaved Frame per port and sample,
+//! a `PlanarBuffer` holds the samples of every channel of every port
+//! next to each other, in the order `[port][channel][sample]`.
+//! That way DSP code gets every channel as one contiguous slice.
+
+extern crate sample;
+
+use self::sample::{Frame, Sample};
+use graph::BufferSet;
+use simd::Kernel;
+use std::slice::{Chunks, ChunksMut};
+
+/// Samples of a number of ports with a number of channels each,
+/// stored port after port and channel after channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanarBuffer<S> {
+    ports: usize,
+    channels: usize,
+    samples: usize,
+    data: Vec<S>,
+}
+
+impl<S> PlanarBuffer<S>
+where
+    S: Sample,
+{
+    /// Create a silent PlanarBuffer.
+    pub fn new(ports: usize, channels: usize, samples: usize) -> Self {
+        PlanarBuffer {
+            ports: ports,
+            channels: channels,
+            samples: samples,
+            data: vec![S::equilibrium(); ports * channels * samples],
+        }
+    }
+
+    /// Create a PlanarBuffer for Frames of type `F` from a BufferSet,
+    /// it gets as many ports as the first FrameSet has Frames.
+    pub fn from_interleaved<F>(buffers: &BufferSet<F>) -> Self
+    where
+        F: Frame<Sample = S>,
+    {
+        let ports = buffers.first().map_or(0, |frames| frames.len());
+        let mut planar = PlanarBuffer::new(ports, F::n_channels(), buffers.len());
+        planar.copy_from_interleaved(buffers);
+        planar
+    }
+
+    pub fn ports(&self) -> usize {
+        self.ports
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// returns the amount of samples every channel holds
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Change the amount of ports and samples, all samples become silent.
+    pub fn reset(&mut self, ports: usize, samples: usize) {
+        self.ports = ports;
+        self.samples = samples;
+        self.data.clear();
+        self.data
+            .resize(ports * self.channels * samples, S::equilibrium());
+    }
+
+    /// Set all samples to silence.
+    pub fn clear(&mut self) {
+        for sample in self.data.iter_mut() {
+            *sample = S::equilibrium();
+        }
+    }
+
+    /// returns all channels of a port, channel after channel
+    pub fn port(&self, port: usize) -> &[S] {
+        let len = self.channels * self.samples;
+        &self.data[port * len..(port + 1) * len]
+    }
+
+    pub fn port_mut(&mut self, port: usize) -> &mut [S] {
+        let len = self.channels * self.samples;
+        &mut self.data[port * len..(port + 1) * len]
+    }
+
+    /// returns the samples of one channel of a port
+    pub fn channel(&self, port: usize, channel: usize) -> &[S] {
+        let start = (port * self.channels + channel) * self.samples;
+        &self.data[start..start + self.samples]
+    }
+
+    pub fn channel_mut(&mut self, port: usize, channel: usize) -> &mut [S] {
+        let start = (port * self.channels + channel) * self.samples;
+        &mut self.data[start..start + self.samples]
+    }
+
+    /// Iterate over the channels of a port.
+    pub fn channels_of<'a>(&'a self, port: usize) -> Chunks<'a, S> {
+        let samples = self.samples.max(1);
+        self.port(port).chunks(samples)
+    }
+
+    /// Iterate mutably over the channels of a port.
+    pub fn channels_of_mut<'a>(&'a mut self, port: usize) -> ChunksMut<'a, S> {
+        let samples = self.samples.max(1);
+        self.port_mut(port).chunks_mut(samples)
+    }
+
+    /// returns one sample of a port as a Frame
+    pub fn frame<F>(&self, port: usize, sample: usize) -> F
+    where
+        F: Frame<Sample = S>,
+    {
+        let port = self.port(port);
+        let samples = self.samples;
+        F::from_fn(|channel| port[channel * samples + sample])
+    }
+
+    pub fn set_frame<F>(&mut self, port: usize, sample: usize, frame: F)
+    where
+        F: Frame<Sample = S>,
+    {
+        let samples = self.samples;
+        let port = self.port_mut(port);
+        for (channel, value) in frame.channels().enumerate() {
+            port[channel * samples + sample] = value;
+        }
+    }
+
+    /// Overwrite a port with a port of another PlanarBuffer,
+    /// as many samples as both have are copied.
+    pub fn copy_port(&mut self, port: usize, source: &PlanarBuffer<S>, source_port: usize) {
+        let len = self.samples.min(source.samples);
+        for channel in 0..self.channels.min(source.channels) {
+            let source = &source.channel(source_port, channel)[..len];
+            self.channel_mut(port, channel)[..len].copy_from_slice(source);
+        }
+    }
+
+    /// Copy the Frames of a BufferSet in,
+    /// as many samples and ports as both have are copied.
+    pub fn copy_from_interleaved<F>(&mut self, buffers: &BufferSet<F>)
+    where
+        F: Frame<Sample = S>,
+    {
+        for (sample, frames) in buffers.iter().take(self.samples).enumerate() {
+            for (port, &frame) in frames.iter().take(self.ports).enumerate() {
+                self.set_frame(port, sample, frame);
+            }
+        }
+    }
+
+    /// Copy the samples out to a BufferSet,
+    /// as many samples and ports as both have are copied.
+    pub fn copy_to_interleaved<F>(&self, buffers: &mut BufferSet<F>)
+    where
+        F: Frame<Sample = S>,
+    {
+        for (sample, frames) in buffers.iter_mut().take(self.samples).enumerate() {
+            for (port, frame) in frames.iter_mut().take(self.ports).enumerate() {
+                *frame = self.frame(port, sample);
+            }
+        }
+    }
+
+    /// Create a BufferSet with one FrameSet per sample.
+    pub fn to_interleaved<F>(&self) -> BufferSet<F>
+    where
+        F: Frame<Sample = S>,
+    {
+        (0..self.samples)
+            .map(|sample| {
+                (0..self.ports)
+                    .map(|port| self.frame(port, sample))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl<S> PlanarBuffer<S>
+where
+    S: Kernel,
+{
+    /// Add a port of another PlanarBuffer to a port,
+    /// as many samples as both have are mixed.
+    pub fn add_port(&mut self, port: usize, source: &PlanarBuffer<S>, source_port: usize) {
+        for channel in 0..self.channels.min(source.channels) {
+            S::add(
+                self.channel_mut(port, channel),
+                source.channel(source_port, channel),
+            );
+        }
+    }
+
+    /// Like `add_port`, but multiplies the source with `gain`.
+    pub fn add_port_scaled(
+        &mut self,
+        port: usize,
+        source: &PlanarBuffer<S>,
+        source_port: usize,
+        gain: f64,
+    ) {
+        for channel in 0..self.channels.min(source.channels) {
+            S::add_scaled(
+                self.channel_mut(port, channel),
+                source.channel(source_port, channel),
+                gain,
+            );
+        }
+    }
+
+    /// Like `add_port_scaled`, but the gain moves linearly from `from` to `to`
+    /// over the first `len` samples and stays at `to` afterwards.
+    pub fn add_port_ramp(
+        &mut self,
+        port: usize,
+        source: &PlanarBuffer<S>,
+        source_port: usize,
+        from: f64,
+        to: f64,
+        len: usize,
+    ) {
+        let len = len.min(self.samples).min(source.samples);
+        let step = if len > 0 { (to - from) / len as f64 } else { 0.0 };
+        for channel in 0..self.channels.min(source.channels) {
+            let (dest, source) = (
+                self.channel_mut(port, channel),
+                source.channel(source_port, channel),
+            );
+            S::add_ramp(&mut dest[..len], &source[..len], from, step);
+            if to != 0.0 {
+                S::add_scaled(&mut dest[len..], &source[len..], to);
+            }
+        }
+    }
+
+    /// returns the average of every channel of a port as a Frame
+    pub fn mean<F>(&self, port: usize) -> F
+    where
+        F: Frame<Sample = S>,
+    {
+        let samples = self.samples.max(1) as f64;
+        let mut channels = self.channels_of(port);
+        F::from_fn(|_| {
+            let channel = channels.next().unwrap_or(&[]);
+            let sum: f64 = channel.iter().map(|x| x.to_sample::<f64>()).sum();
+            (sum / samples).to_sample()
+        })
+    }
+
+    /// Fill a port with values moving linearly from `from` to `to`,
+    /// the last sample is `to`.
+    pub fn fill_ramp<F>(&mut self, port: usize, from: F, to: F)
+    where
+        F: Frame<Sample = S>,
+    {
+        let samples = self.samples;
+        for (channel, (from, to)) in self
+            .channels_of_mut(port)
+            .zip(from.channels().zip(to.channels()))
+        {
+            let (from, to) = (from.to_sample::<f64>(), to.to_sample::<f64>());
+            let step = (to - from) / samples as f64;
+            for (i, x) in channel.iter_mut().enumerate() {
+                *x = (from + step * (i + 1) as f64).to_sample();
+            }
+        }
+    }
+
+    /// Multiply all channels of a port with `gain`.
+    pub fn scale_port(&mut self, port: usize, gain: f64) {
+        S::scale(self.port_mut(port), gain);
+    }
+}
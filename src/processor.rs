@@ -2,12 +2,124 @@ extern crate sample;
 
 use super::graph::BufferSet;
 use super::graph::FrameSet;
+use super::graph::empty_buffer;
+use super::planar::PlanarBuffer;
+
+/// The kind of signal a port carries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortType {
+    /// One Frame per sample.
+    Audio,
+    /// One value per block. A Graph keeps one Frame for every control port,
+    /// it writes the value to the first Frame of a control input, the other
+    /// Frames stay silent, and only the first Frame of a control output is read.
+    /// Audio signals connected to a control input are averaged over the block,
+    /// control outputs connected to an audio input are interpolated linearly
+    /// from the value of the last block.
+    Control,
+    /// Events, which only go from event outputs to event inputs.
+    /// The Frames of event ports are silent.
+    Event,
+}
+
+/// What an Event tells a Processor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    /// A MIDI note number and a velocity from 0.0 to 1.0.
+    NoteOn { note: u8, velocity: f64 },
+    NoteOff { note: u8 },
+    Value(f64),
+}
+
+/// A message sent to an event port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    /// the Frame of the block the event happens at
+    pub time: usize,
+    pub kind: EventKind,
+}
+
+impl Event {
+    pub fn new(time: usize, kind: EventKind) -> Self {
+        Event {
+            time: time,
+            kind: kind,
+        }
+    }
+}
+
+/// What an input or output of a Processor is used for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortKind {
+    /// The signal that gets processed.
+    Main,
+    /// A signal controlling the processing, e.g. the key input of a compressor.
+    Sidechain,
+    /// A signal modulating a parameter.
+    Modulation,
+}
+
+/// The channels a port uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelLayout {
+    /// Every channel, each one processed the same way.
+    Any,
+    /// Only channel 0.
+    Mono,
+    /// Channel 0 is left, channel 1 is right.
+    Stereo,
+}
+
+/// Describes an input or output of a Processor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortInfo {
+    /// the name patches and `Graph::connect_named` refer to the port by
+    pub name: String,
+    pub description: String,
+    pub kind: PortKind,
+    pub layout: ChannelLayout,
+    /// the value an unconnected input holds on every channel
+    pub default: f64,
+}
+
+impl PortInfo {
+    /// Create a PortInfo without description and a default of 0.0.
+    /// Modulation ports are mono, all other ones use any channel.
+    pub fn new(name: &str, kind: PortKind) -> Self {
+        PortInfo {
+            name: name.to_string(),
+            description: String::new(),
+            kind: kind,
+            layout: if kind == PortKind::Modulation {
+                ChannelLayout::Mono
+            } else {
+                ChannelLayout::Any
+            },
+            default: 0.0,
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn with_layout(mut self, layout: ChannelLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_default(mut self, default: f64) -> Self {
+        self.default = default;
+        self
+    }
+}
 
 /// The trait every signal processor has to implement.
 /// The inputs_amt function should return the number of inputs
 /// and the output_amt function should return the number of outputs of the processor 
     ///
-/// Either the process or the frame_process method has to be overriden.
+/// One of the process_planar, process or frame_process methods has to be overriden.
 /// If none of them are overriden the signal processor does nothing.
 pub trait Processor<F: sample::Frame> {
     /// Override this function if you want to work on BufferSets.
@@ -19,6 +131,26 @@ pub trait Processor<F: sample::Frame> {
         }
     }
 
+    /// Override this function if you want to work on contiguous channels,
+    /// and return true from processes_planar so a Graph calls it.
+    /// By default it converts the buffers to BufferSets and calls process.
+    fn process_planar(
+        &mut self,
+        inputs: &PlanarBuffer<F::Sample>,
+        outputs: &mut PlanarBuffer<F::Sample>,
+    ) {
+        let mut interleaved = empty_buffer(outputs.ports(), outputs.samples());
+        self.process(&inputs.to_interleaved(), &mut interleaved);
+        outputs.copy_from_interleaved(&interleaved);
+    }
+
+    /// Override this function to return true if you override process_planar.
+    /// A Graph then calls process_planar, otherwise it converts the buffers
+    /// of the processor to BufferSets itself and calls process.
+    fn processes_planar(&self) -> bool {
+        false
+    }
+
     /// Override this function if you want your processor to store the Samplerate
     fn set_samplerate(&mut self, usize) {}
 
@@ -27,6 +159,52 @@ pub trait Processor<F: sample::Frame> {
     /// and write it to the output FrameSet
     fn frame_process(&mut self, &FrameSet<F>, &mut FrameSet<F>) {}
 
+    /// Override this function if an input is not an audio input.
+    fn input_type(&self, usize) -> PortType {
+        PortType::Audio
+    }
+
+    /// Override this function if an output is not an audio output.
+    fn output_type(&self, usize) -> PortType {
+        PortType::Audio
+    }
+
+    /// Override this function if your processor has event inputs.
+    /// A Graph calls it before process with the events of the next block
+    /// for one input, sorted by time.
+    fn handle_events(&mut self, usize, &[Event]) {}
+
+    /// Override this function if your processor has event outputs.
+    /// A Graph calls it after process, move the events of the block
+    /// of one output to the Vec.
+    fn take_events(&mut self, usize, &mut Vec<Event>) {}
+
+    /// Override this function to describe an input.
+    /// By default it is a main input named by its index.
+    fn input_info(&self, input: usize) -> PortInfo {
+        PortInfo::new(&input.to_string(), PortKind::Main)
+    }
+
+    /// Override this function to describe an output.
+    /// By default it is a main output named by its index.
+    fn output_info(&self, output: usize) -> PortInfo {
+        PortInfo::new(&output.to_string(), PortKind::Main)
+    }
+
+    /// Override this function if your processor keeps producing output
+    /// after its inputs became silent, e.g. a delay or a reverb.
+    /// Return the amount of Frames it takes until the output is silent too.
+    fn tail_length(&self) -> usize {
+        0
+    }
+
+    /// Override this function if your output lags behind its input,
+    /// e.g. a lookahead limiter. Return the delay in Frames,
+    /// offline renderers drop that many Frames from the start of the output.
+    fn latency(&self) -> usize {
+        0
+    }
+
     /// return the amount of inputs
     fn inputs_amt(&self) -> usize;
 
@@ -0,0 +1,444 @@
+//! Converting between channel layouts.
+//!
+//! A `Matrix` maps the channels of one Frame type to the channels of another,
+//! `Adapted` uses two of them to run a Processor of one Frame type in a Graph
+//! of another, for example a mono filter in a stereo Graph.
+//! `ChannelMatrix`, `Split` and `Merge` change the channels inside a Graph.
+//!
+//! Surround Frames have the channel order L, R, C, LFE, Ls, Rs.
+
+extern crate sample;
+
+use graph::{empty_buffer, BufferSet, FrameSet};
+use patch::{Params, Registry};
+use processor::{ChannelLayout, Event, PortInfo, PortKind, PortType, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::FRAC_1_SQRT_2;
+use super::{broadcast, channel_f64, control, map_f64};
+
+/// Gains from every input channel to every output channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    // one row per output channel, one gain per input channel in every row
+    rows: Vec<Vec<f64>>,
+}
+
+impl Matrix {
+    /// Create a Matrix from one row of input gains per output channel,
+    /// all rows need the same length.
+    pub fn from_rows(rows: Vec<Vec<f64>>) -> Result<Self, String> {
+        let inputs = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != inputs) {
+            return Err("all rows of a channel matrix need the same length".to_string());
+        }
+        Ok(Matrix { rows: rows })
+    }
+
+    /// Pass every channel unchanged.
+    pub fn identity(channels: usize) -> Self {
+        Matrix {
+            rows: (0..channels)
+                .map(|output| {
+                    (0..channels)
+                        .map(|input| if input == output { 1.0 } else { 0.0 })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Copy one channel to `outputs` channels.
+    pub fn upmix(outputs: usize) -> Self {
+        Matrix {
+            rows: vec![vec![1.0]; outputs],
+        }
+    }
+
+    /// Average `inputs` channels to one channel.
+    pub fn downmix(inputs: usize) -> Self {
+        Matrix {
+            rows: vec![vec![1.0 / inputs as f64; inputs]],
+        }
+    }
+
+    /// Mix 5.1 surround down to stereo with the coefficients of ITU-R BS.775:
+    /// the center and the surround channels are added at -3dB,
+    /// the LFE channel is left out.
+    pub fn surround_to_stereo() -> Self {
+        let g = FRAC_1_SQRT_2;
+        Matrix {
+            rows: vec![
+                vec![1.0, 0.0, g, 0.0, g, 0.0],
+                vec![0.0, 1.0, g, 0.0, 0.0, g],
+            ],
+        }
+    }
+
+    /// The usual conversion from `inputs` to `outputs` channels:
+    /// the identity for the same number of channels, an upmix from one channel,
+    /// a downmix to one channel and the ITU downmix from six to two channels.
+    /// Otherwise channel N goes to channel N, leftover outputs are silent.
+    pub fn between(inputs: usize, outputs: usize) -> Self {
+        if inputs == outputs {
+            Matrix::identity(inputs)
+        } else if inputs == 1 {
+            Matrix::upmix(outputs)
+        } else if outputs == 1 {
+            Matrix::downmix(inputs)
+        } else if inputs == 6 && outputs == 2 {
+            Matrix::surround_to_stereo()
+        } else {
+            Matrix {
+                rows: (0..outputs)
+                    .map(|output| {
+                        (0..inputs)
+                            .map(|input| if input == output { 1.0 } else { 0.0 })
+                            .collect()
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.len())
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The gain from an input channel to an output channel.
+    pub fn gain(&self, input: usize, output: usize) -> f64 {
+        self.rows
+            .get(output)
+            .and_then(|row| row.get(input))
+            .cloned()
+            .unwrap_or(0.0)
+    }
+
+    /// Convert a Frame, missing input channels count as silent and output
+    /// channels without a row stay silent.
+    pub fn apply<A, B>(&self, frame: &A) -> B
+    where
+        A: Frame,
+        A::Sample: Duplex<f64>,
+        B: Frame,
+        B::Sample: Duplex<f64>,
+    {
+        map_f64(B::equilibrium(), |output, _| match self.rows.get(output) {
+            Some(row) => row
+                .iter()
+                .enumerate()
+                .map(|(input, gain)| gain * channel_f64(frame, input))
+                .sum(),
+            None => 0.0,
+        })
+    }
+}
+
+/// Runs a Processor of Frame type `G` in a Graph of another Frame type.
+/// Its inputs are converted with one Matrix, its outputs with another,
+/// by default the ones of `Matrix::between`.
+///
+/// The types have to be given, e.g. `Adapted::<_, [f32; 1]>::new(filter)`
+/// to run a filter in mono.
+pub struct Adapted<P, G> {
+    processor: P,
+    // None until the Frame type of the Graph is known
+    input: Option<Matrix>,
+    output: Option<Matrix>,
+    inputs: BufferSet<G>,
+    outputs: BufferSet<G>,
+}
+
+impl<P, G> Adapted<P, G>
+where
+    P: Processor<G>,
+    G: Frame,
+    G::Sample: Duplex<f64>,
+{
+    pub fn new(processor: P) -> Self {
+        Adapted {
+            processor: processor,
+            input: None,
+            output: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Wrap a Processor with a Matrix for its inputs and one for its outputs.
+    pub fn with_matrices(processor: P, input: Matrix, output: Matrix) -> Self {
+        Adapted {
+            processor: processor,
+            input: Some(input),
+            output: Some(output),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn into_inner(self) -> P {
+        self.processor
+    }
+}
+
+impl<P, G, F> Processor<F> for Adapted<P, G>
+where
+    P: Processor<G>,
+    G: Frame,
+    G::Sample: Duplex<f64>,
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        let (inputs_amt, outputs_amt) = (self.processor.inputs_amt(), self.processor.outputs_amt());
+        if self.inputs.len() != inputs.len() {
+            // only happens when the buffersize changes or the Adapted is used without a Graph
+            self.inputs = empty_buffer(inputs_amt, inputs.len());
+            self.outputs = empty_buffer(outputs_amt, inputs.len());
+        }
+        let input = self
+            .input
+            .get_or_insert_with(|| Matrix::between(F::n_channels(), G::n_channels()));
+        for (frames, converted) in inputs.iter().zip(self.inputs.iter_mut()) {
+            for (frame, converted) in frames.iter().zip(converted.iter_mut()) {
+                *converted = input.apply(frame);
+            }
+        }
+        self.processor.process(&self.inputs, &mut self.outputs);
+        let output = self
+            .output
+            .get_or_insert_with(|| Matrix::between(G::n_channels(), F::n_channels()));
+        for (frames, converted) in self.outputs.iter().zip(outputs.iter_mut()) {
+            for (frame, converted) in frames.iter().zip(converted.iter_mut()) {
+                *converted = output.apply(frame);
+            }
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.processor.set_samplerate(samplerate);
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        self.processor.input_info(input)
+    }
+
+    fn output_info(&self, output: usize) -> PortInfo {
+        self.processor.output_info(output)
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        self.processor.input_type(input)
+    }
+
+    fn output_type(&self, output: usize) -> PortType {
+        self.processor.output_type(output)
+    }
+
+    fn handle_events(&mut self, input: usize, events: &[Event]) {
+        self.processor.handle_events(input, events);
+    }
+
+    fn take_events(&mut self, output: usize, events: &mut Vec<Event>) {
+        self.processor.take_events(output, events);
+    }
+
+    fn tail_length(&self) -> usize {
+        self.processor.tail_length()
+    }
+
+    fn latency(&self) -> usize {
+        self.processor.latency()
+    }
+
+    fn inputs_amt(&self) -> usize {
+        self.processor.inputs_amt()
+    }
+
+    fn outputs_amt(&self) -> usize {
+        self.processor.outputs_amt()
+    }
+}
+
+/// Converts the channels of its input with a Matrix,
+/// channels the Matrix does not cover are silent.
+pub struct ChannelMatrix {
+    matrix: Matrix,
+}
+
+impl ChannelMatrix {
+    pub fn new(matrix: Matrix) -> Self {
+        ChannelMatrix { matrix: matrix }
+    }
+
+    /// Copy channel 0 to every one of `channels` channels.
+    pub fn upmix(channels: usize) -> Self {
+        ChannelMatrix::new(Matrix::upmix(channels))
+    }
+
+    /// Put the average of `channels` channels on all of them.
+    pub fn downmix(channels: usize) -> Self {
+        ChannelMatrix::new(Matrix {
+            rows: vec![vec![1.0 / channels as f64; channels]; channels],
+        })
+    }
+
+    /// Mix 5.1 surround down to stereo on channel 0 and 1.
+    pub fn surround_to_stereo() -> Self {
+        ChannelMatrix::new(Matrix::surround_to_stereo())
+    }
+
+    pub fn set_matrix(&mut self, matrix: Matrix) {
+        self.matrix = matrix;
+    }
+}
+
+impl<F> Processor<F> for ChannelMatrix
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        outputs[0] = self.matrix.apply(&inputs[0]);
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Has one output per channel of its input,
+/// output N carries channel N of the input on all of its channels.
+pub struct Split {
+    channels: usize,
+}
+
+impl Split {
+    pub fn new(channels: usize) -> Self {
+        Split { channels: channels }
+    }
+}
+
+impl<F> Processor<F> for Split
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        for (channel, output) in outputs.iter_mut().enumerate() {
+            *output = broadcast(channel_f64(&inputs[0], channel));
+        }
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, output: usize) -> PortInfo {
+        PortInfo::new(&format!("ch{}", output), PortKind::Main)
+            .with_description("one channel of the input on every channel")
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        self.channels
+    }
+}
+
+/// Has one input per channel of its output,
+/// channel N of the output is channel 0 of input N.
+pub struct Merge {
+    channels: usize,
+}
+
+impl Merge {
+    pub fn new(channels: usize) -> Self {
+        Merge { channels: channels }
+    }
+}
+
+impl<F> Processor<F> for Merge
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        outputs[0] = map_f64(F::equilibrium(), |channel, _| match inputs.get(channel) {
+            Some(input) => control(input),
+            None => 0.0,
+        });
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        PortInfo::new(&format!("ch{}", input), PortKind::Main).with_layout(ChannelLayout::Mono)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        self.channels
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `upmix()`, copies channel 0 to every channel
+/// * `downmix()`, puts the average of all channels on every channel
+/// * `downmix_5_1()`, mixes 5.1 surround down to stereo on channel 0 and 1
+/// * `split()`, with the outputs `ch0`, `ch1`, ... one per channel
+/// * `merge()`, with the inputs `ch0`, `ch1`, ... one per channel
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    let channels = F::n_channels();
+    registry.register("upmix", move |_: &Params| {
+        Ok(Box::new(ChannelMatrix::upmix(channels)) as Box<Processor<F>>)
+    });
+    registry.register("downmix", move |_: &Params| {
+        Ok(Box::new(ChannelMatrix::downmix(channels)) as Box<Processor<F>>)
+    });
+    registry.register("downmix_5_1", |_: &Params| {
+        Ok(Box::new(ChannelMatrix::surround_to_stereo()) as Box<Processor<F>>)
+    });
+    registry.register("split", move |_: &Params| {
+        Ok(Box::new(Split::new(channels)) as Box<Processor<F>>)
+    });
+    registry.register("merge", move |_: &Params| {
+        Ok(Box::new(Merge::new(channels)) as Box<Processor<F>>)
+    });
+}
@@ -0,0 +1,325 @@
+//! Convolution with impulse responses, for cabinet simulation and convolution reverb.
+//!
+//! The first partition of an impulse response is convolved directly,
+//! the rest with uniformly partitioned overlap-save FFT convolution,
+//! so there is no latency. Nothing is allocated while processing.
+//! Impulse responses loaded from files are resampled to the samplerate of the Graph.
+
+extern crate sample;
+
+use graph::FrameSet;
+use io::decoder;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::path::Path;
+use super::fft::{Complex, Fft};
+use super::resample::{resample, Quality};
+use super::util::db_to_gain;
+use super::{channel_f64, map_f64};
+
+/// The default partition size in samples.
+pub const PARTITION: usize = 64;
+
+/// Convolves one channel with one impulse response.
+pub struct Convolver {
+    partition: usize,
+    // the first partition, convolved directly
+    head: Vec<f64>,
+    // the last inputs for the head, stored twice so the newest
+    // `partition` inputs are always contiguous
+    history: Vec<f64>,
+    history_index: usize,
+    fft: Fft,
+    // the spectra of the other partitions
+    segments: Vec<Vec<Complex>>,
+    // the spectra of the last input windows, one for every segment
+    spectra: Vec<Vec<Complex>>,
+    spectrum_index: usize,
+    // the previous and the current input block
+    window: Vec<f64>,
+    position: usize,
+    // the output of the segments for the current block
+    tail: Vec<f64>,
+    scratch: Vec<Complex>,
+}
+
+impl Convolver {
+    /// Create a Convolver, `partition` has to be a power of two.
+    pub fn new(ir: &[f64], partition: usize) -> Result<Self, String> {
+        let fft = Fft::new(2 * partition)?;
+        let head = ir.iter().cloned().take(partition).collect();
+        let segments: Vec<Vec<Complex>> = ir
+            .chunks(partition)
+            .skip(1)
+            .map(|chunk| {
+                let mut spectrum = vec![Complex::zero(); 2 * partition];
+                for (x, &h) in spectrum.iter_mut().zip(chunk) {
+                    x.re = h;
+                }
+                fft.forward(&mut spectrum);
+                spectrum
+            })
+            .collect();
+        Ok(Convolver {
+            partition: partition,
+            head: head,
+            history: vec![0.0; 2 * partition],
+            history_index: 0,
+            spectra: vec![vec![Complex::zero(); 2 * partition]; segments.len()],
+            segments: segments,
+            spectrum_index: 0,
+            fft: fft,
+            window: vec![0.0; 2 * partition],
+            position: 0,
+            tail: vec![0.0; partition],
+            scratch: vec![Complex::zero(); 2 * partition],
+        })
+    }
+
+    /// The length of the impulse response.
+    pub fn len(&self) -> usize {
+        self.head.len() + self.segments.len() * self.partition
+    }
+
+    /// Convolve the next input sample.
+    pub fn process(&mut self, x: f64) -> f64 {
+        let n = self.partition;
+        // the newest input is at `newest`, the older ones follow it
+        self.history_index = (self.history_index + n - 1) % n;
+        self.history[self.history_index] = x;
+        self.history[self.history_index + n] = x;
+        let newest = &self.history[self.history_index..self.history_index + n];
+        let mut y = self
+            .head
+            .iter()
+            .zip(newest)
+            .fold(0.0, |y, (&h, &x)| y + h * x);
+        y += self.tail[self.position];
+        self.window[n + self.position] = x;
+        self.position += 1;
+        if self.position == n {
+            self.position = 0;
+            if !self.segments.is_empty() {
+                self.next_block();
+            }
+        }
+        y
+    }
+
+    // transforms the current window and calculates the output of the segments
+    // for the next block
+    fn next_block(&mut self) {
+        let n = self.partition;
+        let count = self.segments.len();
+        {
+            let spectrum = &mut self.spectra[self.spectrum_index];
+            for (x, &w) in spectrum.iter_mut().zip(self.window.iter()) {
+                *x = Complex::new(w, 0.0);
+            }
+            self.fft.forward(spectrum);
+        }
+        for x in self.scratch.iter_mut() {
+            *x = Complex::zero();
+        }
+        // segment k is multiplied with the window from k blocks ago
+        for (k, segment) in self.segments.iter().enumerate() {
+            let spectrum = &self.spectra[(self.spectrum_index + count - k) % count];
+            for ((y, &h), &x) in self.scratch.iter_mut().zip(segment).zip(spectrum) {
+                *y = *y + h * x;
+            }
+        }
+        self.fft.inverse(&mut self.scratch);
+        for (y, x) in self.tail.iter_mut().zip(&self.scratch[n..]) {
+            *y = x.re;
+        }
+        self.spectrum_index = (self.spectrum_index + 1) % count;
+        let (previous, current) = self.window.split_at_mut(n);
+        previous.copy_from_slice(current);
+    }
+}
+
+// which input channel goes through which impulse response into which output channel
+struct Route {
+    input: usize,
+    output: usize,
+    convolver: Convolver,
+}
+
+/// Convolves a Frame with an impulse response of one or more channels:
+///
+/// * a mono impulse response is used for every channel
+/// * an impulse response with as many channels as the Frame convolves each
+///   channel with its own impulse response
+/// * a four channel impulse response on a stereo Frame is true stereo,
+///   its channels are left to left, left to right, right to left and right to right
+pub struct Convolution {
+    ir: Vec<Vec<f64>>,
+    // the samplerate of the impulse response, if it is known
+    ir_samplerate: Option<usize>,
+    samplerate: usize,
+    partition: usize,
+    gain: f64,
+    mix: f64,
+    routes: Vec<Route>,
+    channels: usize,
+}
+
+impl Convolution {
+    /// Create a Convolution from the channels of an impulse response,
+    /// which has to have the samplerate of the Graph.
+    pub fn new(ir: Vec<Vec<f64>>) -> Result<Self, String> {
+        if ir.is_empty() {
+            return Err("the impulse response has no channels".to_string());
+        }
+        Ok(Convolution {
+            ir: ir,
+            ir_samplerate: None,
+            samplerate: 44_100,
+            partition: PARTITION,
+            gain: 1.0,
+            mix: 1.0,
+            routes: Vec::new(),
+            channels: 0,
+        })
+    }
+
+    /// Load the impulse response from an audio file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let (ir, samplerate) = decoder::read_channels(path)?;
+        let mut convolution = Convolution::new(ir)?;
+        convolution.ir_samplerate = Some(samplerate);
+        Ok(convolution)
+    }
+
+    /// Set the partition size, a power of two. Smaller partitions need less work
+    /// for the directly convolved head but more for the FFT partitions.
+    pub fn set_partition(&mut self, partition: usize) -> Result<(), String> {
+        Fft::new(2 * partition)?;
+        self.partition = partition;
+        self.channels = 0;
+        Ok(())
+    }
+
+    /// Set the gain of the convolved signal.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+    }
+
+    /// Set the balance between the dry signal (0.0) and the convolved signal (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    // creates the convolvers for a Frame with `channels` channels
+    fn build(&mut self, channels: usize) {
+        let pairs: Vec<(usize, usize, usize)> = if self.ir.len() == 4 && channels == 2 {
+            vec![(0, 0, 0), (0, 1, 1), (1, 0, 2), (1, 1, 3)]
+        } else if self.ir.len() == channels {
+            (0..channels).map(|c| (c, c, c)).collect()
+        } else {
+            (0..channels).map(|c| (c, c, c % self.ir.len())).collect()
+        };
+        let partition = self.partition;
+        let ir = match self.ir_samplerate {
+            Some(rate) if rate != self.samplerate && rate > 0 => {
+                // keeps the level, a response with more samples adds up more of them
+                let gain = rate as f64 / self.samplerate as f64;
+                self.ir
+                    .iter()
+                    .map(|channel| {
+                        let resampled = resample(channel, rate, self.samplerate, Quality::Best);
+                        // the samplerates are not 0
+                        resampled.unwrap().into_iter().map(|x| x * gain).collect()
+                    })
+                    .collect()
+            }
+            _ => self.ir.clone(),
+        };
+        self.routes = pairs
+            .into_iter()
+            .map(|(input, output, index)| Route {
+                input: input,
+                output: output,
+                // the partition size was checked before
+                convolver: Convolver::new(&ir[index], partition).unwrap(),
+            })
+            .collect();
+        self.channels = channels;
+    }
+}
+
+impl<F> Processor<F> for Convolution
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.channels != F::n_channels() {
+            // only happens when the Convolution is used without a Graph
+            self.build(F::n_channels());
+        }
+        // sample::Frame is implemented for up to 32 channels
+        let mut wet = [0.0; 32];
+        for route in &mut self.routes {
+            wet[route.output] += route
+                .convolver
+                .process(channel_f64(&inputs[0], route.input));
+        }
+        let (dry, gain) = (1.0 - self.mix, self.mix * self.gain);
+        outputs[0] = map_f64(inputs[0], |channel, x| dry * x + gain * wet[channel]);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate;
+        self.build(F::n_channels());
+    }
+
+    fn tail_length(&self) -> usize {
+        let len = self
+            .ir
+            .iter()
+            .map(|channel| channel.len())
+            .max()
+            .unwrap_or(0);
+        match self.ir_samplerate {
+            Some(rate) if rate > 0 => len * self.samplerate / rate + 1,
+            _ => len,
+        }
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `convolution(file: "ir.wav", gain: 0, mix: 1, partition: 64)`, the gain is in decibels
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("convolution", |params: &Params| {
+        let file = params.text("file", "")?;
+        let mut convolution = Convolution::open(&file).map_err(|e| format!("{}: {}", file, e))?;
+        convolution.set_gain(db_to_gain(params.number("gain", 0.0)?));
+        convolution.set_mix(params.number("mix", 1.0)?);
+        convolution.set_partition(params.number("partition", PARTITION as f64)? as usize)?;
+        Ok(Box::new(convolution) as Box<Processor<F>>)
+    });
+}
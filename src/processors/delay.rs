@@ -0,0 +1,322 @@
+//! Delays.
+//!
+//! `DelayLine` is a single channel ring buffer that can be read at fractional
+//! positions, the building block for the `Delay` processor and for effects
+//! with modulated delays.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use super::filter::{parse_filter_type, Coefficients, FilterType, Section};
+use super::{channel_f64, control, map_f64};
+
+/// How a DelayLine reads between two samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Cubic Hermite interpolation, smoother than linear for modulated delays.
+    Hermite,
+    /// A first order allpass, it keeps the full bandwidth but should only
+    /// be used for one tap per line whose delay changes slowly.
+    Allpass,
+}
+
+/// A ring buffer for one channel.
+pub struct DelayLine {
+    buffer: Vec<f64>,
+    // the position of the next write
+    index: usize,
+    // the last output of the allpass interpolation
+    allpass: f64,
+}
+
+impl DelayLine {
+    /// Create a DelayLine that can delay by up to `capacity` samples.
+    pub fn new(capacity: usize) -> Self {
+        DelayLine {
+            // room for the points around the longest delay
+            buffer: vec![0.0; capacity + 3],
+            index: 0,
+            allpass: 0.0,
+        }
+    }
+
+    /// The longest delay in samples.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len() - 3
+    }
+
+    pub fn clear(&mut self) {
+        for x in &mut self.buffer {
+            *x = 0.0;
+        }
+        self.allpass = 0.0;
+    }
+
+    pub fn write(&mut self, x: f64) {
+        self.buffer[self.index] = x;
+        self.index = (self.index + 1) % self.buffer.len();
+    }
+
+    // the sample written `delay` samples ago, the last write is 1 sample ago
+    fn at(&self, delay: usize) -> f64 {
+        let len = self.buffer.len();
+        self.buffer[(self.index + len - delay.max(1).min(len - 1)) % len]
+    }
+
+    /// Read the sample written `delay` samples ago, the last written sample is
+    /// one sample ago. The delay is clamped to 1.0 and the capacity.
+    pub fn read(&mut self, delay: f64, interpolation: Interpolation) -> f64 {
+        let delay = delay.max(1.0).min(self.capacity() as f64);
+        let i = delay.floor() as usize;
+        let f = delay - delay.floor();
+        match interpolation {
+            Interpolation::Linear => {
+                let (a, b) = (self.at(i), self.at(i + 1));
+                a + (b - a) * f
+            }
+            Interpolation::Hermite => {
+                let (xm1, x0, x1, x2) =
+                    (self.at(i - 1), self.at(i), self.at(i + 1), self.at(i + 2));
+                let c1 = 0.5 * (x1 - xm1);
+                let c2 = xm1 - 2.5 * x0 + 2.0 * x1 - 0.5 * x2;
+                let c3 = 0.5 * (x2 - xm1) + 1.5 * (x0 - x1);
+                ((c3 * f + c2) * f + c1) * f + x0
+            }
+            Interpolation::Allpass => {
+                // the allpass delays by `f` at low frequencies
+                let eta = (1.0 - f) / (1.0 + f);
+                self.allpass = eta * self.at(i) + self.at(i + 1) - eta * self.allpass;
+                self.allpass
+            }
+        }
+    }
+}
+
+/// The delay time of a Delay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelayTime {
+    Seconds(f64),
+    /// `beats` beats at `bpm` beats per minute.
+    Tempo {
+        bpm: f64,
+        beats: f64,
+    },
+}
+
+impl DelayTime {
+    /// The delay time in seconds.
+    pub fn seconds(&self) -> f64 {
+        match *self {
+            DelayTime::Seconds(seconds) => seconds,
+            DelayTime::Tempo { bpm, beats } => beats * 60.0 / bpm,
+        }
+    }
+}
+
+/// A feedback delay for every channel.
+///
+/// Input 0 is the signal, input 1 is added to the delay time in seconds.
+/// The feedback goes through an optional filter, with ping pong enabled
+/// channels 0 and 1 feed each other so the echoes alternate between them.
+pub struct Delay {
+    max_time: f64,
+    time: DelayTime,
+    feedback: f64,
+    mix: f64,
+    interpolation: Interpolation,
+    ping_pong: bool,
+    filter: Option<(FilterType, f64, f64)>,
+    samplerate: f64,
+    lines: Vec<DelayLine>,
+    section: Section,
+}
+
+impl Delay {
+    /// Create a Delay whose time can go up to `max_time` seconds.
+    /// It starts with a delay of `max_time`, no feedback and only the delayed signal.
+    pub fn new(max_time: f64) -> Self {
+        Delay {
+            max_time: max_time,
+            time: DelayTime::Seconds(max_time),
+            feedback: 0.0,
+            mix: 1.0,
+            interpolation: Interpolation::Linear,
+            ping_pong: false,
+            filter: None,
+            samplerate: 44_100.0,
+            lines: Vec::new(),
+            section: Section::new(Coefficients::identity()),
+        }
+    }
+
+    pub fn set_time(&mut self, time: DelayTime) {
+        self.time = time;
+    }
+
+    /// Set how much of the output is fed back, from 0.0 to below 1.0.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback.max(-0.999).min(0.999);
+    }
+
+    /// Set the balance between the dry signal (0.0) and the delayed signal (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn set_ping_pong(&mut self, ping_pong: bool) {
+        self.ping_pong = ping_pong;
+    }
+
+    /// Filter the feedback with a biquad, `None` removes the filter.
+    pub fn set_feedback_filter(&mut self, filter: Option<(FilterType, f64, f64)>) {
+        self.filter = filter;
+        self.update_filter();
+    }
+
+    fn update_filter(&mut self) {
+        let coefficients = match self.filter {
+            Some((kind, frequency, q)) => Coefficients::new(kind, frequency, q, self.samplerate),
+            None => Coefficients::identity(),
+        };
+        self.section.set_coefficients(coefficients);
+    }
+
+    fn allocate(&mut self, channels: usize) {
+        let capacity = (self.max_time * self.samplerate).ceil() as usize + 1;
+        self.lines = (0..channels).map(|_| DelayLine::new(capacity)).collect();
+        self.section.reset();
+    }
+}
+
+impl<F> Processor<F> for Delay
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.lines.len() != F::n_channels() {
+            self.allocate(F::n_channels());
+        }
+        let time = self.time.seconds() + control(&inputs[1]);
+        let delay = time * self.samplerate;
+        // sample::Frame is implemented for up to 32 channels
+        let mut delayed = [0.0; 32];
+        for channel in 0..F::n_channels() {
+            delayed[channel] = self.lines[channel].read(delay, self.interpolation);
+        }
+        let ping_pong = self.ping_pong && F::n_channels() >= 2;
+        for channel in 0..F::n_channels() {
+            let (input, feedback) = if !ping_pong || channel > 1 {
+                (channel_f64(&inputs[0], channel), delayed[channel])
+            } else if channel == 0 {
+                // both sides of the input start on channel 0
+                let input = (channel_f64(&inputs[0], 0) + channel_f64(&inputs[0], 1)) / 2.0;
+                (input, delayed[1])
+            } else {
+                (0.0, delayed[0])
+            };
+            let feedback = self.section.process(channel, feedback);
+            self.lines[channel].write(input + self.feedback * feedback);
+        }
+        let (dry, wet) = (1.0 - self.mix, self.mix);
+        outputs[0] = map_f64(inputs[0], |channel, x| dry * x + wet * delayed[channel]);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.update_filter();
+        self.allocate(F::n_channels());
+    }
+
+    fn tail_length(&self) -> usize {
+        let delay = self.time.seconds() * self.samplerate;
+        let feedback = self.feedback.abs();
+        // the echoes that are louder than -60dB
+        let echoes = if feedback > 0.0 {
+            (0.001f64.ln() / feedback.ln()).ceil() + 1.0
+        } else {
+            1.0
+        };
+        (delay * echoes).ceil() as usize
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("time", PortKind::Modulation)
+                .with_description("added to the delay time in seconds"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Parses `linear`, `hermite` or `allpass`.
+pub fn parse_interpolation(name: &str) -> Result<Interpolation, String> {
+    match name {
+        "linear" => Ok(Interpolation::Linear),
+        "hermite" => Ok(Interpolation::Hermite),
+        "allpass" => Ok(Interpolation::Allpass),
+        _ => Err(format!("unknown interpolation '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `delay(time: 0.5, max_time: 2, feedback: 0, mix: 0.5, interpolation: linear,
+///   ping_pong: 0)`, with a `bpm` parameter the time is `beats: 1` beats,
+///   a `filter: lowpass` parameter puts a filter with `cutoff: 5000` and `q: 0.707`
+///   into the feedback, every type of `filter::parse_filter_type` works
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("delay", |params: &Params| {
+        let time = match params.get("bpm") {
+            Some(_) => DelayTime::Tempo {
+                bpm: params.number("bpm", 120.0)?,
+                beats: params.number("beats", 1.0)?,
+            },
+            None => DelayTime::Seconds(params.number("time", 0.5)?),
+        };
+        let max_time = params.number("max_time", time.seconds().max(2.0))?;
+        let mut delay = Delay::new(max_time);
+        delay.set_time(time);
+        delay.set_feedback(params.number("feedback", 0.0)?);
+        delay.set_mix(params.number("mix", 0.5)?);
+        delay.set_interpolation(parse_interpolation(
+            &params.text("interpolation", "linear")?,
+        )?);
+        delay.set_ping_pong(params.number("ping_pong", 0.0)? != 0.0);
+        if params.get("filter").is_some() {
+            let kind = parse_filter_type(
+                &params.text("filter", "lowpass")?,
+                params.number("gain", 0.0)?,
+            )?;
+            let cutoff = params.number("cutoff", 5000.0)?;
+            let q = params.number("q", 0.707)?;
+            delay.set_feedback_filter(Some((kind, cutoff, q)));
+        }
+        Ok(Box::new(delay) as Box<Processor<F>>)
+    });
+}
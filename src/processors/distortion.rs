@@ -0,0 +1,372 @@
+//! Distortion: waveshapers and a bitcrusher.
+//!
+//! Both run their nonlinear part oversampled, so the harmonics they create
+//! above the Nyquist frequency are filtered instead of aliasing.
+//! The oversampling filters delay the signal, which is reported as latency,
+//! the dry part of the mix is delayed by the same amount.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::PI;
+use super::delay::{DelayLine, Interpolation};
+use super::oversample::{self, Oversampler};
+use super::util::db_to_gain;
+use super::{channel_f64, control, map_f64};
+
+// the bias of the tube curve, it makes the curve asymmetric
+const TUBE_BIAS: f64 = 0.3;
+// the pole of the filter that removes the offset of the tube curve
+const DC_POLE: f64 = 0.995;
+
+/// The transfer curves of a Waveshaper, all of them go from -1.0 to 1.0
+/// for inputs from -1.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Tanh,
+    /// A cubic curve that reaches 1.0 at an input of 1.0 and stays there.
+    SoftClip,
+    HardClip,
+    /// Reflects the parts above 1.0 and below -1.0 back into the range.
+    Foldback,
+    /// A sine, louder inputs fold smoothly many times.
+    Wavefolder,
+    /// An asymmetric curve, which adds even harmonics.
+    Tube,
+}
+
+impl Curve {
+    pub fn apply(&self, x: f64) -> f64 {
+        match *self {
+            Curve::Tanh => x.tanh(),
+            Curve::SoftClip => {
+                let x = x.max(-1.0).min(1.0);
+                1.5 * x - 0.5 * x * x * x
+            }
+            Curve::HardClip => x.max(-1.0).min(1.0),
+            Curve::Foldback => {
+                let y = (x + 1.0) / 4.0;
+                1.0 - (4.0 * (y - y.floor()) - 2.0).abs()
+            }
+            Curve::Wavefolder => (PI / 2.0 * x).sin(),
+            Curve::Tube => (x + TUBE_BIAS).tanh() - TUBE_BIAS.tanh(),
+        }
+    }
+}
+
+// the state of one channel
+struct Channel {
+    oversampler: Oversampler,
+    // the dry signal, delayed by the latency of the oversampler
+    dry: DelayLine,
+    // the last input and output of the offset filter
+    dc: (f64, f64),
+    // the bitcrusher holds `held` for `remaining` more samples
+    held: f64,
+    remaining: f64,
+}
+
+impl Channel {
+    fn new(oversampling: usize) -> Self {
+        // the factor was checked before
+        let oversampler = Oversampler::new(oversampling).unwrap();
+        Channel {
+            dry: DelayLine::new(oversampler.latency().max(1)),
+            oversampler: oversampler,
+            dc: (0.0, 0.0),
+            held: 0.0,
+            remaining: 0.0,
+        }
+    }
+
+    fn dry(&mut self, x: f64) -> f64 {
+        let latency = self.oversampler.latency();
+        if latency == 0 {
+            return x;
+        }
+        let y = self.dry.read(latency as f64, Interpolation::Linear);
+        self.dry.write(x);
+        y
+    }
+
+    fn remove_dc(&mut self, x: f64) -> f64 {
+        let y = x - self.dc.0 + DC_POLE * self.dc.1;
+        self.dc = (x, y);
+        y
+    }
+}
+
+fn channels(amount: usize, oversampling: usize) -> Vec<Channel> {
+    (0..amount).map(|_| Channel::new(oversampling)).collect()
+}
+
+/// Shapes every channel with a Curve, oversampled 4 times by default.
+///
+/// Input 0 is the signal, input 1 is added to the drive.
+pub struct Waveshaper {
+    curve: Curve,
+    drive: f64,
+    gain: f64,
+    mix: f64,
+    oversampling: usize,
+    channels: Vec<Channel>,
+}
+
+impl Waveshaper {
+    /// Create a Waveshaper with a drive and a gain of 1.0 and only the shaped signal.
+    pub fn new(curve: Curve) -> Self {
+        Waveshaper {
+            curve: curve,
+            drive: 1.0,
+            gain: 1.0,
+            mix: 1.0,
+            oversampling: 4,
+            channels: Vec::new(),
+        }
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    /// Set the gain before the curve.
+    pub fn set_drive(&mut self, drive: f64) {
+        self.drive = drive;
+    }
+
+    /// Set the gain after the curve.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+    }
+
+    /// Set the balance between the dry signal (0.0) and the shaped signal (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    /// Set the oversampling factor, 1, 2, 4 or 8.
+    pub fn set_oversampling(&mut self, oversampling: usize) -> Result<(), String> {
+        Oversampler::new(oversampling)?;
+        self.oversampling = oversampling;
+        self.channels.clear();
+        Ok(())
+    }
+}
+
+impl<F> Processor<F> for Waveshaper
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.channels.len() != F::n_channels() {
+            self.channels = channels(F::n_channels(), self.oversampling);
+        }
+        let (curve, drive) = (self.curve, self.drive + control(&inputs[1]));
+        // sample::Frame is implemented for up to 32 channels
+        let mut dry = [0.0; 32];
+        let mut wet = [0.0; 32];
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            let x = channel_f64(&inputs[0], i);
+            let y = channel.oversampler.process(x, |x| curve.apply(drive * x));
+            wet[i] = if curve == Curve::Tube {
+                channel.remove_dc(y)
+            } else {
+                y
+            };
+            dry[i] = channel.dry(x);
+        }
+        let (mix, gain) = (self.mix, self.gain);
+        outputs[0] = map_f64(inputs[0], |channel, _| {
+            (1.0 - mix) * dry[channel] + mix * gain * wet[channel]
+        });
+    }
+
+    fn set_samplerate(&mut self, _samplerate: usize) {
+        self.channels = channels(F::n_channels(), self.oversampling);
+    }
+
+    fn latency(&self) -> usize {
+        oversample::latency(self.oversampling)
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => {
+                PortInfo::new("drive", PortKind::Modulation).with_description("added to the drive")
+            }
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Reduces the resolution of every channel to a number of bits and holds every
+/// sample for a number of samples, not oversampled by default.
+///
+/// Input 0 is the signal, input 1 is added to the number of bits.
+pub struct Bitcrusher {
+    bits: f64,
+    downsample: f64,
+    mix: f64,
+    oversampling: usize,
+    channels: Vec<Channel>,
+}
+
+impl Bitcrusher {
+    /// Create a Bitcrusher, `bits` can be fractional, a `downsample` factor
+    /// of 1.0 holds no samples.
+    pub fn new(bits: f64, downsample: f64) -> Self {
+        Bitcrusher {
+            bits: bits,
+            downsample: downsample.max(1.0),
+            mix: 1.0,
+            oversampling: 1,
+            channels: Vec::new(),
+        }
+    }
+
+    pub fn set_bits(&mut self, bits: f64) {
+        self.bits = bits;
+    }
+
+    /// Set for how many samples every sample is held, at least 1.0.
+    pub fn set_downsample(&mut self, downsample: f64) {
+        self.downsample = downsample.max(1.0);
+    }
+
+    /// Set the balance between the dry signal (0.0) and the crushed signal (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    /// Set the oversampling factor, 1, 2, 4 or 8.
+    pub fn set_oversampling(&mut self, oversampling: usize) -> Result<(), String> {
+        Oversampler::new(oversampling)?;
+        self.oversampling = oversampling;
+        self.channels.clear();
+        Ok(())
+    }
+}
+
+impl<F> Processor<F> for Bitcrusher
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.channels.len() != F::n_channels() {
+            self.channels = channels(F::n_channels(), self.oversampling);
+        }
+        let bits = (self.bits + control(&inputs[1])).max(1.0);
+        let steps = (bits - 1.0).exp2();
+        let downsample = self.downsample;
+        // one sample at the higher samplerate is this part of an original sample
+        let step = 1.0 / self.oversampling as f64;
+        // sample::Frame is implemented for up to 32 channels
+        let mut dry = [0.0; 32];
+        let mut wet = [0.0; 32];
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            let x = channel_f64(&inputs[0], i);
+            let (held, remaining) = (&mut channel.held, &mut channel.remaining);
+            wet[i] = channel.oversampler.process(x, |x| {
+                if *remaining <= 0.0 {
+                    *remaining += downsample;
+                    *held = (x * steps).round() / steps;
+                }
+                *remaining -= step;
+                *held
+            });
+            dry[i] = channel.dry(x);
+        }
+        let mix = self.mix;
+        outputs[0] = map_f64(inputs[0], |channel, _| {
+            (1.0 - mix) * dry[channel] + mix * wet[channel]
+        });
+    }
+
+    fn set_samplerate(&mut self, _samplerate: usize) {
+        self.channels = channels(F::n_channels(), self.oversampling);
+    }
+
+    fn latency(&self) -> usize {
+        oversample::latency(self.oversampling)
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("bits", PortKind::Modulation)
+                .with_description("added to the number of bits"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Parses `tanh`, `soft_clip`, `hard_clip`, `foldback`, `wavefolder` or `tube`.
+pub fn parse_curve(name: &str) -> Result<Curve, String> {
+    match name {
+        "tanh" => Ok(Curve::Tanh),
+        "soft_clip" => Ok(Curve::SoftClip),
+        "hard_clip" => Ok(Curve::HardClip),
+        "foldback" => Ok(Curve::Foldback),
+        "wavefolder" => Ok(Curve::Wavefolder),
+        "tube" => Ok(Curve::Tube),
+        _ => Err(format!("unknown waveshaper curve '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry, drive and gain are in decibels:
+///
+/// * `waveshaper(curve: tanh, drive: 0, gain: 0, mix: 1, oversampling: 4)`,
+///   every curve of `parse_curve` works
+/// * `bitcrusher(bits: 8, downsample: 1, mix: 1, oversampling: 1)`
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("waveshaper", |params: &Params| {
+        let mut waveshaper = Waveshaper::new(parse_curve(&params.text("curve", "tanh")?)?);
+        waveshaper.set_drive(db_to_gain(params.number("drive", 0.0)?));
+        waveshaper.set_gain(db_to_gain(params.number("gain", 0.0)?));
+        waveshaper.set_mix(params.number("mix", 1.0)?);
+        waveshaper.set_oversampling(params.number("oversampling", 4.0)? as usize)?;
+        Ok(Box::new(waveshaper) as Box<Processor<F>>)
+    });
+    registry.register("bitcrusher", |params: &Params| {
+        let mut bitcrusher = Bitcrusher::new(
+            params.number("bits", 8.0)?,
+            params.number("downsample", 1.0)?,
+        );
+        bitcrusher.set_mix(params.number("mix", 1.0)?);
+        bitcrusher.set_oversampling(params.number("oversampling", 1.0)? as usize)?;
+        Ok(Box::new(bitcrusher) as Box<Processor<F>>)
+    });
+}
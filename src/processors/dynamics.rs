@@ -0,0 +1,648 @@
+//! Dynamics: compressor, expander, noise gate and lookahead limiter.
+//!
+//! All of them have the signal on input 0 and a sidechain on input 1.
+//! With the sidechain enabled the level is detected on input 1 instead of input 0.
+//! The channels are linked, every channel gets the same gain,
+//! which is calculated from the loudest channel.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use super::util::db_to_gain;
+use super::{channel_f64, map_f64};
+
+/// How the level of the signal is measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Detection {
+    Peak,
+    /// The root mean square over about 10ms.
+    Rms,
+}
+
+// the averaging time of the rms detection in seconds
+const RMS_TIME: f64 = 0.01;
+
+// the coefficient of a one pole filter that moves about 63% of the way in `time` seconds
+fn coefficient(time: f64, samplerate: f64) -> f64 {
+    if time <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time * samplerate)).exp()
+    }
+}
+
+fn gain_to_db(gain: f64) -> f64 {
+    20.0 * gain.max(1e-9).log10()
+}
+
+// the level in decibels of the loudest channel of `input` or of the sidechain
+struct Detector {
+    detection: Detection,
+    sidechain: bool,
+    mean_square: f64,
+    coefficient: f64,
+}
+
+impl Detector {
+    fn new() -> Self {
+        Detector {
+            detection: Detection::Peak,
+            sidechain: false,
+            mean_square: 0.0,
+            coefficient: 0.0,
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: f64) {
+        self.coefficient = coefficient(RMS_TIME, samplerate);
+    }
+
+    fn level<F>(&mut self, inputs: &FrameSet<F>) -> f64
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let input = if self.sidechain {
+            &inputs[1]
+        } else {
+            &inputs[0]
+        };
+        let peak = (0..F::n_channels()).fold(0.0f64, |peak, channel| {
+            peak.max(channel_f64(input, channel).abs())
+        });
+        match self.detection {
+            Detection::Peak => gain_to_db(peak),
+            Detection::Rms => {
+                self.mean_square =
+                    peak * peak + self.coefficient * (self.mean_square - peak * peak);
+                gain_to_db(self.mean_square.sqrt())
+            }
+        }
+    }
+}
+
+// smooths a gain in decibels with separate attack and release times,
+// the attack is used while the gain goes down
+struct Ballistics {
+    attack: f64,
+    release: f64,
+    attack_coefficient: f64,
+    release_coefficient: f64,
+    value: f64,
+}
+
+impl Ballistics {
+    fn new(attack: f64, release: f64) -> Self {
+        Ballistics {
+            attack: attack,
+            release: release,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            value: 0.0,
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: f64) {
+        self.attack_coefficient = coefficient(self.attack, samplerate);
+        self.release_coefficient = coefficient(self.release, samplerate);
+    }
+
+    fn process(&mut self, target: f64) -> f64 {
+        let coefficient = if target < self.value {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.value = target + coefficient * (self.value - target);
+        self.value
+    }
+}
+
+/// A feed forward compressor.
+pub struct Compressor {
+    threshold: f64,
+    ratio: f64,
+    knee: f64,
+    makeup: f64,
+    samplerate: f64,
+    detector: Detector,
+    ballistics: Ballistics,
+}
+
+impl Compressor {
+    /// Create a Compressor, the threshold is in decibels,
+    /// attack and release are in seconds.
+    pub fn new(threshold: f64, ratio: f64, attack: f64, release: f64) -> Self {
+        let mut compressor = Compressor {
+            threshold: threshold,
+            ratio: ratio.max(1.0),
+            knee: 0.0,
+            makeup: 0.0,
+            samplerate: 44_100.0,
+            detector: Detector::new(),
+            ballistics: Ballistics::new(attack, release),
+        };
+        compressor.update();
+        compressor
+    }
+
+    /// Set the width of the soft knee in decibels, 0.0 is a hard knee.
+    pub fn set_knee(&mut self, knee: f64) {
+        self.knee = knee.max(0.0);
+    }
+
+    /// Set the makeup gain in decibels.
+    pub fn set_makeup(&mut self, makeup: f64) {
+        self.makeup = makeup;
+    }
+
+    pub fn set_detection(&mut self, detection: Detection) {
+        self.detector.detection = detection;
+    }
+
+    pub fn set_sidechain(&mut self, sidechain: bool) {
+        self.detector.sidechain = sidechain;
+    }
+
+    /// The output level in decibels for an input level, without makeup gain.
+    pub fn curve(&self, level: f64) -> f64 {
+        let over = level - self.threshold;
+        let slope = 1.0 / self.ratio - 1.0;
+        if 2.0 * over < -self.knee {
+            level
+        } else if self.knee > 0.0 && 2.0 * over.abs() <= self.knee {
+            let x = over + self.knee / 2.0;
+            level + slope * x * x / (2.0 * self.knee)
+        } else {
+            self.threshold + over / self.ratio
+        }
+    }
+
+    fn update(&mut self) {
+        self.detector.set_samplerate(self.samplerate);
+        self.ballistics.set_samplerate(self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Compressor
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let level = self.detector.level(inputs);
+        let reduction = self.ballistics.process(self.curve(level) - level);
+        let gain = db_to_gain(reduction + self.makeup);
+        outputs[0] = map_f64(inputs[0], |_, x| x * gain);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.update();
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("sidechain", PortKind::Sidechain)
+                .with_description("the level is detected here while the sidechain is enabled"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// A downward expander, it makes signals below the threshold quieter.
+pub struct Expander {
+    threshold: f64,
+    ratio: f64,
+    range: f64,
+    samplerate: f64,
+    detector: Detector,
+    ballistics: Ballistics,
+}
+
+impl Expander {
+    /// Create an Expander, the threshold is in decibels,
+    /// attack and release are in seconds.
+    /// A ratio of 2.0 turns a signal 10dB below the threshold into one 20dB below it.
+    pub fn new(threshold: f64, ratio: f64, attack: f64, release: f64) -> Self {
+        let mut expander = Expander {
+            threshold: threshold,
+            ratio: ratio.max(1.0),
+            range: -80.0,
+            samplerate: 44_100.0,
+            detector: Detector::new(),
+            ballistics: Ballistics::new(attack, release),
+        };
+        expander.update();
+        expander
+    }
+
+    /// Set the largest gain reduction in decibels, a negative value.
+    pub fn set_range(&mut self, range: f64) {
+        self.range = range.min(0.0);
+    }
+
+    pub fn set_detection(&mut self, detection: Detection) {
+        self.detector.detection = detection;
+    }
+
+    pub fn set_sidechain(&mut self, sidechain: bool) {
+        self.detector.sidechain = sidechain;
+    }
+
+    fn update(&mut self) {
+        self.detector.set_samplerate(self.samplerate);
+        self.ballistics.set_samplerate(self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Expander
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let level = self.detector.level(inputs);
+        let under = (level - self.threshold).min(0.0);
+        let target = (under * (self.ratio - 1.0)).max(self.range);
+        let gain = db_to_gain(self.ballistics.process(target));
+        outputs[0] = map_f64(inputs[0], |_, x| x * gain);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.update();
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("sidechain", PortKind::Sidechain)
+                .with_description("the level is detected here while the sidechain is enabled"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// A noise gate. It opens when the level rises above the threshold and closes
+/// when the level falls below the threshold minus the hysteresis
+/// and the hold time has passed.
+pub struct Gate {
+    threshold: f64,
+    hysteresis: f64,
+    hold: f64,
+    range: f64,
+    samplerate: f64,
+    open: bool,
+    // samples left until the gate may close
+    holding: usize,
+    detector: Detector,
+    ballistics: Ballistics,
+}
+
+impl Gate {
+    /// Create a Gate, the threshold is in decibels,
+    /// attack and release are in seconds.
+    pub fn new(threshold: f64, attack: f64, release: f64) -> Self {
+        let mut gate = Gate {
+            threshold: threshold,
+            hysteresis: 6.0,
+            hold: 0.01,
+            range: -80.0,
+            samplerate: 44_100.0,
+            open: false,
+            holding: 0,
+            detector: Detector::new(),
+            ballistics: Ballistics::new(attack, release),
+        };
+        gate.ballistics.value = gate.range;
+        gate.update();
+        gate
+    }
+
+    /// Set how far below the threshold the level has to fall to close the gate, in decibels.
+    pub fn set_hysteresis(&mut self, hysteresis: f64) {
+        self.hysteresis = hysteresis.max(0.0);
+    }
+
+    /// Set how long the gate stays open after the level fell, in seconds.
+    pub fn set_hold(&mut self, hold: f64) {
+        self.hold = hold.max(0.0);
+    }
+
+    /// Set the gain of the closed gate in decibels, a negative value.
+    pub fn set_range(&mut self, range: f64) {
+        self.range = range.min(0.0);
+    }
+
+    pub fn set_detection(&mut self, detection: Detection) {
+        self.detector.detection = detection;
+    }
+
+    pub fn set_sidechain(&mut self, sidechain: bool) {
+        self.detector.sidechain = sidechain;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn update(&mut self) {
+        self.detector.set_samplerate(self.samplerate);
+        self.ballistics.set_samplerate(self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Gate
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let level = self.detector.level(inputs);
+        if level > self.threshold {
+            self.open = true;
+            self.holding = (self.hold * self.samplerate) as usize;
+        } else if level < self.threshold - self.hysteresis {
+            if self.holding > 0 {
+                self.holding -= 1;
+            } else {
+                self.open = false;
+            }
+        }
+        let target = if self.open { 0.0 } else { self.range };
+        let gain = db_to_gain(self.ballistics.process(target));
+        outputs[0] = map_f64(inputs[0], |_, x| x * gain);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.update();
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("sidechain", PortKind::Sidechain)
+                .with_description("the level is detected here while the sidechain is enabled"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// A brickwall limiter. It looks ahead by delaying the signal,
+/// so the gain is already down when a peak arrives and no sample exceeds the ceiling.
+pub struct Limiter {
+    ceiling: f64,
+    lookahead: f64,
+    release: f64,
+    samplerate: f64,
+    sidechain: bool,
+    // the length of the lookahead in samples
+    length: usize,
+    // ring buffers of the delayed signal, the needed gains and their minimums
+    delayed: Vec<Vec<f64>>,
+    gains: Vec<f64>,
+    minimums: Vec<f64>,
+    index: usize,
+    // the sum of `minimums`, for the moving average
+    sum: f64,
+    gain: f64,
+    release_coefficient: f64,
+}
+
+impl Limiter {
+    /// Create a Limiter, the ceiling is in decibels,
+    /// lookahead and release are in seconds.
+    pub fn new(ceiling: f64, lookahead: f64, release: f64) -> Self {
+        let mut limiter = Limiter {
+            ceiling: db_to_gain(ceiling),
+            lookahead: lookahead,
+            release: release,
+            samplerate: 44_100.0,
+            sidechain: false,
+            length: 1,
+            delayed: Vec::new(),
+            gains: Vec::new(),
+            minimums: Vec::new(),
+            index: 0,
+            sum: 0.0,
+            gain: 1.0,
+            release_coefficient: 0.0,
+        };
+        limiter.allocate(0);
+        limiter
+    }
+
+    pub fn set_sidechain(&mut self, sidechain: bool) {
+        self.sidechain = sidechain;
+    }
+
+    fn allocate(&mut self, channels: usize) {
+        self.length = ((self.lookahead * self.samplerate).round() as usize).max(1);
+        self.delayed = vec![vec![0.0; channels]; self.length];
+        self.gains = vec![1.0; self.length];
+        self.minimums = vec![1.0; self.length];
+        self.index = 0;
+        self.sum = self.length as f64;
+        self.gain = 1.0;
+        self.release_coefficient = coefficient(self.release, self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Limiter
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let channels = F::n_channels();
+        if self.delayed[0].len() != channels {
+            self.allocate(channels);
+        }
+        let detected = if self.sidechain {
+            &inputs[1]
+        } else {
+            &inputs[0]
+        };
+        let peak = (0..channels).fold(0.0f64, |peak, channel| {
+            peak.max(channel_f64(detected, channel).abs())
+        });
+        let needed = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+        let index = self.index;
+        self.gains[index] = needed;
+        // the smallest gain needed over the lookahead, the lookahead is short
+        // enough to search it every sample
+        let minimum = self.gains.iter().fold(1.0f64, |a, &b| a.min(b));
+        self.sum += minimum - self.minimums[index];
+        self.minimums[index] = minimum;
+        if index == 0 {
+            // get rid of the rounding errors the running sum collected
+            self.sum = self.minimums.iter().sum();
+        }
+        // the moving average reaches `minimum` exactly when the peak leaves the delay
+        let smoothed = (self.sum / self.length as f64).min(1.0);
+        self.gain = if smoothed < self.gain {
+            smoothed
+        } else {
+            smoothed + self.release_coefficient * (self.gain - smoothed)
+        };
+        // the oldest frame of the delay is the one `length - 1` frames ago
+        let oldest = (index + 1) % self.length;
+        for channel in 0..channels {
+            self.delayed[index][channel] = channel_f64(&inputs[0], channel);
+        }
+        let (gain, ceiling) = (self.gain, self.ceiling);
+        let delayed = &self.delayed[oldest];
+        outputs[0] = map_f64(inputs[0], |channel, _| {
+            (delayed[channel] * gain).max(-ceiling).min(ceiling)
+        });
+        self.index = oldest;
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.allocate(F::n_channels());
+    }
+
+    fn latency(&self) -> usize {
+        self.length - 1
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("sidechain", PortKind::Sidechain)
+                .with_description("the level is detected here while the sidechain is enabled"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Parses `peak` or `rms`.
+pub fn parse_detection(name: &str) -> Result<Detection, String> {
+    match name {
+        "peak" => Ok(Detection::Peak),
+        "rms" => Ok(Detection::Rms),
+        _ => Err(format!("unknown detection '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry, all levels are in decibels
+/// and all times in seconds:
+///
+/// * `compressor(threshold: -20, ratio: 4, attack: 0.01, release: 0.1, knee: 6,
+///   makeup: 0, detection: peak)`, the detection can also be `rms`
+/// * `expander(threshold: -40, ratio: 2, attack: 0.001, release: 0.1, range: -80,
+///   detection: peak)`
+/// * `gate(threshold: -50, hysteresis: 6, attack: 0.001, hold: 0.01, release: 0.1,
+///   range: -80, detection: peak)`
+/// * `limiter(ceiling: -0.1, lookahead: 0.005, release: 0.05)`
+///
+/// With `sidechain: 1` the level is detected on the `sidechain` input.
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("compressor", |params: &Params| {
+        let mut compressor = Compressor::new(
+            params.number("threshold", -20.0)?,
+            params.number("ratio", 4.0)?,
+            params.number("attack", 0.01)?,
+            params.number("release", 0.1)?,
+        );
+        compressor.set_knee(params.number("knee", 6.0)?);
+        compressor.set_makeup(params.number("makeup", 0.0)?);
+        compressor.set_detection(parse_detection(&params.text("detection", "peak")?)?);
+        compressor.set_sidechain(params.number("sidechain", 0.0)? != 0.0);
+        Ok(Box::new(compressor) as Box<Processor<F>>)
+    });
+    registry.register("expander", |params: &Params| {
+        let mut expander = Expander::new(
+            params.number("threshold", -40.0)?,
+            params.number("ratio", 2.0)?,
+            params.number("attack", 0.001)?,
+            params.number("release", 0.1)?,
+        );
+        expander.set_range(params.number("range", -80.0)?);
+        expander.set_detection(parse_detection(&params.text("detection", "peak")?)?);
+        expander.set_sidechain(params.number("sidechain", 0.0)? != 0.0);
+        Ok(Box::new(expander) as Box<Processor<F>>)
+    });
+    registry.register("gate", |params: &Params| {
+        let mut gate = Gate::new(
+            params.number("threshold", -50.0)?,
+            params.number("attack", 0.001)?,
+            params.number("release", 0.1)?,
+        );
+        gate.set_hysteresis(params.number("hysteresis", 6.0)?);
+        gate.set_hold(params.number("hold", 0.01)?);
+        gate.set_range(params.number("range", -80.0)?);
+        gate.set_detection(parse_detection(&params.text("detection", "peak")?)?);
+        gate.set_sidechain(params.number("sidechain", 0.0)? != 0.0);
+        Ok(Box::new(gate) as Box<Processor<F>>)
+    });
+    registry.register("limiter", |params: &Params| {
+        let mut limiter = Limiter::new(
+            params.number("ceiling", -0.1)?,
+            params.number("lookahead", 0.005)?,
+            params.number("release", 0.05)?,
+        );
+        limiter.set_sidechain(params.number("sidechain", 0.0)? != 0.0);
+        Ok(Box::new(limiter) as Box<Processor<F>>)
+    });
+}
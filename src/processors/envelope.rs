@@ -0,0 +1,337 @@
+//! Envelope generators.
+//!
+//! An Envelope starts its attack when its gate input (input 0) rises above 0.0
+//! and its release when the gate falls back to 0.0 or below.
+//! Input 1 takes note events, a NoteOn starts the attack
+//! and a NoteOff for the same note the release.
+//! The output goes from 0.0 to 1.0 and is written to every channel of output 0,
+//! so it can be connected to the modulation input of any processor.
+//! It is a control output, in a Graph it carries the level once per block.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{Event, EventKind, PortInfo, PortKind, PortType, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use super::{broadcast, control, Schedule};
+
+/// The shape of the stages of an Envelope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// Like an analog envelope, the attack slows down towards its peak,
+    /// decay and release slow down towards their end.
+    Exponential,
+}
+
+/// What happens when the gate rises while the Envelope is still releasing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retrigger {
+    /// Jump to 0.0 and start the attack.
+    Restart,
+    /// Start the attack from the current level.
+    Continue,
+    /// Skip the attack and go back to the sustain level,
+    /// a trigger after the release finished starts a normal attack.
+    Legato,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Idle,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+impl Stage {
+    // the stage that follows when this one reached its end
+    fn next(&self) -> Stage {
+        match *self {
+            Stage::Attack => Stage::Hold,
+            Stage::Hold => Stage::Decay,
+            Stage::Decay => Stage::Sustain,
+            Stage::Sustain => Stage::Sustain,
+            Stage::Release | Stage::Idle => Stage::Idle,
+        }
+    }
+}
+
+// how far exponential stages overshoot their end, relative to their range.
+// the small value makes decay and release close to a real exponential decay,
+// the larger one gives the attack its usual convex shape
+const ATTACK_OVERSHOOT: f64 = 0.3;
+const DECAY_OVERSHOOT: f64 = 0.001;
+
+/// An AHDSR envelope, an ADSR envelope is one without hold time.
+/// The times are in seconds, the sustain level goes from 0.0 to 1.0.
+pub struct Envelope {
+    attack: f64,
+    hold: f64,
+    decay: f64,
+    sustain: f64,
+    release: f64,
+    curve: Curve,
+    retrigger: Retrigger,
+    samplerate: f64,
+    stage: Stage,
+    level: f64,
+    gate: bool,
+    // the gate input in the last Frame, events and trigger()
+    // are only undone by an edge of it
+    gate_input: bool,
+    // the note of the last NoteOn
+    note: Option<u8>,
+    schedule: Schedule,
+    // the current stage ends at `end` after `remaining` samples,
+    // exponential stages approach `target` with `coefficient`,
+    // linear ones add `step` every sample
+    end: f64,
+    target: f64,
+    coefficient: f64,
+    step: f64,
+    remaining: usize,
+}
+
+impl Envelope {
+    pub fn adsr(attack: f64, decay: f64, sustain: f64, release: f64) -> Self {
+        Envelope::ahdsr(attack, 0.0, decay, sustain, release)
+    }
+
+    pub fn ahdsr(attack: f64, hold: f64, decay: f64, sustain: f64, release: f64) -> Self {
+        Envelope {
+            attack: attack,
+            hold: hold,
+            decay: decay,
+            sustain: sustain.max(0.0).min(1.0),
+            release: release,
+            curve: Curve::Exponential,
+            retrigger: Retrigger::Restart,
+            samplerate: 44_100.0,
+            stage: Stage::Idle,
+            level: 0.0,
+            gate: false,
+            gate_input: false,
+            note: None,
+            schedule: Schedule::new(),
+            end: 0.0,
+            target: 0.0,
+            coefficient: 0.0,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    pub fn set_retrigger(&mut self, retrigger: Retrigger) {
+        self.retrigger = retrigger;
+    }
+
+    pub fn set_sustain(&mut self, sustain: f64) {
+        self.sustain = sustain.max(0.0).min(1.0);
+    }
+
+    /// The current output of the envelope.
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Whether the envelope finished its release.
+    pub fn is_idle(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    /// Open the gate, like a rising gate input.
+    pub fn trigger(&mut self) {
+        self.gate = true;
+        if self.retrigger == Retrigger::Legato && self.stage != Stage::Idle {
+            self.enter(Stage::Decay);
+            return;
+        }
+        if self.retrigger == Retrigger::Restart {
+            self.level = 0.0;
+        }
+        self.enter(Stage::Attack);
+    }
+
+    /// Close the gate, like a falling gate input.
+    pub fn release(&mut self) {
+        self.gate = false;
+        if self.stage != Stage::Idle {
+            self.enter(Stage::Release);
+        }
+    }
+
+    /// Calculate the next level.
+    pub fn next(&mut self) -> f64 {
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Sustain => self.level = self.sustain,
+            stage => {
+                self.remaining -= 1;
+                if self.remaining == 0 {
+                    self.level = self.end;
+                    self.enter(stage.next());
+                } else if stage != Stage::Hold {
+                    self.level = match self.curve {
+                        Curve::Linear => self.level + self.step,
+                        Curve::Exponential => {
+                            self.target + (self.level - self.target) * self.coefficient
+                        }
+                    };
+                }
+            }
+        }
+        self.level
+    }
+
+    // starts a stage from the current level
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        let (end, time, overshoot) = match stage {
+            Stage::Attack => (1.0, self.attack, ATTACK_OVERSHOOT),
+            Stage::Decay => (self.sustain, self.decay, DECAY_OVERSHOOT),
+            Stage::Release => (0.0, self.release, DECAY_OVERSHOOT),
+            Stage::Hold => (self.level, self.hold, 0.0),
+            Stage::Idle | Stage::Sustain => return,
+        };
+        let samples = (time * self.samplerate).round();
+        if samples < 1.0 || (end == self.level && stage != Stage::Hold) {
+            self.level = end;
+            self.enter(stage.next());
+            return;
+        }
+        self.remaining = samples as usize;
+        self.end = end;
+        self.step = (end - self.level) / samples;
+        // with this target and coefficient the level reaches `end` after `samples`
+        self.target = end + (end - self.level) * overshoot;
+        self.coefficient = (overshoot / (1.0 + overshoot)).powf(1.0 / samples);
+    }
+}
+
+impl<F> Processor<F> for Envelope
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        while let Some(kind) = self.schedule.due() {
+            match kind {
+                EventKind::NoteOn { note, .. } => {
+                    self.note = Some(note);
+                    self.trigger();
+                }
+                EventKind::NoteOff { note } if self.note == Some(note) => {
+                    self.note = None;
+                    self.release();
+                }
+                _ => {}
+            }
+        }
+        self.schedule.tick();
+        let gate = control(&inputs[0]) > 0.0;
+        if gate && !self.gate_input {
+            self.trigger();
+        } else if !gate && self.gate_input && self.gate {
+            self.release();
+        }
+        self.gate_input = gate;
+        outputs[0] = broadcast(self.next());
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        if input == 1 {
+            PortType::Event
+        } else {
+            PortType::Audio
+        }
+    }
+
+    fn output_type(&self, _: usize) -> PortType {
+        PortType::Control
+    }
+
+    fn handle_events(&mut self, _: usize, events: &[Event]) {
+        self.schedule.add(events);
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("gate", PortKind::Modulation)
+                .with_description("the envelope is open while the gate is above 0.0"),
+            _ => {
+                PortInfo::new("note", PortKind::Main).with_description("NoteOn and NoteOff events")
+            }
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Parses `linear` or `exponential`.
+pub fn parse_curve(name: &str) -> Result<Curve, String> {
+    match name {
+        "linear" => Ok(Curve::Linear),
+        "exponential" => Ok(Curve::Exponential),
+        _ => Err(format!("unknown curve '{}'", name)),
+    }
+}
+
+/// Parses `restart`, `continue` or `legato`.
+pub fn parse_retrigger(name: &str) -> Result<Retrigger, String> {
+    match name {
+        "restart" => Ok(Retrigger::Restart),
+        "continue" => Ok(Retrigger::Continue),
+        "legato" => Ok(Retrigger::Legato),
+        _ => Err(format!("unknown retrigger mode '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `adsr(attack: 0.01, decay: 0.1, sustain: 0.7, release: 0.2,
+///   curve: exponential, retrigger: restart)`, the curve can also be `linear`,
+///   the retrigger mode `continue` or `legato`
+/// * `ahdsr(hold: 0, ...)` with the same parameters and a hold time
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    for &name in &["adsr", "ahdsr"] {
+        registry.register(name, |params: &Params| {
+            let mut envelope = Envelope::ahdsr(
+                params.number("attack", 0.01)?,
+                params.number("hold", 0.0)?,
+                params.number("decay", 0.1)?,
+                params.number("sustain", 0.7)?,
+                params.number("release", 0.2)?,
+            );
+            envelope.set_curve(parse_curve(&params.text("curve", "exponential")?)?);
+            envelope.set_retrigger(parse_retrigger(&params.text("retrigger", "restart")?)?);
+            Ok(Box::new(envelope) as Box<Processor<F>>)
+        });
+    }
+}
@@ -0,0 +1,143 @@
+//! A radix-2 fast fourier transform for the processors that work in the
+//! frequency domain. It does not allocate after it was created.
+
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+/// A complex number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Complex { re: re, im: im }
+    }
+
+    pub fn zero() -> Self {
+        Complex::new(0.0, 0.0)
+    }
+
+    pub fn conj(self) -> Self {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn abs(self) -> f64 {
+        (self.re * self.re + self.im * self.im).sqrt()
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// A transform of a fixed size, which has to be a power of two.
+pub struct Fft {
+    size: usize,
+    twiddles: Vec<Complex>,
+    reversed: Vec<usize>,
+}
+
+impl Fft {
+    pub fn new(size: usize) -> Result<Self, String> {
+        if !size.is_power_of_two() {
+            return Err(format!("the fft size {} is not a power of two", size));
+        }
+        let bits = size.trailing_zeros();
+        let twiddles = (0..size / 2)
+            .map(|i| {
+                let angle = -2.0 * PI * i as f64 / size as f64;
+                Complex::new(angle.cos(), angle.sin())
+            })
+            .collect();
+        let reversed = (0..size).map(|i| reverse_bits(i, bits)).collect();
+        Ok(Fft {
+            size: size,
+            twiddles: twiddles,
+            reversed: reversed,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Transform `data` into the frequency domain, in place.
+    /// `data` has to have the size of the transform.
+    pub fn forward(&self, data: &mut [Complex]) {
+        self.transform(data, false);
+    }
+
+    /// Transform `data` back into the time domain, in place.
+    /// The result is scaled, so `inverse` undoes `forward`.
+    pub fn inverse(&self, data: &mut [Complex]) {
+        self.transform(data, true);
+        let scale = 1.0 / self.size as f64;
+        for x in data.iter_mut() {
+            x.re *= scale;
+            x.im *= scale;
+        }
+    }
+
+    fn transform(&self, data: &mut [Complex], inverse: bool) {
+        assert_eq!(data.len(), self.size);
+        for i in 0..self.size {
+            let j = self.reversed[i];
+            if i < j {
+                data.swap(i, j);
+            }
+        }
+        let mut len = 2;
+        while len <= self.size {
+            let stride = self.size / len;
+            for start in (0..self.size).step_by(len) {
+                for k in 0..len / 2 {
+                    let mut twiddle = self.twiddles[k * stride];
+                    if inverse {
+                        twiddle = twiddle.conj();
+                    }
+                    let a = data[start + k];
+                    let b = data[start + k + len / 2] * twiddle;
+                    data[start + k] = a + b;
+                    data[start + k + len / 2] = a - b;
+                }
+            }
+            len *= 2;
+        }
+    }
+}
+
+// reverses the lowest `bits` bits of `i`
+fn reverse_bits(mut i: usize, bits: u32) -> usize {
+    let mut reversed = 0;
+    for _ in 0..bits {
+        reversed = (reversed << 1) | (i & 1);
+        i >>= 1;
+    }
+    reversed
+}
@@ -0,0 +1,612 @@
+//! Filters: RBJ biquads, cascaded Butterworth and Linkwitz-Riley filters
+//! and a state variable filter whose cutoff can be modulated per sample.
+//!
+//! Every channel of a Frame is filtered separately.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::PI;
+use super::{channel_f64, control, map_f64};
+
+/// The responses of a biquad, from the Audio EQ Cookbook by Robert Bristow-Johnson.
+/// The gains of the peak and shelf filters are in decibels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterType {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+    Allpass,
+    Peak(f64),
+    LowShelf(f64),
+    HighShelf(f64),
+}
+
+/// The normalized coefficients of a biquad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+// the highest frequency a filter can be tuned to, relative to the samplerate
+const MAX_FREQUENCY: f64 = 0.49;
+
+impl Coefficients {
+    /// Coefficients that leave the signal unchanged.
+    pub fn identity() -> Self {
+        Coefficients {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        }
+    }
+
+    /// Calculate the coefficients of a biquad.
+    pub fn new(kind: FilterType, frequency: f64, q: f64, samplerate: f64) -> Self {
+        let frequency = frequency.max(1.0).min(samplerate * MAX_FREQUENCY);
+        let w0 = 2.0 * PI * frequency / samplerate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q.max(1e-3));
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            FilterType::Lowpass => {
+                let b = (1.0 - cos) / 2.0;
+                (b, 1.0 - cos, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            FilterType::Highpass => {
+                let b = (1.0 + cos) / 2.0;
+                (b, -1.0 - cos, b, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+            }
+            FilterType::Bandpass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            FilterType::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            FilterType::Allpass => (
+                1.0 - alpha,
+                -2.0 * cos,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            FilterType::Peak(gain) => {
+                let a = 10f64.powf(gain / 40.0);
+                (
+                    1.0 + alpha * a,
+                    -2.0 * cos,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    -2.0 * cos,
+                    1.0 - alpha / a,
+                )
+            }
+            FilterType::LowShelf(gain) => {
+                let a = 10f64.powf(gain / 40.0);
+                let s = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + s),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - s),
+                    (a + 1.0) + (a - 1.0) * cos + s,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - s,
+                )
+            }
+            FilterType::HighShelf(gain) => {
+                let a = 10f64.powf(gain / 40.0);
+                let s = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + s),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - s),
+                    (a + 1.0) - (a - 1.0) * cos + s,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - s,
+                )
+            }
+        };
+        Coefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Calculate the coefficients of a first order lowpass or highpass,
+    /// any other type is treated as a lowpass.
+    pub fn first_order(kind: FilterType, frequency: f64, samplerate: f64) -> Self {
+        let frequency = frequency.max(1.0).min(samplerate * MAX_FREQUENCY);
+        let k = (PI * frequency / samplerate).tan();
+        let a1 = (k - 1.0) / (k + 1.0);
+        let (b0, b1) = match kind {
+            FilterType::Highpass => (1.0 / (k + 1.0), -1.0 / (k + 1.0)),
+            _ => (k / (k + 1.0), k / (k + 1.0)),
+        };
+        Coefficients {
+            b0: b0,
+            b1: b1,
+            b2: 0.0,
+            a1: a1,
+            a2: 0.0,
+        }
+    }
+
+    /// The gain of the filter at a frequency.
+    pub fn magnitude(&self, frequency: f64, samplerate: f64) -> f64 {
+        let w = 2.0 * PI * frequency / samplerate;
+        // evaluate numerator and denominator at z = e^(jw)
+        let (re_b, im_b) = (
+            self.b0 + self.b1 * w.cos() + self.b2 * (2.0 * w).cos(),
+            -self.b1 * w.sin() - self.b2 * (2.0 * w).sin(),
+        );
+        let (re_a, im_a) = (
+            1.0 + self.a1 * w.cos() + self.a2 * (2.0 * w).cos(),
+            -self.a1 * w.sin() - self.a2 * (2.0 * w).sin(),
+        );
+        ((re_b * re_b + im_b * im_b) / (re_a * re_a + im_a * im_a)).sqrt()
+    }
+}
+
+/// A biquad with its state for every channel, in transposed direct form II.
+/// Processors use it to filter inside their own signal path, like a feedback loop.
+pub struct Section {
+    coefficients: Coefficients,
+    state: Vec<[f64; 2]>,
+}
+
+impl Section {
+    pub fn new(coefficients: Coefficients) -> Self {
+        Section {
+            coefficients: coefficients,
+            state: Vec::new(),
+        }
+    }
+
+    /// Change the coefficients and keep the state.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn reset(&mut self) {
+        for state in &mut self.state {
+            *state = [0.0; 2];
+        }
+    }
+
+    /// Filter one sample of a channel.
+    pub fn process(&mut self, channel: usize, x: f64) -> f64 {
+        if channel >= self.state.len() {
+            self.state.resize(channel + 1, [0.0; 2]);
+        }
+        let c = &self.coefficients;
+        let s = &mut self.state[channel];
+        let y = c.b0 * x + s[0];
+        s[0] = c.b1 * x - c.a1 * y + s[1];
+        s[1] = c.b2 * x - c.a2 * y;
+        y
+    }
+}
+
+/// A biquad filter with one input and one output.
+pub struct Biquad {
+    kind: FilterType,
+    frequency: f64,
+    q: f64,
+    samplerate: f64,
+    section: Section,
+}
+
+impl Biquad {
+    pub fn new(kind: FilterType, frequency: f64, q: f64) -> Self {
+        let mut biquad = Biquad {
+            kind: kind,
+            frequency: frequency,
+            q: q,
+            samplerate: 44_100.0,
+            section: Section::new(Coefficients::identity()),
+        };
+        biquad.update();
+        biquad
+    }
+
+    pub fn set_kind(&mut self, kind: FilterType) {
+        self.kind = kind;
+        self.update();
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+        self.update();
+    }
+
+    pub fn set_q(&mut self, q: f64) {
+        self.q = q;
+        self.update();
+    }
+
+    pub fn coefficients(&self) -> Coefficients {
+        self.section.coefficients
+    }
+
+    fn update(&mut self) {
+        self.section.coefficients =
+            Coefficients::new(self.kind, self.frequency, self.q, self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Biquad
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let section = &mut self.section;
+        outputs[0] = map_f64(inputs[0], |channel, x| section.process(channel, x));
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.update();
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// The alignment of a Cascade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    /// Maximally flat passband, -3dB at the cutoff.
+    Butterworth,
+    /// Two Butterworth filters of half the order in series, -6dB at the cutoff.
+    /// The lowpass and highpass outputs add up to a flat response,
+    /// which makes it the usual choice for crossovers.
+    LinkwitzRiley,
+}
+
+/// A lowpass or highpass of any order, made of biquads in series.
+pub struct Cascade {
+    alignment: Alignment,
+    kind: FilterType,
+    order: usize,
+    frequency: f64,
+    samplerate: f64,
+    sections: Vec<Section>,
+}
+
+impl Cascade {
+    /// Create a cascade, `kind` has to be `FilterType::Lowpass` or `FilterType::Highpass`.
+    /// Linkwitz-Riley filters need an even order.
+    pub fn new(
+        alignment: Alignment,
+        kind: FilterType,
+        order: usize,
+        frequency: f64,
+    ) -> Result<Self, String> {
+        match kind {
+            FilterType::Lowpass | FilterType::Highpass => {}
+            _ => {
+                return Err(format!(
+                    "{:?} can only be a lowpass or a highpass",
+                    alignment
+                ))
+            }
+        }
+        if order == 0 || (alignment == Alignment::LinkwitzRiley && order % 2 != 0) {
+            return Err(format!(
+                "a {:?} filter can not have order {}",
+                alignment, order
+            ));
+        }
+        let mut cascade = Cascade {
+            alignment: alignment,
+            kind: kind,
+            order: order,
+            frequency: frequency,
+            samplerate: 44_100.0,
+            sections: Vec::new(),
+        };
+        cascade.update();
+        Ok(cascade)
+    }
+
+    pub fn butterworth(kind: FilterType, order: usize, frequency: f64) -> Result<Self, String> {
+        Cascade::new(Alignment::Butterworth, kind, order, frequency)
+    }
+
+    pub fn linkwitz_riley(kind: FilterType, order: usize, frequency: f64) -> Result<Self, String> {
+        Cascade::new(Alignment::LinkwitzRiley, kind, order, frequency)
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+        self.update();
+    }
+
+    /// The coefficients of all sections.
+    pub fn coefficients(&self) -> Vec<Coefficients> {
+        self.sections.iter().map(|s| s.coefficients).collect()
+    }
+
+    fn update(&mut self) {
+        let mut coefficients = butterworth(self.kind, self.order, self.frequency, self.samplerate);
+        if self.alignment == Alignment::LinkwitzRiley {
+            coefficients = butterworth(self.kind, self.order / 2, self.frequency, self.samplerate);
+            let copy = coefficients.clone();
+            coefficients.extend(copy);
+        }
+        if self.sections.len() == coefficients.len() {
+            // keep the state when only the frequency changes
+            for (section, c) in self.sections.iter_mut().zip(coefficients) {
+                section.coefficients = c;
+            }
+        } else {
+            self.sections = coefficients.into_iter().map(Section::new).collect();
+        }
+    }
+}
+
+// the sections of a butterworth filter
+fn butterworth(
+    kind: FilterType,
+    order: usize,
+    frequency: f64,
+    samplerate: f64,
+) -> Vec<Coefficients> {
+    let mut sections = Vec::new();
+    if order % 2 != 0 {
+        sections.push(Coefficients::first_order(kind, frequency, samplerate));
+    }
+    for k in 0..order / 2 {
+        // the angle of the pole pair from the negative real axis
+        let angle = if order % 2 == 0 {
+            PI * (2 * k + 1) as f64 / (2 * order) as f64
+        } else {
+            PI * (k + 1) as f64 / order as f64
+        };
+        let q = 1.0 / (2.0 * angle.cos());
+        sections.push(Coefficients::new(kind, frequency, q, samplerate));
+    }
+    sections
+}
+
+impl<F> Processor<F> for Cascade
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let sections = &mut self.sections;
+        outputs[0] = map_f64(inputs[0], |channel, x| {
+            sections
+                .iter_mut()
+                .fold(x, |x, section| section.process(channel, x))
+        });
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.update();
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// A state variable filter in the topology preserving transform form,
+/// which stays stable when its cutoff changes every sample.
+///
+/// Input 0 is the signal, input 1 is added to the cutoff in Hz.
+/// The outputs are lowpass, bandpass, highpass and notch.
+pub struct Svf {
+    frequency: f64,
+    q: f64,
+    samplerate: f64,
+    // the cutoff the coefficients were calculated for
+    current: f64,
+    g: f64,
+    state: Vec<[f64; 2]>,
+}
+
+impl Svf {
+    pub fn new(frequency: f64, q: f64) -> Self {
+        Svf {
+            frequency: frequency,
+            q: q,
+            samplerate: 44_100.0,
+            current: ::std::f64::NAN,
+            g: 0.0,
+            state: Vec::new(),
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    pub fn set_q(&mut self, q: f64) {
+        self.q = q;
+    }
+
+    // returns lowpass, bandpass and highpass for one sample of a channel
+    fn tick(&mut self, channel: usize, x: f64) -> (f64, f64, f64) {
+        let k = 1.0 / self.q.max(1e-3);
+        let g = self.g;
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        let s = &mut self.state[channel];
+        let v3 = x - s[1];
+        let v1 = a1 * s[0] + a2 * v3;
+        let v2 = s[1] + a2 * s[0] + a3 * v3;
+        s[0] = 2.0 * v1 - s[0];
+        s[1] = 2.0 * v2 - s[1];
+        (v2, v1, x - k * v1 - v2)
+    }
+}
+
+impl<F> Processor<F> for Svf
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let cutoff = (self.frequency + control(&inputs[1]))
+            .max(1.0)
+            .min(self.samplerate * MAX_FREQUENCY);
+        if cutoff != self.current {
+            self.current = cutoff;
+            self.g = (PI * cutoff / self.samplerate).tan();
+        }
+        if self.state.len() < F::n_channels() {
+            self.state.resize(F::n_channels(), [0.0; 2]);
+        }
+        // sample::Frame is implemented for up to 32 channels
+        let mut results = [(0.0, 0.0, 0.0); 32];
+        for channel in 0..F::n_channels() {
+            results[channel] = self.tick(channel, channel_f64(&inputs[0], channel));
+        }
+        outputs[0] = map_f64(inputs[0], |channel, _| results[channel].0);
+        outputs[1] = map_f64(inputs[0], |channel, _| results[channel].1);
+        outputs[2] = map_f64(inputs[0], |channel, _| results[channel].2);
+        outputs[3] = map_f64(inputs[0], |channel, _| {
+            results[channel].0 + results[channel].2
+        });
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.current = ::std::f64::NAN;
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("cutoff", PortKind::Modulation)
+                .with_description("added to the cutoff in Hz"),
+        }
+    }
+
+    fn output_info(&self, output: usize) -> PortInfo {
+        let name = match output {
+            0 => "lowpass",
+            1 => "bandpass",
+            2 => "highpass",
+            _ => "notch",
+        };
+        PortInfo::new(name, PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        4
+    }
+}
+
+/// Parses `lowpass`, `highpass`, `bandpass`, `notch`, `allpass`, `peak`,
+/// `low_shelf` or `high_shelf`, the gain is only used by the last three.
+pub fn parse_filter_type(name: &str, gain: f64) -> Result<FilterType, String> {
+    match name {
+        "lowpass" => Ok(FilterType::Lowpass),
+        "highpass" => Ok(FilterType::Highpass),
+        "bandpass" => Ok(FilterType::Bandpass),
+        "notch" => Ok(FilterType::Notch),
+        "allpass" => Ok(FilterType::Allpass),
+        "peak" => Ok(FilterType::Peak(gain)),
+        "low_shelf" => Ok(FilterType::LowShelf(gain)),
+        "high_shelf" => Ok(FilterType::HighShelf(gain)),
+        _ => Err(format!("unknown filter type '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `lowpass(cutoff: 1000, q: 0.707)`, the same for `highpass`, `bandpass`,
+///   `notch` and `allpass`
+/// * `peak(cutoff: 1000, q: 0.707, gain: 0)`, the same for `low_shelf` and `high_shelf`,
+///   the gain is in decibels
+/// * `butterworth(type: lowpass, order: 2, cutoff: 1000)`, the type can also be `highpass`
+/// * `linkwitz_riley(type: lowpass, order: 4, cutoff: 1000)`
+/// * `svf(cutoff: 1000, q: 0.707)` with the inputs `in` and `cutoff`
+///   and the outputs `lowpass`, `bandpass`, `highpass` and `notch`
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    let types = [
+        "lowpass",
+        "highpass",
+        "bandpass",
+        "notch",
+        "allpass",
+        "peak",
+        "low_shelf",
+        "high_shelf",
+    ];
+    for &name in &types {
+        registry.register(name, move |params: &Params| {
+            let kind = parse_filter_type(name, params.number("gain", 0.0)?)?;
+            let cutoff = params.number("cutoff", 1000.0)?;
+            let q = params.number("q", 0.707)?;
+            Ok(Box::new(Biquad::new(kind, cutoff, q)) as Box<Processor<F>>)
+        });
+    }
+    let cascades = [
+        ("butterworth", Alignment::Butterworth, 2.0),
+        ("linkwitz_riley", Alignment::LinkwitzRiley, 4.0),
+    ];
+    for &(name, alignment, order) in &cascades {
+        registry.register(name, move |params: &Params| {
+            let kind = parse_filter_type(&params.text("type", "lowpass")?, 0.0)?;
+            let order = params.number("order", order)? as usize;
+            let cutoff = params.number("cutoff", 1000.0)?;
+            let cascade = Cascade::new(alignment, kind, order, cutoff)?;
+            Ok(Box::new(cascade) as Box<Processor<F>>)
+        });
+    }
+    registry.register("svf", |params: &Params| {
+        let cutoff = params.number("cutoff", 1000.0)?;
+        let q = params.number("q", 0.707)?;
+        Ok(Box::new(Svf::new(cutoff, q)) as Box<Processor<F>>)
+    });
+}
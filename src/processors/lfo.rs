@@ -0,0 +1,275 @@
+//! Low frequency oscillators.
+//!
+//! An Lfo has two inputs, the first is added to its rate in Hz,
+//! a rising edge through 0.0 on the second resets its phase.
+//! Its output is `offset + depth * wave`, where the wave goes from -1.0 to 1.0,
+//! so it can be scaled to fit the modulation input it is connected to.
+//! The rate input and the output are control ports, in a Graph they
+//! carry one value per block.
+
+extern crate sample;
+
+use graph::{BufferSet, FrameSet};
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, PortType, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::PI;
+use super::{broadcast, control};
+
+/// The shapes an Lfo can produce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    /// A new random value every cycle.
+    SampleAndHold,
+    /// Glides from one random value to the next every cycle.
+    SmoothRandom,
+}
+
+/// How fast an Lfo runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    Hz(f64),
+    /// One cycle lasts `beats` beats at `bpm` beats per minute.
+    Tempo {
+        bpm: f64,
+        beats: f64,
+    },
+}
+
+impl Rate {
+    /// The rate in Hz.
+    pub fn hz(&self) -> f64 {
+        match *self {
+            Rate::Hz(hz) => hz,
+            Rate::Tempo { bpm, beats } => bpm / 60.0 / beats,
+        }
+    }
+}
+
+/// A low frequency oscillator.
+pub struct Lfo {
+    shape: Shape,
+    rate: Rate,
+    depth: f64,
+    offset: f64,
+    samplerate: f64,
+    phase: f64,
+    last_reset: f64,
+    // the random values of the current and the next cycle
+    random: (f64, f64),
+    seed: u64,
+}
+
+impl Lfo {
+    pub fn new(shape: Shape, rate: Rate) -> Self {
+        let mut lfo = Lfo {
+            shape: shape,
+            rate: rate,
+            depth: 1.0,
+            offset: 0.0,
+            samplerate: 44_100.0,
+            phase: 0.0,
+            last_reset: 0.0,
+            random: (0.0, 0.0),
+            seed: 0x2545_f491_4f6c_dd1d,
+        };
+        lfo.random = (lfo.random(), lfo.random());
+        lfo
+    }
+
+    pub fn set_shape(&mut self, shape: Shape) {
+        self.shape = shape;
+    }
+
+    pub fn set_rate(&mut self, rate: Rate) {
+        self.rate = rate;
+    }
+
+    /// Change the tempo of a synced Lfo, an Lfo running in Hz is switched
+    /// to one beat per cycle.
+    pub fn set_tempo(&mut self, bpm: f64) {
+        self.rate = match self.rate {
+            Rate::Tempo { beats, .. } => Rate::Tempo {
+                bpm: bpm,
+                beats: beats,
+            },
+            Rate::Hz(_) => Rate::Tempo {
+                bpm: bpm,
+                beats: 1.0,
+            },
+        };
+    }
+
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth;
+    }
+
+    pub fn set_offset(&mut self, offset: f64) {
+        self.offset = offset;
+    }
+
+    /// Set the phase, from 0.0 to 1.0.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase - phase.floor();
+    }
+
+    /// Set the seed of the random shapes, Lfos with the same seed produce the same values.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed.max(1);
+        self.random = (self.random(), self.random());
+    }
+
+    /// Calculate the next value, `rate` is added to the rate in Hz.
+    pub fn next(&mut self, rate: f64) -> f64 {
+        let p = self.phase;
+        let wave = match self.shape {
+            Shape::Sine => (2.0 * PI * p).sin(),
+            Shape::Triangle => {
+                // shifted by a quarter cycle to start at 0.0 rising, like the sine
+                let q = p + 0.25 - (p + 0.25).floor();
+                1.0 - 4.0 * (q - 0.5).abs()
+            }
+            Shape::Saw => 2.0 * p - 1.0,
+            Shape::Square => {
+                if p < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Shape::SampleAndHold => self.random.0,
+            Shape::SmoothRandom => {
+                let (a, b) = self.random;
+                let t = (1.0 - (PI * p).cos()) / 2.0;
+                a + (b - a) * t
+            }
+        };
+        self.phase += (self.rate.hz() + rate) / self.samplerate;
+        if self.phase >= 1.0 || self.phase < 0.0 {
+            self.phase -= self.phase.floor();
+            self.random = (self.random.1, self.random());
+        }
+        self.offset + self.depth * wave
+    }
+
+    // resets the phase on a rising edge of the reset input
+    fn reset(&mut self, reset: f64) {
+        if self.last_reset <= 0.0 && reset > 0.0 {
+            self.phase = 0.0;
+        }
+        self.last_reset = reset;
+    }
+
+    // a xorshift generator, returns a value from -1.0 to 1.0
+    fn random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+impl<F> Processor<F> for Lfo
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    /// The rate is read from the first Frame of the block.
+    fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        let rate = inputs.first().map_or(0.0, |frame| control(&frame[0]));
+        for (input, output) in inputs.iter().zip(outputs.iter_mut()) {
+            self.reset(control(&input[1]));
+            output[0] = broadcast(self.next(rate));
+        }
+    }
+
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        self.reset(control(&inputs[1]));
+        outputs[0] = broadcast(self.next(control(&inputs[0])));
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        if input == 0 {
+            PortType::Control
+        } else {
+            PortType::Audio
+        }
+    }
+
+    fn output_type(&self, _: usize) -> PortType {
+        PortType::Control
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("rate", PortKind::Modulation)
+                .with_description("added to the rate in Hz"),
+            _ => PortInfo::new("reset", PortKind::Modulation)
+                .with_description("a rising edge through 0.0 resets the phase"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Parses `sine`, `triangle`, `saw`, `square`, `sample_and_hold` or `smooth_random`.
+pub fn parse_shape(name: &str) -> Result<Shape, String> {
+    match name {
+        "sine" => Ok(Shape::Sine),
+        "triangle" => Ok(Shape::Triangle),
+        "saw" => Ok(Shape::Saw),
+        "square" => Ok(Shape::Square),
+        "sample_and_hold" => Ok(Shape::SampleAndHold),
+        "smooth_random" => Ok(Shape::SmoothRandom),
+        _ => Err(format!("unknown lfo shape '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `lfo(shape: sine, rate: 1, depth: 1, offset: 0, phase: 0)`,
+///   with a `bpm` parameter the rate is ignored and a cycle lasts `beats: 1` beats.
+///   An optional `seed` replaces the default seed of `Lfo::new`, `0x2545_f491_4f6c_dd1d`
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("lfo", |params: &Params| {
+        let shape = parse_shape(&params.text("shape", "sine")?)?;
+        let rate = match params.get("bpm") {
+            Some(_) => Rate::Tempo {
+                bpm: params.number("bpm", 120.0)?,
+                beats: params.number("beats", 1.0)?,
+            },
+            None => Rate::Hz(params.number("rate", 1.0)?),
+        };
+        let mut lfo = Lfo::new(shape, rate);
+        lfo.set_depth(params.number("depth", 1.0)?);
+        lfo.set_offset(params.number("offset", 0.0)?);
+        lfo.set_phase(params.number("phase", 0.0)?);
+        if params.get("seed").is_some() {
+            lfo.set_seed(params.number("seed", 1.0)? as u64);
+        }
+        Ok(Box::new(lfo) as Box<Processor<F>>)
+    });
+}
@@ -0,0 +1,118 @@
+//! Ready to use Processors.
+//!
+//! All of them work with any `sample::Frame` type whose samples can be
+//! converted to and from `f64`, which is what they calculate with.
+//! Inputs carrying control values, like a modulated gain,
+//! only use channel 0 of their Frames.
+//! Inputs named `note` are event inputs, they react to NoteOn and NoteOff events.
+//! Every processor describes its ports with `PortInfo`s,
+//! patches refer to the ports by the names in them.
+
+extern crate sample;
+
+pub mod channels;
+pub mod convolution;
+pub mod delay;
+pub mod distortion;
+pub mod dynamics;
+pub mod envelope;
+pub mod fft;
+pub mod filter;
+pub mod lfo;
+pub mod modfx;
+pub mod osc;
+pub mod oversample;
+pub mod resample;
+pub mod reverb;
+pub mod util;
+mod tests;
+
+use self::sample::conv::Duplex;
+use self::sample::{Frame, Sample};
+use processor::{Event, EventKind};
+use std::collections::VecDeque;
+
+// returns channel `channel` of a frame as f64
+fn channel_f64<F>(frame: &F, channel: usize) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    match frame.channel(channel) {
+        Some(sample) => sample.to_sample::<f64>(),
+        None => 0.0,
+    }
+}
+
+// returns the control value carried by a frame
+fn control<F>(frame: &F) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    channel_f64(frame, 0)
+}
+
+// applies `map` to every channel of a frame, it gets the channel index and the sample
+fn map_f64<F, M>(frame: F, mut map: M) -> F
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+    M: FnMut(usize, f64) -> f64,
+{
+    F::from_fn(|channel| F::Sample::from_sample(map(channel, channel_f64(&frame, channel))))
+}
+
+// returns a frame with the same value on every channel
+fn broadcast<F>(value: f64) -> F
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let sample = F::Sample::from_sample(value);
+    F::from_fn(|_| sample)
+}
+
+// holds events until the Frame they happen at, for processors working Frame by Frame
+struct Schedule {
+    // events with the time counted from the first Frame
+    pending: VecDeque<Event>,
+    // the Frame that is processed next
+    clock: usize,
+}
+
+impl Schedule {
+    fn new() -> Self {
+        Schedule {
+            pending: VecDeque::new(),
+            clock: 0,
+        }
+    }
+
+    // stores the events of the next block, they are sorted by time
+    fn add(&mut self, events: &[Event]) {
+        for event in events {
+            self.pending
+                .push_back(Event::new(self.clock + event.time, event.kind));
+        }
+    }
+
+    // returns the next event that happens at or before the current Frame
+    fn due(&mut self) -> Option<EventKind> {
+        match self.pending.front() {
+            Some(event) if event.time <= self.clock => {}
+            _ => return None,
+        }
+        self.pending.pop_front().map(|event| event.kind)
+    }
+
+    // moves on to the next Frame
+    fn tick(&mut self) {
+        self.clock += 1;
+    }
+}
+
+// returns the frequency of a MIDI note in Hz
+fn note_frequency(note: u8) -> f64 {
+    440.0 * 2f64.powf((note as f64 - 69.0) / 12.0)
+}
@@ -0,0 +1,573 @@
+//! Modulation effects: chorus, flanger and phaser.
+//!
+//! Every effect has two inputs, the signal and a value that is added to the
+//! rate of its Lfos in Hz. Each channel has its own Lfos, the odd channels
+//! are shifted by up to half a cycle by the stereo spread, so a stereo Frame
+//! gets a wider sound.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::PI;
+use super::delay::{DelayLine, Interpolation};
+use super::lfo::{Lfo, Rate, Shape};
+use super::{channel_f64, control, map_f64};
+
+// the highest frequency of the phaser allpasses, relative to the samplerate
+const MAX_FREQUENCY: f64 = 0.49;
+
+// the phase of an Lfo for a channel, from 0.0 to 1.5
+fn phase(channel: usize, spread: f64, offset: f64) -> f64 {
+    offset + spread * 0.5 * (channel % 2) as f64
+}
+
+// creates `amount` Lfos for every channel, the ones of a channel are evenly spread over a cycle
+fn lfos<F>(shape: Shape, rate: f64, spread: f64, amount: usize, samplerate: f64) -> Vec<Lfo>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let mut lfos = Vec::with_capacity(F::n_channels() * amount);
+    for channel in 0..F::n_channels() {
+        for i in 0..amount {
+            let mut lfo = Lfo::new(shape, Rate::Hz(rate));
+            Processor::<F>::set_samplerate(&mut lfo, samplerate as usize);
+            lfo.set_phase(phase(channel, spread, i as f64 / amount as f64));
+            lfos.push(lfo);
+        }
+    }
+    lfos
+}
+
+/// A chorus, every channel is mixed with several copies of itself whose
+/// delays are modulated by Lfos with different phases.
+pub struct Chorus {
+    voices: usize,
+    rate: f64,
+    depth: f64,
+    delay: f64,
+    spread: f64,
+    mix: f64,
+    samplerate: f64,
+    lines: Vec<DelayLine>,
+    // `voices` Lfos for every channel
+    lfos: Vec<Lfo>,
+}
+
+impl Chorus {
+    /// Create a Chorus with three voices, a rate of 0.8Hz, a delay of 15ms
+    /// modulated by 2ms, a spread of 0.5 and a mix of 0.5.
+    pub fn new() -> Self {
+        Chorus {
+            voices: 3,
+            rate: 0.8,
+            depth: 0.002,
+            delay: 0.015,
+            spread: 0.5,
+            mix: 0.5,
+            samplerate: 44_100.0,
+            lines: Vec::new(),
+            lfos: Vec::new(),
+        }
+    }
+
+    /// Set the number of delayed copies, from 1 to 16.
+    pub fn set_voices(&mut self, voices: usize) {
+        self.voices = voices.max(1).min(16);
+        self.lines.clear();
+    }
+
+    /// Set the rate of the Lfos in Hz.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        for lfo in &mut self.lfos {
+            lfo.set_rate(Rate::Hz(rate));
+        }
+    }
+
+    /// Set how far the delays move to either side, in seconds.
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.max(0.0);
+        self.lines.clear();
+    }
+
+    /// Set the delay around which the voices move, in seconds.
+    pub fn set_delay(&mut self, delay: f64) {
+        self.delay = delay.max(0.0);
+        self.lines.clear();
+    }
+
+    /// Set how far the Lfos of the odd channels are shifted, from 0.0 to 1.0
+    /// which is half a cycle.
+    pub fn set_spread(&mut self, spread: f64) {
+        self.spread = spread.max(0.0).min(1.0);
+        self.lines.clear();
+    }
+
+    /// Set the balance between the dry signal (0.0) and the voices (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    fn allocate<F>(&mut self)
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let capacity = ((self.delay + self.depth) * self.samplerate).ceil() as usize + 2;
+        self.lines = (0..F::n_channels())
+            .map(|_| DelayLine::new(capacity))
+            .collect();
+        self.lfos = lfos::<F>(
+            Shape::Sine,
+            self.rate,
+            self.spread,
+            self.voices,
+            self.samplerate,
+        );
+    }
+}
+
+impl<F> Processor<F> for Chorus
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.lines.len() != F::n_channels() {
+            self.allocate::<F>();
+        }
+        let rate = control(&inputs[1]);
+        // sample::Frame is implemented for up to 32 channels
+        let mut wet = [0.0; 32];
+        for channel in 0..F::n_channels() {
+            let line = &mut self.lines[channel];
+            let lfos = &mut self.lfos[channel * self.voices..(channel + 1) * self.voices];
+            let mut sum = 0.0;
+            for lfo in lfos {
+                let delay = (self.delay + self.depth * lfo.next(rate)) * self.samplerate;
+                sum += line.read(delay, Interpolation::Hermite);
+            }
+            wet[channel] = sum / self.voices as f64;
+            line.write(channel_f64(&inputs[0], channel));
+        }
+        let (dry, mix) = (1.0 - self.mix, self.mix);
+        outputs[0] = map_f64(inputs[0], |channel, x| dry * x + mix * wet[channel]);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.allocate::<F>();
+    }
+
+    fn tail_length(&self) -> usize {
+        ((self.delay + self.depth) * self.samplerate).ceil() as usize
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("rate", PortKind::Modulation)
+                .with_description("added to the rate of the Lfos in Hz"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// A flanger, every channel is mixed with a copy whose short delay is swept
+/// by an Lfo, the delayed copy can be fed back.
+///
+/// A through-zero flanger delays the dry signal as well, by the middle of the
+/// sweep, so the delayed copy can pass it. This delay is reported as latency.
+pub struct Flanger {
+    rate: f64,
+    depth: f64,
+    delay: f64,
+    feedback: f64,
+    through_zero: bool,
+    spread: f64,
+    mix: f64,
+    samplerate: f64,
+    lines: Vec<DelayLine>,
+    // the dry signal of a through-zero flanger, without the feedback
+    dry_lines: Vec<DelayLine>,
+    lfos: Vec<Lfo>,
+}
+
+impl Flanger {
+    /// Create a Flanger with a rate of 0.25Hz, a delay of 1ms swept by 2ms,
+    /// no feedback, a spread of 0.5 and a mix of 0.5.
+    pub fn new() -> Self {
+        Flanger {
+            rate: 0.25,
+            depth: 0.002,
+            delay: 0.001,
+            feedback: 0.0,
+            through_zero: false,
+            spread: 0.5,
+            mix: 0.5,
+            samplerate: 44_100.0,
+            lines: Vec::new(),
+            dry_lines: Vec::new(),
+            lfos: Vec::new(),
+        }
+    }
+
+    /// Set the rate of the Lfo in Hz.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        for lfo in &mut self.lfos {
+            lfo.set_rate(Rate::Hz(rate));
+        }
+    }
+
+    /// Set the length of the sweep in seconds.
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.max(0.0);
+        self.lines.clear();
+    }
+
+    /// Set the shortest delay of the sweep in seconds.
+    pub fn set_delay(&mut self, delay: f64) {
+        self.delay = delay.max(0.0);
+        self.lines.clear();
+    }
+
+    /// Set how much of the delayed copy is fed back, from -1.0 to 1.0 exclusive.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback.max(-0.999).min(0.999);
+    }
+
+    pub fn set_through_zero(&mut self, through_zero: bool) {
+        self.through_zero = through_zero;
+        self.lines.clear();
+    }
+
+    /// Set how far the Lfos of the odd channels are shifted, from 0.0 to 1.0
+    /// which is half a cycle.
+    pub fn set_spread(&mut self, spread: f64) {
+        self.spread = spread.max(0.0).min(1.0);
+        self.lines.clear();
+    }
+
+    /// Set the balance between the dry signal (0.0) and the delayed copy (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    // the delay of the dry signal of a through-zero flanger in samples
+    fn center(&self) -> f64 {
+        ((self.delay + self.depth / 2.0) * self.samplerate).ceil()
+    }
+
+    fn allocate<F>(&mut self)
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let capacity = ((self.delay + self.depth) * self.samplerate).ceil() as usize + 2;
+        self.lines = (0..F::n_channels())
+            .map(|_| DelayLine::new(capacity))
+            .collect();
+        let center = self.center() as usize;
+        self.dry_lines = (0..F::n_channels())
+            .map(|_| DelayLine::new(center))
+            .collect();
+        self.lfos = lfos::<F>(Shape::Triangle, self.rate, self.spread, 1, self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Flanger
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.lines.len() != F::n_channels() {
+            self.allocate::<F>();
+        }
+        let rate = control(&inputs[1]);
+        let center = self.center();
+        // sample::Frame is implemented for up to 32 channels
+        let mut dry = [0.0; 32];
+        let mut wet = [0.0; 32];
+        for channel in 0..F::n_channels() {
+            let x = channel_f64(&inputs[0], channel);
+            let line = &mut self.lines[channel];
+            // the Lfo sweeps the delay from `delay` to `delay + depth`
+            let sweep = (self.lfos[channel].next(rate) + 1.0) / 2.0;
+            let delay = (self.delay + self.depth * sweep) * self.samplerate;
+            wet[channel] = line.read(delay, Interpolation::Hermite);
+            dry[channel] = if self.through_zero {
+                let dry_line = &mut self.dry_lines[channel];
+                let delayed = dry_line.read(center, Interpolation::Linear);
+                dry_line.write(x);
+                delayed
+            } else {
+                x
+            };
+            line.write(x + self.feedback * wet[channel]);
+        }
+        let mix = self.mix;
+        outputs[0] = map_f64(inputs[0], |channel, _| {
+            (1.0 - mix) * dry[channel] + mix * wet[channel]
+        });
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.allocate::<F>();
+    }
+
+    fn tail_length(&self) -> usize {
+        let delay = (self.delay + self.depth) * self.samplerate;
+        let feedback = self.feedback.abs();
+        // the echoes that are louder than -60dB
+        let echoes = if feedback > 0.0 {
+            (0.001f64.ln() / feedback.ln()).ceil() + 1.0
+        } else {
+            1.0
+        };
+        (delay * echoes).ceil() as usize
+    }
+
+    fn latency(&self) -> usize {
+        if self.through_zero {
+            self.center() as usize
+        } else {
+            0
+        }
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("rate", PortKind::Modulation)
+                .with_description("added to the rate of the Lfos in Hz"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// A phaser, every channel is mixed with a copy that went through a chain of
+/// first order allpass filters whose frequency is swept by an Lfo.
+/// The output of the chain can be fed back to its input.
+pub struct Phaser {
+    stages: usize,
+    rate: f64,
+    depth: f64,
+    frequency: f64,
+    feedback: f64,
+    spread: f64,
+    mix: f64,
+    samplerate: f64,
+    // the state of every allpass, `stages` for every channel
+    states: Vec<f64>,
+    // the last output of the chain of every channel
+    last: Vec<f64>,
+    lfos: Vec<Lfo>,
+}
+
+impl Phaser {
+    /// Create a Phaser with four stages, a rate of 0.5Hz, a sweep of one octave
+    /// around 800Hz, no feedback, a spread of 0.5 and a mix of 0.5.
+    pub fn new() -> Self {
+        Phaser {
+            stages: 4,
+            rate: 0.5,
+            depth: 1.0,
+            frequency: 800.0,
+            feedback: 0.0,
+            spread: 0.5,
+            mix: 0.5,
+            samplerate: 44_100.0,
+            states: Vec::new(),
+            last: Vec::new(),
+            lfos: Vec::new(),
+        }
+    }
+
+    /// Set the number of allpass filters, from 1 to 24.
+    /// Every two stages add one notch.
+    pub fn set_stages(&mut self, stages: usize) {
+        self.stages = stages.max(1).min(24);
+        self.last.clear();
+    }
+
+    /// Set the rate of the Lfo in Hz.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        for lfo in &mut self.lfos {
+            lfo.set_rate(Rate::Hz(rate));
+        }
+    }
+
+    /// Set how many octaves the frequency moves to either side.
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.max(0.0);
+    }
+
+    /// Set the frequency around which the allpass filters are swept.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Set how much of the output of the chain is fed back, from -1.0 to 1.0 exclusive.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback.max(-0.99).min(0.99);
+    }
+
+    /// Set how far the Lfos of the odd channels are shifted, from 0.0 to 1.0
+    /// which is half a cycle.
+    pub fn set_spread(&mut self, spread: f64) {
+        self.spread = spread.max(0.0).min(1.0);
+        self.last.clear();
+    }
+
+    /// Set the balance between the dry signal (0.0) and the filtered copy (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    fn allocate<F>(&mut self)
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        self.states = vec![0.0; F::n_channels() * self.stages];
+        self.last = vec![0.0; F::n_channels()];
+        self.lfos = lfos::<F>(Shape::Sine, self.rate, self.spread, 1, self.samplerate);
+    }
+}
+
+impl<F> Processor<F> for Phaser
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if self.last.len() != F::n_channels() {
+            self.allocate::<F>();
+        }
+        let rate = control(&inputs[1]);
+        // sample::Frame is implemented for up to 32 channels
+        let mut wet = [0.0; 32];
+        for channel in 0..F::n_channels() {
+            let octaves = self.depth * self.lfos[channel].next(rate);
+            let frequency = (self.frequency * octaves.exp2())
+                .max(1.0)
+                .min(self.samplerate * MAX_FREQUENCY);
+            let t = (PI * frequency / self.samplerate).tan();
+            let a = (t - 1.0) / (t + 1.0);
+            let mut y = channel_f64(&inputs[0], channel) + self.feedback * self.last[channel];
+            let states = &mut self.states[channel * self.stages..(channel + 1) * self.stages];
+            for state in states {
+                let x = y;
+                y = a * x + *state;
+                *state = x - a * y;
+            }
+            self.last[channel] = y;
+            wet[channel] = y;
+        }
+        let (dry, mix) = (1.0 - self.mix, self.mix);
+        outputs[0] = map_f64(inputs[0], |channel, x| dry * x + mix * wet[channel]);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.allocate::<F>();
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("rate", PortKind::Modulation)
+                .with_description("added to the rate of the Lfos in Hz"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Add the processors of this module to a registry,
+/// times are in seconds and rates in Hz:
+///
+/// * `chorus(voices: 3, rate: 0.8, depth: 0.002, delay: 0.015, spread: 0.5, mix: 0.5)`
+/// * `flanger(rate: 0.25, depth: 0.002, delay: 0.001, feedback: 0, through_zero: 0,
+///   spread: 0.5, mix: 0.5)`
+/// * `phaser(stages: 4, rate: 0.5, depth: 1, frequency: 800, feedback: 0, spread: 0.5,
+///   mix: 0.5)`, the depth is in octaves
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("chorus", |params: &Params| {
+        let mut chorus = Chorus::new();
+        chorus.set_voices(params.number("voices", 3.0)? as usize);
+        chorus.set_rate(params.number("rate", 0.8)?);
+        chorus.set_depth(params.number("depth", 0.002)?);
+        chorus.set_delay(params.number("delay", 0.015)?);
+        chorus.set_spread(params.number("spread", 0.5)?);
+        chorus.set_mix(params.number("mix", 0.5)?);
+        Ok(Box::new(chorus) as Box<Processor<F>>)
+    });
+    registry.register("flanger", |params: &Params| {
+        let mut flanger = Flanger::new();
+        flanger.set_rate(params.number("rate", 0.25)?);
+        flanger.set_depth(params.number("depth", 0.002)?);
+        flanger.set_delay(params.number("delay", 0.001)?);
+        flanger.set_feedback(params.number("feedback", 0.0)?);
+        flanger.set_through_zero(params.number("through_zero", 0.0)? != 0.0);
+        flanger.set_spread(params.number("spread", 0.5)?);
+        flanger.set_mix(params.number("mix", 0.5)?);
+        Ok(Box::new(flanger) as Box<Processor<F>>)
+    });
+    registry.register("phaser", |params: &Params| {
+        let mut phaser = Phaser::new();
+        phaser.set_stages(params.number("stages", 4.0)? as usize);
+        phaser.set_rate(params.number("rate", 0.5)?);
+        phaser.set_depth(params.number("depth", 1.0)?);
+        phaser.set_frequency(params.number("frequency", 800.0)?);
+        phaser.set_feedback(params.number("feedback", 0.0)?);
+        phaser.set_spread(params.number("spread", 0.5)?);
+        phaser.set_mix(params.number("mix", 0.5)?);
+        Ok(Box::new(phaser) as Box<Processor<F>>)
+    });
+}
@@ -0,0 +1,402 @@
+//! Oscillators. Saw, square, pulse and triangle waves are band limited with
+//! PolyBLEP and PolyBLAMP corrections, wavetables with one table per octave.
+//!
+//! An Oscillator has these inputs:
+//!
+//! * 0: frequency in Hz, added to the frequency of the oscillator
+//! * 1: phase modulation in cycles
+//! * 2: sync, the phase is reset on every rising edge through 0.0
+//! * 3: pulse width modulation, added to the pulse width (pulse waves only)
+//! * 4: note events, a NoteOn sets the frequency to the one of its note
+//!
+//! A WavetableOscillator has the same inputs without the pulse width,
+//! so its note events arrive at input 3.
+//!
+//! The wave goes from -1.0 to 1.0 and is written to every channel of output 0.
+
+extern crate sample;
+
+use graph::FrameSet;
+use io::decoder;
+use patch::{Params, Registry};
+use processor::{Event, EventKind, PortInfo, PortKind, PortType, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::PI;
+use super::{broadcast, control, note_frequency, Schedule};
+
+/// The shapes an Oscillator can produce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Saw,
+    Square,
+    /// A pulse wave with a width from 0.0 to 1.0, 0.5 is a square wave.
+    Pulse(f64),
+    Triangle,
+}
+
+// correction for a step of height 1 at phase 0,
+// `t` is the phase and `dt` the phase increment per sample
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let x = t / dt;
+        2.0 * x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+// correction for a change of slope of 1 per sample at phase 0
+fn poly_blamp(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let x = 1.0 - t / dt;
+        x * x * x / 3.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 3.0
+    } else {
+        0.0
+    }
+}
+
+// wraps a phase into 0.0..1.0
+fn wrap(phase: f64) -> f64 {
+    phase - phase.floor()
+}
+
+// the state every oscillator needs: the phase and the sync detection
+struct Phase {
+    phase: f64,
+    last_sync: f64,
+    samplerate: f64,
+}
+
+impl Phase {
+    fn new() -> Self {
+        Phase {
+            phase: 0.0,
+            last_sync: 0.0,
+            samplerate: 44_100.0,
+        }
+    }
+
+    // returns the phase to read at and the phase increment,
+    // then advances the phase
+    fn advance(&mut self, frequency: f64, phase_modulation: f64, sync: f64) -> (f64, f64) {
+        if self.last_sync <= 0.0 && sync > 0.0 {
+            self.phase = 0.0;
+        }
+        self.last_sync = sync;
+        let dt = (frequency / self.samplerate).abs().min(0.5);
+        let read = wrap(self.phase + phase_modulation);
+        self.phase = wrap(self.phase + frequency / self.samplerate);
+        (read, dt)
+    }
+}
+
+/// An oscillator producing a basic Waveform.
+pub struct Oscillator {
+    waveform: Waveform,
+    frequency: f64,
+    phase: Phase,
+    schedule: Schedule,
+}
+
+impl Oscillator {
+    pub fn new(waveform: Waveform, frequency: f64) -> Self {
+        Oscillator {
+            waveform: waveform,
+            frequency: frequency,
+            phase: Phase::new(),
+            schedule: Schedule::new(),
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    /// set the phase, from 0.0 to 1.0
+    pub fn reset(&mut self, phase: f64) {
+        self.phase.phase = wrap(phase);
+    }
+
+    // returns the next sample
+    fn next(&mut self, frequency: f64, phase_modulation: f64, sync: f64, pwm: f64) -> f64 {
+        let (t, dt) = self.phase.advance(frequency, phase_modulation, sync);
+        match self.waveform {
+            Waveform::Sine => (2.0 * PI * t).sin(),
+            Waveform::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Waveform::Square => pulse(t, dt, 0.5),
+            Waveform::Pulse(width) => pulse(t, dt, width + pwm),
+            Waveform::Triangle => {
+                let naive = if t < 0.5 {
+                    4.0 * t - 1.0
+                } else {
+                    3.0 - 4.0 * t
+                };
+                // the slope changes by 8 per cycle at both corners
+                let slope = 8.0 * dt;
+                naive + slope * poly_blamp(t, dt) - slope * poly_blamp(wrap(t + 0.5), dt)
+            }
+        }
+    }
+}
+
+fn pulse(t: f64, dt: f64, width: f64) -> f64 {
+    let width = width.max(dt).min(1.0 - dt);
+    let naive = if t < width { 1.0 } else { -1.0 };
+    naive + poly_blep(t, dt) - poly_blep(wrap(t - width + 1.0), dt)
+}
+
+impl<F> Processor<F> for Oscillator
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        while let Some(kind) = self.schedule.due() {
+            if let EventKind::NoteOn { note, .. } = kind {
+                self.frequency = note_frequency(note);
+            }
+        }
+        self.schedule.tick();
+        let frequency = self.frequency + control(&inputs[0]);
+        let value = self.next(
+            frequency,
+            control(&inputs[1]),
+            control(&inputs[2]),
+            control(&inputs[3]),
+        );
+        outputs[0] = broadcast(value);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.phase.samplerate = samplerate as f64;
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        if input == 4 {
+            PortType::Event
+        } else {
+            PortType::Audio
+        }
+    }
+
+    fn handle_events(&mut self, _: usize, events: &[Event]) {
+        self.schedule.add(events);
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        let (name, description) = match input {
+            0 => ("frequency", "added to the frequency in Hz"),
+            1 => ("phase", "phase modulation in cycles"),
+            2 => ("sync", "a rising edge through 0.0 resets the phase"),
+            3 => ("width", "added to the pulse width"),
+            _ => return PortInfo::new("note", PortKind::Main).with_description("NoteOn events"),
+        };
+        PortInfo::new(name, PortKind::Modulation).with_description(description)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        5
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// An oscillator playing a single cycle wavetable.
+/// The table is band limited once per octave, and the oscillator reads
+/// from the table whose harmonics all stay below the nyquist frequency.
+pub struct WavetableOscillator {
+    // tables[i] contains the harmonics up to `harmonics >> i`
+    tables: Vec<Vec<f64>>,
+    harmonics: usize,
+    frequency: f64,
+    phase: Phase,
+    schedule: Schedule,
+}
+
+impl WavetableOscillator {
+    /// Create an oscillator from one cycle of a wave, which can have any length.
+    pub fn new(cycle: &[f64], frequency: f64) -> Self {
+        let harmonics = cycle.len() / 2;
+        let mut amplitudes = Vec::with_capacity(harmonics);
+        // a plain DFT, it only runs once
+        for k in 1..harmonics + 1 {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (n, &x) in cycle.iter().enumerate() {
+                let angle = 2.0 * PI * (k * n) as f64 / cycle.len() as f64;
+                re += x * angle.cos();
+                im -= x * angle.sin();
+            }
+            let scale = 2.0 / cycle.len() as f64;
+            amplitudes.push((re * scale, im * scale));
+        }
+        WavetableOscillator::from_harmonics(&amplitudes, frequency)
+    }
+
+    /// Create an oscillator from the cosine and sine amplitudes of its harmonics,
+    /// starting with the fundamental.
+    pub fn from_harmonics(amplitudes: &[(f64, f64)], frequency: f64) -> Self {
+        let harmonics = amplitudes.len().max(1);
+        // enough samples per table to contain all harmonics
+        let size = (4 * harmonics).next_power_of_two();
+        let mut tables = Vec::new();
+        let mut limit = harmonics;
+        loop {
+            let table = (0..size)
+                .map(|n| {
+                    amplitudes
+                        .iter()
+                        .take(limit)
+                        .enumerate()
+                        .map(|(k, &(re, im))| {
+                            let angle = 2.0 * PI * ((k + 1) * n) as f64 / size as f64;
+                            re * angle.cos() - im * angle.sin()
+                        })
+                        .sum()
+                })
+                .collect();
+            tables.push(table);
+            if limit <= 1 {
+                break;
+            }
+            limit /= 2;
+        }
+        WavetableOscillator {
+            tables: tables,
+            harmonics: harmonics,
+            frequency: frequency,
+            phase: Phase::new(),
+            schedule: Schedule::new(),
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    fn next(&mut self, frequency: f64, phase_modulation: f64, sync: f64) -> f64 {
+        let (t, dt) = self.phase.advance(frequency, phase_modulation, sync);
+        // the highest harmonic allowed below nyquist
+        let allowed = (0.5 / dt.max(1e-9)).floor() as usize;
+        let mut level = 0;
+        while level + 1 < self.tables.len() && self.harmonics >> level > allowed {
+            level += 1;
+        }
+        let table = &self.tables[level];
+        let position = t * table.len() as f64;
+        let index = position as usize % table.len();
+        let fraction = position - position.floor();
+        let next = (index + 1) % table.len();
+        table[index] + (table[next] - table[index]) * fraction
+    }
+}
+
+impl<F> Processor<F> for WavetableOscillator
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        while let Some(kind) = self.schedule.due() {
+            if let EventKind::NoteOn { note, .. } = kind {
+                self.frequency = note_frequency(note);
+            }
+        }
+        self.schedule.tick();
+        let frequency = self.frequency + control(&inputs[0]);
+        let value = self.next(frequency, control(&inputs[1]), control(&inputs[2]));
+        outputs[0] = broadcast(value);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.phase.samplerate = samplerate as f64;
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        if input == 3 {
+            PortType::Event
+        } else {
+            PortType::Audio
+        }
+    }
+
+    fn handle_events(&mut self, _: usize, events: &[Event]) {
+        self.schedule.add(events);
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        let (name, description) = match input {
+            0 => ("frequency", "added to the frequency in Hz"),
+            1 => ("phase", "phase modulation in cycles"),
+            2 => ("sync", "a rising edge through 0.0 resets the phase"),
+            _ => return PortInfo::new("note", PortKind::Main).with_description("NoteOn events"),
+        };
+        PortInfo::new(name, PortKind::Modulation).with_description(description)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        4
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `sine(frequency: 440)`, `saw`, `square` and `triangle` with the same parameter
+/// * `pulse(frequency: 440, width: 0.5)`
+/// * `wavetable(file: "cycle.wav", frequency: 440)`, the file contains one cycle
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    let waveforms = [
+        ("sine", Waveform::Sine),
+        ("saw", Waveform::Saw),
+        ("square", Waveform::Square),
+        ("triangle", Waveform::Triangle),
+    ];
+    for &(name, waveform) in &waveforms {
+        registry.register(name, move |params: &Params| {
+            let frequency = params.number("frequency", 440.0)?;
+            Ok(Box::new(Oscillator::new(waveform, frequency)) as Box<Processor<F>>)
+        });
+    }
+    registry.register("pulse", |params: &Params| {
+        let frequency = params.number("frequency", 440.0)?;
+        let waveform = Waveform::Pulse(params.number("width", 0.5)?);
+        Ok(Box::new(Oscillator::new(waveform, frequency)) as Box<Processor<F>>)
+    });
+    registry.register("wavetable", |params: &Params| {
+        let file = params.text("file", "")?;
+        let (cycle, _): (Vec<[f64; 1]>, usize) =
+            decoder::read_file(&file).map_err(|e| format!("{}: {}", file, e))?;
+        let cycle: Vec<f64> = cycle.iter().map(|frame| frame[0]).collect();
+        let frequency = params.number("frequency", 440.0)?;
+        Ok(Box::new(WavetableOscillator::new(&cycle, frequency)) as Box<Processor<F>>)
+    });
+}
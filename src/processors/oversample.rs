@@ -0,0 +1,473 @@
+//! Oversampling for nonlinear processing, which creates harmonics above the
+//! Nyquist frequency that would otherwise alias.
+//!
+//! The samplerate is doubled by up to three stages of polyphase halfband
+//! filters, every stage has a windowed sinc filter with 63 taps.
+//! Only the taps of one phase have to be calculated, the other phase of a
+//! halfband filter is a delay.
+//!
+//! `Oversampler` runs a function on one channel, `Oversampled` runs a whole
+//! Processor, which can also be a Graph, at the higher samplerate.
+
+extern crate sample;
+
+use graph::{empty_buffer, BufferSet};
+use processor::{Event, PortInfo, PortType, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use std::f64::consts::PI;
+use std::mem;
+use super::delay::{DelayLine, Interpolation};
+use super::{channel_f64, map_f64};
+
+// half the number of nonzero taps of the polyphase branch
+const HALF_LENGTH: usize = 16;
+// the center of the filter, it has 4 * HALF_LENGTH - 1 taps
+const CENTER: usize = 2 * HALF_LENGTH - 1;
+
+// the taps of the halfband filter at even positions, the odd ones are zero
+// except the center which is 0.5
+fn halfband() -> Vec<f64> {
+    let length = 4 * HALF_LENGTH - 1;
+    let mut taps: Vec<f64> = (0..2 * HALF_LENGTH)
+        .map(|j| {
+            let k = 2 * j;
+            let x = (k as f64 - CENTER as f64) / 2.0;
+            let sinc = (PI * x).sin() / (PI * x);
+            // a Blackman window
+            let w = 2.0 * PI * k as f64 / (length - 1) as f64;
+            let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+            0.5 * sinc * window
+        })
+        .collect();
+    // the filter has a gain of 1.0 at 0Hz
+    let sum: f64 = taps.iter().sum();
+    for tap in &mut taps {
+        *tap *= 0.5 / sum;
+    }
+    taps
+}
+
+// the last samples of a signal, stored twice so they are always contiguous
+struct History {
+    buffer: Vec<f64>,
+    index: usize,
+}
+
+impl History {
+    fn new(len: usize) -> Self {
+        History {
+            buffer: vec![0.0; 2 * len],
+            index: 0,
+        }
+    }
+
+    fn push(&mut self, x: f64) {
+        let len = self.buffer.len() / 2;
+        self.index = (self.index + len - 1) % len;
+        self.buffer[self.index] = x;
+        self.buffer[self.index + len] = x;
+    }
+
+    // the sample pushed `delay` samples before the last one
+    fn get(&self, delay: usize) -> f64 {
+        self.buffer[self.index + delay]
+    }
+
+    // the last pushed samples multiplied with `taps`, the newest with the first tap
+    fn dot(&self, taps: &[f64]) -> f64 {
+        taps.iter()
+            .zip(&self.buffer[self.index..])
+            .fold(0.0, |y, (&h, &x)| y + h * x)
+    }
+}
+
+// the second half of a doubling of the samplerate
+struct DownStage {
+    even: History,
+    odd: History,
+    // the output is delayed by one more sample if `padded`,
+    // to keep the latency of the whole Oversampler a whole number of samples
+    padded: bool,
+    last: f64,
+}
+
+impl DownStage {
+    fn new(padded: bool) -> Self {
+        DownStage {
+            even: History::new(2 * HALF_LENGTH),
+            odd: History::new(HALF_LENGTH + 1),
+            padded: padded,
+            last: 0.0,
+        }
+    }
+
+    fn process(&mut self, taps: &[f64], even: f64, odd: f64) -> f64 {
+        self.even.push(even);
+        self.odd.push(odd);
+        let y = self.even.dot(taps) + 0.5 * self.odd.get(HALF_LENGTH);
+        if self.padded {
+            mem::replace(&mut self.last, y)
+        } else {
+            y
+        }
+    }
+}
+
+// the number of doublings for a factor
+fn stages(factor: usize) -> Result<usize, String> {
+    match factor {
+        1 => Ok(0),
+        2 => Ok(1),
+        4 => Ok(2),
+        8 => Ok(3),
+        _ => Err(format!(
+            "the oversampling factor {} is not 1, 2, 4 or 8",
+            factor
+        )),
+    }
+}
+
+/// The latency of an Upsampler and a Downsampler with the given factor
+/// in a row, in samples at the lower samplerate.
+pub fn latency(factor: usize) -> usize {
+    // every stage delays by CENTER samples at its lower samplerate,
+    // the padded ones by CENTER + 1
+    let stages = factor.trailing_zeros() as usize;
+    (0..stages)
+        .map(|s| if s == 0 { CENTER } else { (CENTER + 1) >> s })
+        .sum()
+}
+
+/// Raises the samplerate of one channel by a factor of 1, 2, 4 or 8.
+pub struct Upsampler {
+    taps: Vec<f64>,
+    stages: Vec<History>,
+    buffer: Vec<f64>,
+    scratch: Vec<f64>,
+}
+
+impl Upsampler {
+    pub fn new(factor: usize) -> Result<Self, String> {
+        Ok(Upsampler {
+            taps: halfband(),
+            stages: (0..stages(factor)?)
+                .map(|_| History::new(2 * HALF_LENGTH))
+                .collect(),
+            buffer: vec![0.0; factor],
+            scratch: vec![0.0; factor],
+        })
+    }
+
+    pub fn factor(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Upsample one sample, the result holds `factor` samples.
+    pub fn process(&mut self, x: f64) -> &[f64] {
+        self.buffer[0] = x;
+        let mut len = 1;
+        for history in &mut self.stages {
+            for i in 0..len {
+                history.push(self.buffer[i]);
+                // the gain of 2.0 makes up for the inserted zeros
+                self.scratch[2 * i] = 2.0 * history.dot(&self.taps);
+                self.scratch[2 * i + 1] = history.get(HALF_LENGTH - 1);
+            }
+            mem::swap(&mut self.buffer, &mut self.scratch);
+            len *= 2;
+        }
+        &self.buffer
+    }
+}
+
+/// Lowers the samplerate of one channel by a factor of 1, 2, 4 or 8.
+pub struct Downsampler {
+    taps: Vec<f64>,
+    stages: Vec<DownStage>,
+    buffer: Vec<f64>,
+}
+
+impl Downsampler {
+    pub fn new(factor: usize) -> Result<Self, String> {
+        Ok(Downsampler {
+            taps: halfband(),
+            stages: (0..stages(factor)?)
+                .map(|s| DownStage::new(s > 0))
+                .collect(),
+            buffer: vec![0.0; factor],
+        })
+    }
+
+    pub fn factor(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Downsample `factor` samples into one.
+    pub fn process(&mut self, samples: &[f64]) -> f64 {
+        self.buffer.copy_from_slice(samples);
+        let mut len = self.buffer.len();
+        // the last stage of the Upsampler is the first one here
+        for stage in self.stages.iter_mut().rev() {
+            len /= 2;
+            for i in 0..len {
+                self.buffer[i] =
+                    stage.process(&self.taps, self.buffer[2 * i], self.buffer[2 * i + 1]);
+            }
+        }
+        self.buffer[0]
+    }
+}
+
+/// Runs a function on one channel at a multiple of the samplerate.
+pub struct Oversampler {
+    up: Upsampler,
+    down: Downsampler,
+    buffer: Vec<f64>,
+}
+
+impl Oversampler {
+    /// Create an Oversampler, `factor` has to be 1, 2, 4 or 8.
+    /// A factor of 1 calls the function directly, without latency.
+    pub fn new(factor: usize) -> Result<Self, String> {
+        Ok(Oversampler {
+            up: Upsampler::new(factor)?,
+            down: Downsampler::new(factor)?,
+            buffer: vec![0.0; factor],
+        })
+    }
+
+    pub fn factor(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The delay of `process` in samples at the original samplerate.
+    pub fn latency(&self) -> usize {
+        latency(self.factor())
+    }
+
+    pub fn reset(&mut self) {
+        *self = Oversampler::new(self.factor()).unwrap();
+    }
+
+    /// Upsample `x`, call `function` with every sample at the higher samplerate
+    /// and downsample the results again.
+    pub fn process<S>(&mut self, x: f64, mut function: S) -> f64
+    where
+        S: FnMut(f64) -> f64,
+    {
+        for (y, &x) in self.buffer.iter_mut().zip(self.up.process(x)) {
+            *y = function(x);
+        }
+        self.down.process(&self.buffer)
+    }
+}
+
+/// Runs a Processor at `factor` times the samplerate, it gets this samplerate
+/// in `set_samplerate`. All inputs are upsampled and all outputs downsampled,
+/// so the harmonics a nonlinear Processor creates do not alias.
+///
+/// The Processor gets `factor` times as many Frames per `process` call.
+/// A Graph always processes blocks of its buffersize, so a wrapped Graph needs
+/// a buffersize of `factor` times the one of the Graph the Oversampled is in.
+///
+/// Control and event ports are not filtered: a control input reaches the
+/// Processor in the first Frame of its block, the first Frame of a control
+/// output is passed on, and the times of events are multiplied with `factor`.
+pub struct Oversampled<P, F> {
+    processor: P,
+    factor: usize,
+    // one for every channel of every input or output, port after port
+    upsamplers: Vec<Upsampler>,
+    downsamplers: Vec<Downsampler>,
+    // delays the outputs so the latency is a whole number of samples
+    // at the lower samplerate
+    pads: Vec<DelayLine>,
+    inputs: BufferSet<F>,
+    outputs: BufferSet<F>,
+    // the events of one port with converted times
+    events: Vec<Event>,
+}
+
+impl<P, F> Oversampled<P, F>
+where
+    P: Processor<F>,
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    /// Wrap a Processor, `factor` has to be 1, 2, 4 or 8.
+    pub fn new(processor: P, factor: usize) -> Result<Self, String> {
+        stages(factor)?;
+        Ok(Oversampled {
+            processor: processor,
+            factor: factor,
+            upsamplers: Vec::new(),
+            downsamplers: Vec::new(),
+            pads: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            events: Vec::new(),
+        })
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn into_inner(self) -> P {
+        self.processor
+    }
+
+    // the delay of the outputs at the higher samplerate
+    fn pad(&self) -> usize {
+        let latency = self.processor.latency();
+        (self.factor - latency % self.factor) % self.factor
+    }
+
+    fn allocate(&mut self) {
+        let (channels, factor) = (F::n_channels(), self.factor);
+        let inputs = self.processor.inputs_amt() * channels;
+        let outputs = self.processor.outputs_amt() * channels;
+        self.upsamplers = (0..inputs)
+            .map(|_| Upsampler::new(factor).unwrap())
+            .collect();
+        self.downsamplers = (0..outputs)
+            .map(|_| Downsampler::new(factor).unwrap())
+            .collect();
+        let pad = self.pad();
+        self.pads = (0..outputs).map(|_| DelayLine::new(pad.max(1))).collect();
+    }
+}
+
+impl<P, F> Processor<F> for Oversampled<P, F>
+where
+    P: Processor<F>,
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        let channels = F::n_channels();
+        let (inputs_amt, outputs_amt) = (self.processor.inputs_amt(), self.processor.outputs_amt());
+        if self.upsamplers.len() != inputs_amt * channels {
+            // only happens when the Oversampled is used without a Graph
+            self.allocate();
+        }
+        let (factor, len) = (self.factor, inputs.len() * self.factor);
+        if self.inputs.len() != len {
+            self.inputs = empty_buffer(inputs_amt, len);
+            self.outputs = empty_buffer(outputs_amt, len);
+        }
+        // sample::Frame is implemented for up to 32 channels
+        let mut upsampled = [[0.0; 8]; 32];
+        for (i, frames) in inputs.iter().enumerate() {
+            for port in 0..inputs_amt {
+                if self.processor.input_type(port) != PortType::Audio {
+                    for k in 0..factor {
+                        self.inputs[i * factor + k][port] =
+                            if k == 0 { frames[port] } else { F::equilibrium() };
+                    }
+                    continue;
+                }
+                for channel in 0..channels {
+                    let upsampler = &mut self.upsamplers[port * channels + channel];
+                    let samples = upsampler.process(channel_f64(&frames[port], channel));
+                    upsampled[channel][..factor].copy_from_slice(samples);
+                }
+                for k in 0..factor {
+                    self.inputs[i * factor + k][port] =
+                        map_f64(F::equilibrium(), |channel, _| upsampled[channel][k]);
+                }
+            }
+        }
+        self.processor.process(&self.inputs, &mut self.outputs);
+        let pad = self.pad() as f64;
+        let mut samples = [0.0; 8];
+        let mut downsampled = [0.0; 32];
+        for (i, frames) in outputs.iter_mut().enumerate() {
+            for port in 0..outputs_amt {
+                if self.processor.output_type(port) != PortType::Audio {
+                    frames[port] = self.outputs[i * factor][port];
+                    continue;
+                }
+                for channel in 0..channels {
+                    let index = port * channels + channel;
+                    for k in 0..factor {
+                        let x = channel_f64(&self.outputs[i * factor + k][port], channel);
+                        samples[k] = if pad > 0.0 {
+                            let line = &mut self.pads[index];
+                            let delayed = line.read(pad, Interpolation::Linear);
+                            line.write(x);
+                            delayed
+                        } else {
+                            x
+                        };
+                    }
+                    downsampled[channel] = self.downsamplers[index].process(&samples[..factor]);
+                }
+                frames[port] = map_f64(frames[port], |channel, _| downsampled[channel]);
+            }
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.processor.set_samplerate(samplerate * self.factor);
+        self.allocate();
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        self.processor.input_info(input)
+    }
+
+    fn output_info(&self, output: usize) -> PortInfo {
+        self.processor.output_info(output)
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        self.processor.input_type(input)
+    }
+
+    fn output_type(&self, output: usize) -> PortType {
+        self.processor.output_type(output)
+    }
+
+    fn handle_events(&mut self, input: usize, events: &[Event]) {
+        let factor = self.factor;
+        self.events.clear();
+        self.events.extend(
+            events
+                .iter()
+                .map(|event| Event::new(event.time * factor, event.kind)),
+        );
+        self.processor.handle_events(input, &self.events);
+    }
+
+    fn take_events(&mut self, output: usize, events: &mut Vec<Event>) {
+        let start = events.len();
+        self.processor.take_events(output, events);
+        for event in &mut events[start..] {
+            event.time /= self.factor;
+        }
+    }
+
+    fn tail_length(&self) -> usize {
+        let tail = self.processor.tail_length();
+        latency(self.factor) + (tail + self.factor - 1) / self.factor
+    }
+
+    fn latency(&self) -> usize {
+        let inner = self.processor.latency() + self.pad();
+        latency(self.factor) + inner / self.factor
+    }
+
+    fn inputs_amt(&self) -> usize {
+        self.processor.inputs_amt()
+    }
+
+    fn outputs_amt(&self) -> usize {
+        self.processor.outputs_amt()
+    }
+}
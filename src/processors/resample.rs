@@ -0,0 +1,548 @@
+//! Samplerate conversion with windowed sinc interpolation.
+//!
+//! `Resampler` converts one channel as a stream, `resample` and
+//! `resample_frames` convert whole signals. `Resampled` embeds a Processor,
+//! usually a Graph, that runs at a different samplerate than the Graph it is in.
+//!
+//! The conversion is aligned in time: the first output sample is the first
+//! input sample, a Resampler only has to wait for the input samples following
+//! the point it interpolates.
+
+extern crate sample;
+
+use graph::{empty_buffer, BufferSet, Graph};
+use processor::{Event, PortInfo, PortType, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use simd::Kernel;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use super::{channel_f64, map_f64};
+
+// the kernel is tabulated with this many points per input sample
+const RESOLUTION: usize = 256;
+
+/// How many input samples a Resampler uses for every output sample.
+/// Better quality keeps more of the high frequencies and costs more time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quality {
+    /// 16 input samples, flat up to about 80% of the Nyquist frequency.
+    Fast,
+    /// 32 input samples, flat up to about 90% of the Nyquist frequency.
+    Medium,
+    /// 64 input samples, flat up to about 95% of the Nyquist frequency.
+    Best,
+}
+
+impl Quality {
+    // the zero crossings of the sinc on each side and the cutoff relative to Nyquist
+    fn parameters(&self) -> (usize, f64) {
+        match *self {
+            Quality::Fast => (8, 0.85),
+            Quality::Medium => (16, 0.92),
+            Quality::Best => (32, 0.96),
+        }
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Converts one channel from one samplerate to another.
+pub struct Resampler {
+    // the step between two outputs is `from / to` input samples
+    from: usize,
+    to: usize,
+    // half the length of the kernel in input samples
+    width: usize,
+    kernel: Vec<f64>,
+    // the input samples from `first` on
+    buffer: Vec<f64>,
+    first: i64,
+    // the time of the next output is `index + phase / to`
+    index: i64,
+    phase: usize,
+}
+
+impl Resampler {
+    pub fn new(from: usize, to: usize, quality: Quality) -> Result<Self, String> {
+        if from == 0 || to == 0 {
+            return Err(format!("can not resample from {}Hz to {}Hz", from, to));
+        }
+        let divisor = gcd(from, to);
+        let (from, to) = (from / divisor, to / divisor);
+        let (crossings, cutoff) = quality.parameters();
+        // when downsampling the cutoff is below the new Nyquist frequency,
+        // without conversion the kernel passes the input through
+        let scale = if from == to {
+            1.0
+        } else {
+            cutoff * (to as f64 / from as f64).min(1.0)
+        };
+        let width = (crossings as f64 / scale).ceil() as usize;
+        let kernel = (0..width * RESOLUTION + 2)
+            .map(|i| {
+                let d = i as f64 / RESOLUTION as f64;
+                if d >= width as f64 {
+                    return 0.0;
+                }
+                let sinc = if d == 0.0 {
+                    1.0
+                } else {
+                    (PI * scale * d).sin() / (PI * scale * d)
+                };
+                // a Blackman window over the whole kernel
+                let u = PI * d / width as f64;
+                let window = 0.42 + 0.5 * u.cos() + 0.08 * (2.0 * u).cos();
+                scale * sinc * window
+            })
+            .collect();
+        Ok(Resampler {
+            from: from,
+            to: to,
+            width: width,
+            kernel: kernel,
+            // the samples before the first input are silent
+            buffer: vec![0.0; width],
+            first: -(width as i64),
+            index: 0,
+            phase: 0,
+        })
+    }
+
+    /// The number of input samples a Resampler needs after the point it
+    /// interpolates before it can output it.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Forget all input.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(self.width, 0.0);
+        self.first = -(self.width as i64);
+        self.index = 0;
+        self.phase = 0;
+    }
+
+    fn kernel(&self, d: f64) -> f64 {
+        let position = d.abs() * RESOLUTION as f64;
+        let i = position as usize;
+        let f = position - i as f64;
+        self.kernel[i] + (self.kernel[i + 1] - self.kernel[i]) * f
+    }
+
+    /// Add one input sample and append all output samples that are complete now.
+    pub fn process(&mut self, x: f64, output: &mut Vec<f64>) {
+        self.buffer.push(x);
+        let last = self.first + self.buffer.len() as i64 - 1;
+        let width = self.width as i64;
+        while self.index + width <= last {
+            let t = self.phase as f64 / self.to as f64;
+            let start = (self.index - width + 1 - self.first) as usize;
+            let mut y = 0.0;
+            for (k, &x) in self.buffer[start..start + 2 * self.width]
+                .iter()
+                .enumerate()
+            {
+                // the distance of this input from the point in time of the output
+                let d = (width - 1 - k as i64) as f64 + t;
+                y += x * self.kernel(d);
+            }
+            output.push(y);
+            self.phase += self.from;
+            while self.phase >= self.to {
+                self.phase -= self.to;
+                self.index += 1;
+            }
+        }
+        // drop the inputs that are not needed anymore, not for every sample
+        let unused = (self.index - width + 1 - self.first).max(0) as usize;
+        if unused > 4 * self.width {
+            self.buffer.drain(..unused);
+            self.first += unused as i64;
+        }
+    }
+}
+
+/// Parses `fast`, `medium` or `best`.
+pub fn parse_quality(name: &str) -> Result<Quality, String> {
+    match name {
+        "fast" => Ok(Quality::Fast),
+        "medium" => Ok(Quality::Medium),
+        "best" => Ok(Quality::Best),
+        _ => Err(format!("unknown resampling quality '{}'", name)),
+    }
+}
+
+/// Convert a whole signal from one samplerate to another.
+/// The output lasts as long as the input.
+pub fn resample(
+    input: &[f64],
+    from: usize,
+    to: usize,
+    quality: Quality,
+) -> Result<Vec<f64>, String> {
+    let mut resampler = Resampler::new(from, to, quality)?;
+    let len = (input.len() as u64 * to as u64 + from as u64 - 1) / from as u64;
+    let len = len as usize;
+    let mut output = Vec::with_capacity(len + 1);
+    for &x in input {
+        resampler.process(x, &mut output);
+    }
+    // the last outputs need the silence after the input
+    while output.len() < len {
+        resampler.process(0.0, &mut output);
+    }
+    output.truncate(len);
+    Ok(output)
+}
+
+/// Convert whole signal of Frames from one samplerate to another.
+pub fn resample_frames<F>(
+    frames: &[F],
+    from: usize,
+    to: usize,
+    quality: Quality,
+) -> Result<Vec<F>, String>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let channels = (0..F::n_channels())
+        .map(|channel| {
+            let input: Vec<f64> = frames
+                .iter()
+                .map(|frame| channel_f64(frame, channel))
+                .collect();
+            resample(&input, from, to, quality)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let len = channels.first().map_or(0, |channel| channel.len());
+    Ok((0..len)
+        .map(|i| map_f64(F::equilibrium(), |channel, _| channels[channel][i]))
+        .collect())
+}
+
+// converts every channel of `frames` with its own Resampler and appends the
+// results to `converted`, returns how many samples every channel has now
+fn convert<F>(resamplers: &mut [Resampler], frames: &[F], converted: &mut [Vec<f64>]) -> usize
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let channels = F::n_channels();
+    for (port, frame) in frames.iter().enumerate() {
+        for channel in 0..channels {
+            let index = port * channels + channel;
+            resamplers[index].process(channel_f64(frame, channel), &mut converted[index]);
+        }
+    }
+    converted[0].len()
+}
+
+/// Runs a Processor at its own samplerate inside a Graph with another one.
+/// The inputs are converted to the samplerate of the Processor, its outputs
+/// back to the samplerate of the Graph.
+///
+/// The Processor always gets blocks of the same number of Frames, so the
+/// converted inputs are collected until a block is complete. The outputs are
+/// delayed long enough that a block is always ready in time, this delay is
+/// reported as latency.
+///
+/// Control and event ports are not converted: every block of the Processor
+/// gets the last value of a control input, the last value of a control output
+/// is passed on without the delay, and events are passed on in the next block
+/// of the other side with their times converted to its samplerate.
+pub struct Resampled<P, F> {
+    processor: P,
+    samplerate: usize,
+    outer_samplerate: usize,
+    block: usize,
+    quality: Quality,
+    // one for every channel of every input or output, port after port
+    upsamplers: Vec<Resampler>,
+    downsamplers: Vec<Resampler>,
+    // counts the Frames of a Processor without inputs, in steps of 1 / outer_samplerate
+    clock: usize,
+    // the converted inputs that are not processed yet, one Frame per input
+    pending: VecDeque<F>,
+    pending_frames: usize,
+    // the converted outputs that are not played yet, one Frame per output
+    ready: VecDeque<F>,
+    delay: usize,
+    inputs: BufferSet<F>,
+    outputs: BufferSet<F>,
+    converted: Vec<Vec<f64>>,
+    // the last value of every control input and output
+    controls: Vec<F>,
+    held: Vec<F>,
+    // the events of one port with converted times
+    events: Vec<Event>,
+    // the events of every output of the processed blocks
+    taken: Vec<Vec<Event>>,
+}
+
+impl<P, F> Resampled<P, F>
+where
+    P: Processor<F>,
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    /// Run `processor` at `samplerate` with blocks of `block` Frames.
+    pub fn new(processor: P, samplerate: usize, block: usize, quality: Quality) -> Self {
+        Resampled {
+            processor: processor,
+            samplerate: samplerate,
+            outer_samplerate: samplerate,
+            block: block.max(1),
+            quality: quality,
+            upsamplers: Vec::new(),
+            downsamplers: Vec::new(),
+            clock: 0,
+            pending: VecDeque::new(),
+            pending_frames: 0,
+            ready: VecDeque::new(),
+            delay: 0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            converted: Vec::new(),
+            controls: Vec::new(),
+            held: Vec::new(),
+            events: Vec::new(),
+            taken: Vec::new(),
+        }
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn into_inner(self) -> P {
+        self.processor
+    }
+
+    fn allocate(&mut self) {
+        let channels = F::n_channels();
+        let (inner, outer, quality) = (self.samplerate, self.outer_samplerate, self.quality);
+        let (inputs_amt, outputs_amt) = (self.processor.inputs_amt(), self.processor.outputs_amt());
+        // the samplerates were checked in `set_samplerate`
+        self.upsamplers = (0..inputs_amt * channels)
+            .map(|_| Resampler::new(outer, inner, quality).unwrap())
+            .collect();
+        self.downsamplers = (0..outputs_amt * channels)
+            .map(|_| Resampler::new(inner, outer, quality).unwrap())
+            .collect();
+        // the outer Frames it takes until a block and the kernels are complete
+        let up = self.upsamplers.first().map_or(0, |r| r.width());
+        let down = self.downsamplers.first().map_or(0, |r| r.width());
+        let ratio = outer as f64 / inner as f64;
+        self.delay = ((down + self.block) as f64 * ratio).ceil() as usize + up + 2;
+        self.clock = 0;
+        self.pending.clear();
+        self.pending_frames = 0;
+        self.ready.clear();
+        for _ in 0..self.delay * outputs_amt {
+            self.ready.push_back(F::equilibrium());
+        }
+        self.inputs = empty_buffer(inputs_amt, self.block);
+        self.outputs = empty_buffer(outputs_amt, self.block);
+        self.converted = vec![Vec::new(); (inputs_amt.max(outputs_amt) * channels).max(1)];
+        self.controls = vec![F::equilibrium(); inputs_amt];
+        self.held = vec![F::equilibrium(); outputs_amt];
+        self.taken = vec![Vec::new(); outputs_amt];
+    }
+
+    // the Frame `i` of port `port` of the converted channels
+    fn converted_frame(&self, port: usize, i: usize) -> F {
+        let channels = F::n_channels();
+        map_f64(F::equilibrium(), |channel, _| {
+            self.converted[port * channels + channel][i]
+        })
+    }
+
+    // processes a block of pending inputs and converts its outputs
+    fn process_block(&mut self) {
+        let (inputs_amt, outputs_amt) = (self.processor.inputs_amt(), self.processor.outputs_amt());
+        for frames in self.inputs.iter_mut() {
+            for frame in frames.iter_mut().take(inputs_amt) {
+                *frame = self.pending.pop_front().unwrap();
+            }
+        }
+        self.pending_frames -= self.block;
+        for port in 0..inputs_amt {
+            if self.processor.input_type(port) != PortType::Audio {
+                for (i, frames) in self.inputs.iter_mut().enumerate() {
+                    frames[port] = if i == 0 { self.controls[port] } else { F::equilibrium() };
+                }
+            }
+        }
+        self.processor.process(&self.inputs, &mut self.outputs);
+        for port in 0..outputs_amt {
+            match self.processor.output_type(port) {
+                PortType::Audio => {}
+                PortType::Control => self.held[port] = self.outputs[0][port],
+                PortType::Event => self.processor.take_events(port, &mut self.taken[port]),
+            }
+        }
+        for converted in &mut self.converted {
+            converted.clear();
+        }
+        let mut len = 0;
+        for frames in &self.outputs {
+            len = convert(&mut self.downsamplers, frames, &mut self.converted);
+        }
+        for i in 0..len {
+            for port in 0..outputs_amt {
+                let frame = self.converted_frame(port, i);
+                self.ready.push_back(frame);
+            }
+        }
+    }
+}
+
+impl<F> Resampled<Graph<F>, F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64> + Kernel,
+{
+    /// Run a Graph at its own samplerate and buffersize.
+    pub fn graph(graph: Graph<F>, quality: Quality) -> Self {
+        let (samplerate, block) = (graph.samplerate(), graph.buffersize());
+        Resampled::new(graph, samplerate, block, quality)
+    }
+}
+
+impl<P, F> Processor<F> for Resampled<P, F>
+where
+    P: Processor<F>,
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        let (inputs_amt, outputs_amt) = (self.processor.inputs_amt(), self.processor.outputs_amt());
+        if self.upsamplers.len() != inputs_amt * F::n_channels() || self.inputs.len() != self.block
+        {
+            // only happens when the Resampled is used without a Graph
+            self.allocate();
+        }
+        if let Some(frames) = inputs.first() {
+            for port in 0..inputs_amt {
+                if self.processor.input_type(port) == PortType::Control {
+                    self.controls[port] = frames[port];
+                }
+            }
+        }
+        for (frames, output) in inputs.iter().zip(outputs.iter_mut()) {
+            for converted in &mut self.converted {
+                converted.clear();
+            }
+            let len = if inputs_amt > 0 {
+                convert(
+                    &mut self.upsamplers,
+                    &frames[..inputs_amt],
+                    &mut self.converted,
+                )
+            } else {
+                // without inputs only the number of Frames matters
+                self.clock += self.samplerate;
+                let len = self.clock / self.outer_samplerate;
+                self.clock %= self.outer_samplerate;
+                len
+            };
+            for i in 0..len {
+                for port in 0..inputs_amt {
+                    let frame = self.converted_frame(port, i);
+                    self.pending.push_back(frame);
+                }
+            }
+            self.pending_frames += len;
+            while self.pending_frames >= self.block {
+                self.process_block();
+            }
+            for (port, frame) in output.iter_mut().take(outputs_amt).enumerate() {
+                *frame = self.ready.pop_front().unwrap_or(F::equilibrium());
+                if self.processor.output_type(port) != PortType::Audio {
+                    *frame = self.held[port];
+                }
+            }
+        }
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        // the processor keeps its own samplerate
+        if samplerate == 0 || self.samplerate == 0 {
+            return;
+        }
+        self.processor.set_samplerate(self.samplerate);
+        self.outer_samplerate = samplerate;
+        self.allocate();
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        self.processor.input_info(input)
+    }
+
+    fn output_info(&self, output: usize) -> PortInfo {
+        self.processor.output_info(output)
+    }
+
+    fn input_type(&self, input: usize) -> PortType {
+        self.processor.input_type(input)
+    }
+
+    fn output_type(&self, output: usize) -> PortType {
+        self.processor.output_type(output)
+    }
+
+    fn handle_events(&mut self, input: usize, events: &[Event]) {
+        let (inner, outer) = (self.samplerate, self.outer_samplerate);
+        let last = self.block - 1;
+        self.events.clear();
+        self.events.extend(
+            events
+                .iter()
+                .map(|event| Event::new((event.time * inner / outer).min(last), event.kind)),
+        );
+        self.processor.handle_events(input, &self.events);
+    }
+
+    fn take_events(&mut self, output: usize, events: &mut Vec<Event>) {
+        let (inner, outer) = (self.samplerate, self.outer_samplerate);
+        if let Some(taken) = self.taken.get_mut(output) {
+            events.extend(
+                taken
+                    .drain(..)
+                    .map(|event| Event::new(event.time * outer / inner, event.kind)),
+            );
+        }
+    }
+
+    fn tail_length(&self) -> usize {
+        let tail = self.processor.tail_length() as f64;
+        (tail * self.outer_samplerate as f64 / self.samplerate as f64).ceil() as usize
+    }
+
+    fn latency(&self) -> usize {
+        let latency = self.processor.latency() as f64;
+        self.delay
+            + (latency * self.outer_samplerate as f64 / self.samplerate as f64).round() as usize
+    }
+
+    fn inputs_amt(&self) -> usize {
+        self.processor.inputs_amt()
+    }
+
+    fn outputs_amt(&self) -> usize {
+        self.processor.outputs_amt()
+    }
+}
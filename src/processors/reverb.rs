@@ -0,0 +1,250 @@
+//! An algorithmic stereo reverb after Freeverb by Jezar at Dreampoint:
+//! eight lowpass feedback comb filters in parallel and four allpass filters
+//! in series for each side.
+//!
+//! All channels of the input are summed before they enter the reverb.
+//! The left side goes to the even channels of the output, the right side to
+//! the odd ones, a mono Frame gets both.
+
+extern crate sample;
+
+use graph::FrameSet;
+use patch::{Params, Registry};
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::Frame;
+use super::delay::{DelayLine, Interpolation};
+use super::{channel_f64, map_f64};
+
+// the delay lengths of the original at 44.1kHz
+const COMBS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASSES: [usize; 4] = [556, 441, 341, 225];
+// how much longer the delays of the right side are
+const SPREAD: usize = 23;
+const INPUT_GAIN: f64 = 0.015;
+const WET_GAIN: f64 = 3.0;
+// the longest pre-delay in seconds
+const MAX_PREDELAY: f64 = 1.0;
+
+struct Comb {
+    buffer: Vec<f64>,
+    index: usize,
+    filter: f64,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Comb {
+            buffer: vec![0.0; len.max(1)],
+            index: 0,
+            filter: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64, feedback: f64, damping: f64) -> f64 {
+        let y = self.buffer[self.index];
+        self.filter = y * (1.0 - damping) + self.filter * damping;
+        self.buffer[self.index] = x + self.filter * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        y
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f64>,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Allpass {
+            buffer: vec![0.0; len.max(1)],
+            index: 0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = x + delayed * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - x
+    }
+}
+
+// the filters of one side
+struct Side {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Side {
+    fn new(samplerate: f64, spread: usize) -> Self {
+        let scale = |len: usize| ((len + spread) as f64 * samplerate / 44_100.0) as usize;
+        Side {
+            combs: COMBS.iter().map(|&len| Comb::new(scale(len))).collect(),
+            allpasses: ALLPASSES
+                .iter()
+                .map(|&len| Allpass::new(scale(len)))
+                .collect(),
+        }
+    }
+
+    fn process(&mut self, x: f64, feedback: f64, damping: f64) -> f64 {
+        let mut y = 0.0;
+        for comb in &mut self.combs {
+            y += comb.process(x, feedback, damping);
+        }
+        for allpass in &mut self.allpasses {
+            y = allpass.process(y);
+        }
+        y
+    }
+}
+
+/// A stereo reverb.
+pub struct Reverb {
+    size: f64,
+    damping: f64,
+    predelay: f64,
+    width: f64,
+    mix: f64,
+    samplerate: f64,
+    left: Side,
+    right: Side,
+    delay: DelayLine,
+}
+
+impl Reverb {
+    /// Create a Reverb with a medium size and damping, full width,
+    /// no pre-delay and a mix of 0.3.
+    pub fn new() -> Self {
+        Reverb {
+            size: 0.5,
+            damping: 0.5,
+            predelay: 0.0,
+            width: 1.0,
+            mix: 0.3,
+            samplerate: 44_100.0,
+            left: Side::new(44_100.0, 0),
+            right: Side::new(44_100.0, SPREAD),
+            delay: DelayLine::new((MAX_PREDELAY * 44_100.0) as usize + 1),
+        }
+    }
+
+    /// Set the size of the room from 0.0 to 1.0, larger rooms reverberate longer.
+    pub fn set_size(&mut self, size: f64) {
+        self.size = size.max(0.0).min(1.0);
+    }
+
+    /// Set how fast high frequencies die away, from 0.0 to 1.0.
+    pub fn set_damping(&mut self, damping: f64) {
+        self.damping = damping.max(0.0).min(1.0);
+    }
+
+    /// Set the time before the reverb starts, in seconds up to 1.0.
+    pub fn set_predelay(&mut self, predelay: f64) {
+        self.predelay = predelay.max(0.0).min(MAX_PREDELAY);
+    }
+
+    /// Set the stereo width from 0.0 (mono) to 1.0.
+    pub fn set_width(&mut self, width: f64) {
+        self.width = width.max(0.0).min(1.0);
+    }
+
+    /// Set the balance between the dry signal (0.0) and the reverb (1.0).
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = mix.max(0.0).min(1.0);
+    }
+
+    fn feedback(&self) -> f64 {
+        0.7 + 0.28 * self.size
+    }
+
+    fn allocate(&mut self) {
+        self.left = Side::new(self.samplerate, 0);
+        self.right = Side::new(self.samplerate, SPREAD);
+        self.delay = DelayLine::new((MAX_PREDELAY * self.samplerate) as usize + 1);
+    }
+}
+
+impl<F> Processor<F> for Reverb
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let sum =
+            (0..F::n_channels()).fold(0.0, |sum, channel| sum + channel_f64(&inputs[0], channel));
+        self.delay.write(sum * INPUT_GAIN);
+        // the sample that was just written is one sample ago
+        let predelay = self.predelay * self.samplerate + 1.0;
+        let x = self.delay.read(predelay, Interpolation::Linear);
+        let (feedback, damping) = (self.feedback(), self.damping * 0.4);
+        let left = self.left.process(x, feedback, damping);
+        let right = self.right.process(x, feedback, damping);
+        let wet = self.mix * WET_GAIN;
+        let (direct, crossed) = (
+            wet * (0.5 + self.width / 2.0),
+            wet * (1.0 - self.width) / 2.0,
+        );
+        let dry = 1.0 - self.mix;
+        let sides = if F::n_channels() == 1 {
+            let mono = wet * (left + right) / 2.0;
+            [mono, mono]
+        } else {
+            [
+                direct * left + crossed * right,
+                direct * right + crossed * left,
+            ]
+        };
+        outputs[0] = map_f64(inputs[0], |channel, x| dry * x + sides[channel % 2]);
+    }
+
+    fn set_samplerate(&mut self, samplerate: usize) {
+        self.samplerate = samplerate as f64;
+        self.allocate();
+    }
+
+    fn tail_length(&self) -> usize {
+        // the time the longest comb takes to decay by 60dB
+        let longest = (COMBS[7] + SPREAD) as f64 * self.samplerate / 44_100.0;
+        let decay = longest * 0.001f64.ln() / self.feedback().ln();
+        (decay + self.predelay * self.samplerate).ceil() as usize
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `reverb(size: 0.5, damping: 0.5, predelay: 0, width: 1, mix: 0.3)`,
+///   the pre-delay is in seconds, all other parameters go from 0 to 1
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("reverb", |params: &Params| {
+        let mut reverb = Reverb::new();
+        reverb.set_size(params.number("size", 0.5)?);
+        reverb.set_damping(params.number("damping", 0.5)?);
+        reverb.set_predelay(params.number("predelay", 0.0)?);
+        reverb.set_width(params.number("width", 1.0)?);
+        reverb.set_mix(params.number("mix", 0.3)?);
+        Ok(Box::new(reverb) as Box<Processor<F>>)
+    });
+}
@@ -0,0 +1,1141 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::super::graph::{BufferSet, Graph};
+    use super::super::super::io::wav::WavPlayer;
+    use super::super::super::planar::PlanarBuffer;
+    use super::super::super::processor::{Event, EventKind, PortType, Processor};
+    use super::super::channels::*;
+    use super::super::convolution::*;
+    use super::super::delay::*;
+    use super::super::distortion::{self, Bitcrusher, Waveshaper};
+    use super::super::dynamics::*;
+    use super::super::envelope::*;
+    use super::super::fft::*;
+    use super::super::filter::{Biquad, Cascade, Coefficients, FilterType, Svf};
+    use super::super::lfo::*;
+    use super::super::modfx::*;
+    use super::super::osc::*;
+    use super::super::oversample::*;
+    use super::super::resample::*;
+    use super::super::reverb::*;
+    use super::super::util::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+    use std::f64::consts::PI;
+
+    // runs a processor over one block, every input gets one frame per sample
+    fn run<P>(processor: &mut P, inputs: Vec<Vec<[f32; 2]>>) -> BufferSet<[f32; 2]>
+    where
+        P: Processor<[f32; 2]>,
+    {
+        let mut outputs = vec![vec![[0.0; 2]; processor.outputs_amt()]; inputs.len()];
+        processor.process(&inputs, &mut outputs);
+        outputs
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} is not {}", a, b);
+    }
+
+    // sends its events on its only output in the next block
+    struct EventSource {
+        events: Vec<Event>,
+    }
+
+    impl Processor<[f32; 2]> for EventSource {
+        fn output_type(&self, _: usize) -> PortType {
+            PortType::Event
+        }
+        fn take_events(&mut self, _: usize, events: &mut Vec<Event>) {
+            events.extend(self.events.drain(..));
+        }
+        fn inputs_amt(&self) -> usize {
+            0
+        }
+        fn outputs_amt(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn mixer_test() {
+        let mut mixer = Mixer::with_gains(vec![1.0, 0.5, 2.0]);
+        let output = run(&mut mixer, vec![vec![[0.1, 0.2], [0.4, 0.4], [0.1, -0.1]]]);
+        assert_near(output[0][0][0], 0.1 + 0.2 + 0.2);
+        assert_near(output[0][0][1], 0.2 + 0.2 - 0.2);
+        // the planar version mixes the same way
+        let inputs = vec![vec![[0.1, 0.2], [0.4, 0.4], [0.1, -0.1]]];
+        let mut outputs = PlanarBuffer::new(1, 2, 1);
+        outputs.channel_mut(0, 0)[0] = 1.0;
+        let planar = PlanarBuffer::from_interleaved(&inputs);
+        Processor::<[f32; 2]>::process_planar(&mut mixer, &planar, &mut outputs);
+        assert_eq!(outputs.to_interleaved::<[f32; 2]>(), run(&mut mixer, inputs));
+    }
+
+    #[test]
+    fn gain_test() {
+        let mut gain = Gain::from_db(-6.0);
+        let output = run(&mut gain, vec![vec![[1.0, -0.5], [0.0; 2]]]);
+        assert_near(output[0][0][0], 0.501187);
+        assert_near(output[0][0][1], -0.250594);
+        // the modulation input is added to the gain
+        let mut gain = Gain::new(0.0);
+        let output = run(&mut gain, vec![vec![[1.0, -0.5], [0.5, 0.0]]]);
+        assert_eq!(output[0][0], [0.5, -0.25]);
+    }
+
+    #[test]
+    fn pan_test() {
+        let mut pan = Pan::new(0.0, PanLaw::ConstantPower);
+        let output = run(&mut pan, vec![vec![[1.0, 1.0], [0.0; 2]]]);
+        assert_near(output[0][0][0], FRAC_1_SQRT_2);
+        assert_near(output[0][0][1], FRAC_1_SQRT_2);
+        let mut pan = Pan::new(0.0, PanLaw::Linear);
+        let output = run(
+            &mut pan,
+            vec![vec![[1.0, 1.0], [-1.0, 0.0]], vec![[1.0, 1.0], [0.0; 2]]],
+        );
+        assert_eq!(output[0][0], [1.0, 0.0]);
+        assert_eq!(output[1][0], [0.5, 0.5]);
+    }
+
+    #[test]
+    fn routing_test() {
+        let mut splitter = Splitter::new(3);
+        let output = run(&mut splitter, vec![vec![[0.1, 0.2]]]);
+        assert_eq!(output[0], vec![[0.1, 0.2]; 3]);
+        let planar = PlanarBuffer::from_interleaved(&vec![vec![[0.1, 0.2]], vec![[0.3, 0.4]]]);
+        let mut outputs = PlanarBuffer::new(3, 2, 2);
+        Processor::<[f32; 2]>::process_planar(&mut splitter, &planar, &mut outputs);
+        assert_eq!(
+            outputs.to_interleaved::<[f32; 2]>(),
+            vec![vec![[0.1, 0.2]; 3], vec![[0.3, 0.4]; 3]]
+        );
+        let mut constant = Constant::from_value(0.25);
+        let output = run(&mut constant, vec![vec![], vec![]]);
+        assert_eq!(output, vec![vec![[0.25, 0.25]]; 2]);
+        let output = run(&mut Invert {}, vec![vec![[0.1, -0.2]]]);
+        assert_eq!(output[0][0], [-0.1, 0.2]);
+        let output = run(&mut DcOffset::new(0.5), vec![vec![[0.1, -0.2]]]);
+        assert_eq!(output[0][0], [0.6, 0.3]);
+        let mut mute = Mute::new(true);
+        assert_eq!(run(&mut mute, vec![vec![[0.1, -0.2]]])[0][0], [0.0, 0.0]);
+        mute.set_muted(false);
+        assert_eq!(run(&mut mute, vec![vec![[0.1, -0.2]]])[0][0], [0.1, -0.2]);
+    }
+
+    #[test]
+    fn crossfade_test() {
+        let mut crossfade = Crossfade::new(0.0, PanLaw::Linear);
+        let output = run(
+            &mut crossfade,
+            vec![
+                vec![[1.0, 1.0], [0.0, -1.0], [0.0; 2]],
+                vec![[1.0, 1.0], [0.0, -1.0], [0.25, 0.0]],
+                vec![[1.0, 1.0], [0.0, -1.0], [1.0, 0.0]],
+            ],
+        );
+        assert_eq!(output[0][0], [1.0, 1.0]);
+        assert_eq!(output[1][0], [0.75, 0.5]);
+        assert_eq!(output[2][0], [0.0, -1.0]);
+    }
+
+    #[test]
+    fn matrix_test() {
+        let matrix = Matrix::surround_to_stereo();
+        let stereo: [f32; 2] = matrix.apply(&[1.0f32, 0.5, 0.5, 1.0, 0.25, -0.25]);
+        let g = 0.5f32.sqrt();
+        assert_near(stereo[0], 1.0 + g * 0.75);
+        assert_near(stereo[1], 0.5 + g * 0.25);
+        let mono: [f32; 1] = Matrix::between(2, 1).apply(&[1.0f32, 0.5]);
+        assert_eq!(mono, [0.75]);
+        let stereo: [f32; 2] = Matrix::between(1, 2).apply(&mono);
+        assert_eq!(stereo, [0.75, 0.75]);
+        // channel N goes to channel N, missing channels are silent
+        let quad: [f32; 4] = Matrix::between(2, 4).apply(&[0.1f32, 0.2]);
+        assert_eq!(quad, [0.1, 0.2, 0.0, 0.0]);
+        assert!(Matrix::from_rows(vec![vec![1.0], vec![1.0, 0.0]]).is_err());
+    }
+
+    #[test]
+    fn adapted_test() {
+        // a mono Gain in a stereo Graph
+        let mut adapted = Adapted::<_, [f32; 1]>::new(Gain::new(2.0));
+        let output = run(&mut adapted, vec![vec![[1.0, 0.5], [0.0; 2]]]);
+        assert_eq!(output[0][0], [1.5, 1.5]);
+        // a stereo Gain in a 5.1 Graph, with the left channel on every output
+        let matrix = Matrix::from_rows(vec![vec![1.0, 0.0]; 6]).unwrap();
+        let mut adapted =
+            Adapted::<_, [f32; 2]>::with_matrices(Gain::new(0.5), Matrix::between(6, 2), matrix);
+        let inputs = vec![vec![[1.0f32, 0.0, 1.0, 0.0, 0.0, 0.0], [0.0; 6]]; 2];
+        let mut outputs = vec![vec![[0.0; 6]]; 2];
+        adapted.process(&inputs, &mut outputs);
+        assert_near(outputs[1][0][5], 0.5 * 1.707107);
+        assert_eq!(Processor::<[f32; 6]>::inputs_amt(&adapted), 2);
+        // the latency of the wrapped Processor is reported
+        let limiter = Limiter::new(0.0, 4.0 / 48_000.0, 0.1);
+        let mut adapted = Adapted::<_, [f32; 1]>::new(limiter);
+        Processor::<[f32; 2]>::set_samplerate(&mut adapted, 48_000);
+        let latency = Processor::<[f32; 1]>::latency(adapted.processor());
+        assert!(latency > 0);
+        assert_eq!(Processor::<[f32; 2]>::latency(&adapted), latency);
+    }
+
+    #[test]
+    fn split_merge_test() {
+        let output = run(&mut Split::new(2), vec![vec![[0.1, 0.2]]]);
+        assert_eq!(output[0], vec![[0.1, 0.1], [0.2, 0.2]]);
+        let output = run(&mut Merge::new(2), vec![vec![[0.3, 0.0], [0.4, 1.0]]]);
+        assert_eq!(output[0][0], [0.3, 0.4]);
+        let output = run(&mut ChannelMatrix::downmix(2), vec![vec![[0.1, 0.3]]]);
+        assert_near(output[0][0][0], 0.2);
+        assert_near(output[0][0][1], 0.2);
+        let output = run(&mut ChannelMatrix::upmix(2), vec![vec![[0.1, 0.3]]]);
+        assert_eq!(output[0][0], [0.1, 0.1]);
+    }
+
+    // renders `len` samples of an oscillator at 48 kHz without any input
+    fn oscillate<P>(processor: &mut P, len: usize) -> Vec<f32>
+    where
+        P: Processor<[f32; 2]>,
+    {
+        processor.set_samplerate(48_000);
+        let output = run(processor, vec![vec![[0.0; 2]; 4]; len]);
+        output.iter().map(|frame| frame[0][0]).collect()
+    }
+
+    #[test]
+    fn sine_test() {
+        let mut sine = Oscillator::new(Waveform::Sine, 12_000.0);
+        let output = oscillate(&mut sine, 4);
+        assert_near(output[0], 0.0);
+        assert_near(output[1], 1.0);
+        assert_near(output[2], 0.0);
+        assert_near(output[3], -1.0);
+    }
+
+    #[test]
+    fn band_limited_test() {
+        let waveforms = [
+            Waveform::Saw,
+            Waveform::Square,
+            Waveform::Pulse(0.25),
+            Waveform::Triangle,
+        ];
+        for &waveform in &waveforms {
+            let mut oscillator = Oscillator::new(waveform, 440.0);
+            let output = oscillate(&mut oscillator, 4800);
+            let mean = output.iter().sum::<f32>() / output.len() as f32;
+            let peak = output.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+            let expected = if let Waveform::Pulse(width) = waveform {
+                2.0 * width as f32 - 1.0
+            } else {
+                0.0
+            };
+            assert!(
+                (mean - expected).abs() < 0.01,
+                "{:?}: mean {}",
+                waveform,
+                mean
+            );
+            assert!(peak > 0.9 && peak <= 1.05, "{:?}: peak {}", waveform, peak);
+            // no naive jumps from -1 to 1 are left
+            for pair in output.windows(2) {
+                assert!((pair[1] - pair[0]).abs() < 1.5, "{:?}", waveform);
+            }
+        }
+    }
+
+    #[test]
+    fn oscillator_inputs_test() {
+        // the frequency input is added to the frequency
+        let mut sine = Oscillator::new(Waveform::Sine, 0.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut sine, 48_000);
+        let inputs = vec![vec![[12_000.0; 2], [0.0; 2], [0.0; 2], [0.0; 2]]; 2];
+        let output = run(&mut sine, inputs);
+        assert_near(output[1][0][0], 1.0);
+        // phase modulation is in cycles
+        let inputs = vec![vec![[0.0; 2], [0.25; 2], [0.0; 2], [0.0; 2]]];
+        let mut sine = Oscillator::new(Waveform::Sine, 0.0);
+        assert_near(run(&mut sine, inputs)[0][0][0], 1.0);
+        // a rising sync input resets the phase
+        let mut sine = Oscillator::new(Waveform::Sine, 12_000.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut sine, 48_000);
+        let sync = [0.0, 0.0, 1.0, 1.0];
+        let inputs = sync
+            .iter()
+            .map(|&s| vec![[0.0; 2], [0.0; 2], [s; 2], [0.0; 2]])
+            .collect();
+        let output = run(&mut sine, inputs);
+        assert_near(output[2][0][0], 0.0);
+        assert_near(output[3][0][0], 1.0);
+    }
+
+    #[test]
+    fn wavetable_test() {
+        // a square wave with many harmonics
+        let cycle: Vec<f64> = (0..256).map(|i| if i < 128 { 1.0 } else { -1.0 }).collect();
+        let mut low = WavetableOscillator::new(&cycle, 100.0);
+        let output = oscillate(&mut low, 480);
+        assert!(output[100] > 0.9 && output[300] < -0.9);
+        // close to nyquist only the fundamental is left
+        let mut high = WavetableOscillator::new(&cycle, 12_000.0);
+        let output = oscillate(&mut high, 4);
+        let fundamental = 4.0 / ::std::f32::consts::PI;
+        assert!((output[1] - fundamental).abs() < 0.05, "{}", output[1]);
+        assert!((output[3] + fundamental).abs() < 0.05, "{}", output[3]);
+    }
+
+    // the gain of a processor for a sine at `frequency`, measured after it settled
+    fn sine_gain<P>(processor: &mut P, frequency: f64, output: usize) -> f32
+    where
+        P: Processor<[f32; 2]>,
+    {
+        processor.set_samplerate(48_000);
+        let inputs = (0..9600)
+            .map(|i| {
+                let x = (2.0 * ::std::f64::consts::PI * frequency * i as f64 / 48_000.0).sin();
+                vec![[x as f32; 2], [0.0; 2]]
+            })
+            .collect();
+        let outputs = run(processor, inputs);
+        outputs[4800..]
+            .iter()
+            .fold(0.0f32, |peak, frame| peak.max(frame[output][0].abs()))
+    }
+
+    #[test]
+    fn biquad_test() {
+        let c = Coefficients::new(FilterType::Lowpass, 1000.0, 0.707, 48_000.0);
+        assert!((c.magnitude(1000.0, 48_000.0) - 0.707).abs() < 0.01);
+        assert!(c.magnitude(10.0, 48_000.0) > 0.99);
+        let c = Coefficients::new(FilterType::Peak(6.0), 1000.0, 1.0, 48_000.0);
+        assert!((c.magnitude(1000.0, 48_000.0) - 1.995).abs() < 0.01);
+        let c = Coefficients::new(FilterType::LowShelf(-12.0), 1000.0, 0.707, 48_000.0);
+        assert!((c.magnitude(10.0, 48_000.0) - 0.251).abs() < 0.01);
+        assert!((c.magnitude(20_000.0, 48_000.0) - 1.0).abs() < 0.01);
+        let c = Coefficients::new(FilterType::Allpass, 1000.0, 0.707, 48_000.0);
+        assert!((c.magnitude(3000.0, 48_000.0) - 1.0).abs() < 1e-6);
+
+        let mut highpass = Biquad::new(FilterType::Highpass, 1000.0, 0.707);
+        assert!(sine_gain(&mut highpass, 100.0, 0) < 0.02);
+        assert!(sine_gain(&mut highpass, 10_000.0, 0) > 0.98);
+    }
+
+    #[test]
+    fn cascade_test() {
+        let butterworth = Cascade::butterworth(FilterType::Lowpass, 5, 1000.0).unwrap();
+        assert_eq!(butterworth.coefficients().len(), 3);
+        let mut butterworth = Cascade::butterworth(FilterType::Lowpass, 4, 1000.0).unwrap();
+        assert!((sine_gain(&mut butterworth, 1000.0, 0) - 0.707).abs() < 0.01);
+        // 24dB per octave
+        assert!((sine_gain(&mut butterworth, 2000.0, 0) - 0.062).abs() < 0.01);
+
+        let mut low = Cascade::linkwitz_riley(FilterType::Lowpass, 4, 1000.0).unwrap();
+        let mut high = Cascade::linkwitz_riley(FilterType::Highpass, 4, 1000.0).unwrap();
+        assert!((sine_gain(&mut low, 1000.0, 0) - 0.5).abs() < 0.01);
+        assert!((sine_gain(&mut high, 1000.0, 0) - 0.5).abs() < 0.01);
+        assert!(Cascade::linkwitz_riley(FilterType::Lowpass, 3, 1000.0).is_err());
+        assert!(Cascade::butterworth(FilterType::Notch, 2, 1000.0).is_err());
+    }
+
+    #[test]
+    fn svf_test() {
+        let mut svf = Svf::new(1000.0, 0.707);
+        assert!(sine_gain(&mut svf, 100.0, 0) > 0.99);
+        assert!(sine_gain(&mut svf, 10_000.0, 0) < 0.02);
+        assert!(sine_gain(&mut svf, 10_000.0, 2) > 0.98);
+        assert!(sine_gain(&mut svf, 1000.0, 3) < 0.01);
+        // the cutoff input moves the filter up
+        let mut svf = Svf::new(100.0, 0.707);
+        Processor::<[f32; 2]>::set_samplerate(&mut svf, 48_000);
+        let inputs = (0..9600)
+            .map(|i| {
+                let x = (2.0 * ::std::f64::consts::PI * 1000.0 * i as f64 / 48_000.0).sin();
+                vec![[x as f32; 2], [9900.0; 2]]
+            })
+            .collect();
+        let outputs = run(&mut svf, inputs);
+        let peak = outputs[4800..]
+            .iter()
+            .fold(0.0f32, |peak, frame| peak.max(frame[0][1].abs()));
+        assert!(peak > 0.99);
+    }
+
+    // runs an envelope over a gate signal at 1 kHz
+    fn envelope(envelope: &mut Envelope, gate: &[f32]) -> Vec<f32> {
+        Processor::<[f32; 2]>::set_samplerate(envelope, 1000);
+        let inputs = gate.iter().map(|&g| vec![[g; 2]]).collect();
+        let outputs = run(envelope, inputs);
+        outputs.iter().map(|frame| frame[0][0]).collect()
+    }
+
+    #[test]
+    fn envelope_test() {
+        let mut adsr = Envelope::adsr(0.01, 0.01, 0.5, 0.02);
+        adsr.set_curve(Curve::Linear);
+        assert_eq!(Processor::<[f32; 2]>::output_type(&adsr, 0), PortType::Control);
+        let mut gate = vec![1.0; 40];
+        gate.extend(vec![0.0; 30]);
+        let output = envelope(&mut adsr, &gate);
+        assert_near(output[0], 0.1);
+        assert_near(output[4], 0.5);
+        assert_near(output[9], 1.0);
+        assert_near(output[14], 0.75);
+        assert_near(output[19], 0.5);
+        assert_near(output[39], 0.5);
+        assert_near(output[49], 0.25);
+        assert_near(output[59], 0.0);
+        assert!(adsr.is_idle());
+
+        // the exponential curve reaches its levels at the same times
+        let mut ahdsr = Envelope::ahdsr(0.01, 0.005, 0.01, 0.5, 0.02);
+        let output = envelope(&mut ahdsr, &gate);
+        assert!(output[4] > 0.5);
+        assert_near(output[9], 1.0);
+        assert_near(output[14], 1.0);
+        assert!(output[19] < 0.75);
+        assert_near(output[24], 0.5);
+        assert!(output[49] < 0.25);
+        assert_near(output[59], 0.0);
+    }
+
+    #[test]
+    fn retrigger_test() {
+        // the gate opens again halfway through the release
+        let mut gate = vec![1.0; 20];
+        gate.extend(vec![0.0; 10]);
+        gate.extend(vec![1.0; 30]);
+        let modes = [
+            (Retrigger::Restart, 0.1),
+            (Retrigger::Continue, 0.35),
+            (Retrigger::Legato, 0.25),
+        ];
+        for &(mode, expected) in &modes {
+            let mut adsr = Envelope::adsr(0.01, 0.01, 0.5, 0.02);
+            adsr.set_curve(Curve::Linear);
+            adsr.set_retrigger(mode);
+            let output = envelope(&mut adsr, &gate);
+            assert_near(output[29], 0.25);
+            assert!(
+                (output[30] - expected).abs() < 0.03,
+                "{:?}: {}",
+                mode,
+                output[30]
+            );
+            assert_near(output[59], 0.5);
+        }
+    }
+
+    #[test]
+    fn note_event_test() {
+        let mut adsr = Envelope::adsr(0.0, 0.0, 1.0, 0.0);
+        Processor::<[f32; 2]>::handle_events(
+            &mut adsr,
+            1,
+            &[
+                Event::new(2, EventKind::NoteOn { note: 60, velocity: 1.0 }),
+                Event::new(4, EventKind::NoteOff { note: 61 }),
+                Event::new(6, EventKind::NoteOff { note: 60 }),
+            ],
+        );
+        // an unconnected gate does not release the note
+        let output = envelope(&mut adsr, &[0.0; 8]);
+        assert_eq!(output, vec![0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0]);
+
+        // a NoteOn sets the frequency of an oscillator, A4 is 440 Hz
+        let mut sine = Oscillator::new(Waveform::Sine, 100.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut sine, 1760);
+        Processor::<[f32; 2]>::handle_events(
+            &mut sine,
+            4,
+            &[Event::new(0, EventKind::NoteOn { note: 69, velocity: 1.0 })],
+        );
+        let output = run(&mut sine, vec![vec![[0.0; 2]; 5]; 4]);
+        assert_near(output[1][0][0], 1.0);
+        assert_near(output[3][0][0], -1.0);
+
+        // a wavetable has no pulse width input, its notes arrive at input 3
+        let cycle: Vec<f64> = (0..64).map(|i| (i as f64 / 32.0 * PI).sin()).collect();
+        let mut wavetable = WavetableOscillator::new(&cycle, 100.0);
+        assert_eq!(Processor::<[f32; 2]>::inputs_amt(&wavetable), 4);
+        assert_eq!(Processor::<[f32; 2]>::input_type(&wavetable, 3), PortType::Event);
+        Processor::<[f32; 2]>::set_samplerate(&mut wavetable, 1760);
+        Processor::<[f32; 2]>::handle_events(
+            &mut wavetable,
+            3,
+            &[Event::new(0, EventKind::NoteOn { note: 69, velocity: 1.0 })],
+        );
+        let output = run(&mut wavetable, vec![vec![[0.0; 2]; 4]; 4]);
+        assert!((output[1][0][0] - 1.0).abs() < 1e-3, "{}", output[1][0][0]);
+        assert!((output[3][0][0] + 1.0).abs() < 1e-3, "{}", output[3][0][0]);
+    }
+
+    #[test]
+    fn lfo_test() {
+        let shapes = [
+            (Shape::Sine, [0.0, 1.0, 0.0, -1.0]),
+            (Shape::Triangle, [0.0, 1.0, 0.0, -1.0]),
+            (Shape::Saw, [-1.0, -0.5, 0.0, 0.5]),
+            (Shape::Square, [1.0, 1.0, -1.0, -1.0]),
+        ];
+        for &(shape, expected) in &shapes {
+            let mut lfo = Lfo::new(shape, Rate::Hz(1.0));
+            Processor::<[f32; 2]>::set_samplerate(&mut lfo, 4);
+            let output = run(&mut lfo, vec![vec![[0.0; 2]; 2]; 4]);
+            for i in 0..4 {
+                assert_near(output[i][0][0], expected[i]);
+            }
+        }
+        // 120 bpm with two beats per cycle is 1 Hz
+        assert_eq!(
+            Rate::Tempo {
+                bpm: 120.0,
+                beats: 2.0
+            }
+            .hz(),
+            1.0
+        );
+        let mut lfo = Lfo::new(
+            Shape::Saw,
+            Rate::Tempo {
+                bpm: 60.0,
+                beats: 1.0,
+            },
+        );
+        lfo.set_depth(100.0);
+        lfo.set_offset(1000.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut lfo, 4);
+        let output = run(&mut lfo, vec![vec![[0.0; 2]; 2]; 2]);
+        assert_near(output[1][0][0], 950.0);
+        // the reset input restarts the cycle
+        let inputs = vec![vec![[0.0; 2], [0.0; 2]], vec![[0.0; 2], [1.0; 2]]];
+        let output = run(&mut lfo, inputs);
+        assert_near(output[1][0][0], 900.0);
+
+        // in a Graph the rate input gets one value per block and the output
+        // is interpolated from the value at the start of the last block
+        let mut graph = Graph::<[f32; 2]>::new(4, 4);
+        let rate = graph.add_processor(Box::new(Constant::<[f32; 2]>::from_value(0.25)));
+        let lfo = Lfo::new(Shape::Saw, Rate::Hz(0.25));
+        assert_eq!(Processor::<[f32; 2]>::input_type(&lfo, 0), PortType::Control);
+        assert_eq!(Processor::<[f32; 2]>::output_type(&lfo, 0), PortType::Control);
+        let lfo = graph.add_processor(Box::new(lfo));
+        graph.add_connection(&(rate, 0), &(lfo, 0)).unwrap();
+        graph.set_output_amt(1);
+        graph.connect_output(0, (lfo, 0)).unwrap();
+        let inputs = vec![Vec::new(); 4];
+        let mut outputs = vec![vec![[0.0; 2]]; 4];
+        graph.process(&inputs, &mut outputs);
+        assert_eq!(outputs, vec![vec![[-1.0; 2]]; 4]);
+        // 0.5 Hz moves the saw half a cycle per block
+        graph.process(&inputs, &mut outputs);
+        let left: Vec<f32> = outputs.iter().map(|frames| frames[0][0]).collect();
+        assert_eq!(left, vec![-0.75, -0.5, -0.25, 0.0]);
+    }
+
+    #[test]
+    fn random_lfo_test() {
+        for &shape in &[Shape::SampleAndHold, Shape::SmoothRandom] {
+            let mut lfo = Lfo::new(shape, Rate::Hz(1.0));
+            Processor::<[f32; 2]>::set_samplerate(&mut lfo, 100);
+            let output = run(&mut lfo, vec![vec![[0.0; 2]; 2]; 1000]);
+            let values: Vec<f32> = output.iter().map(|frame| frame[0][0]).collect();
+            assert!(values.iter().all(|x| x.abs() <= 1.0));
+            for pair in values.windows(2) {
+                let step = (pair[1] - pair[0]).abs();
+                if shape == Shape::SmoothRandom {
+                    assert!(step < 0.1);
+                }
+            }
+            // a new value every cycle
+            if shape == Shape::SampleAndHold {
+                assert_eq!(values[0], values[99]);
+                assert!(values[99] != values[100]);
+            }
+            let mut other = Lfo::new(shape, Rate::Hz(1.0));
+            other.set_seed(7);
+            Processor::<[f32; 2]>::set_samplerate(&mut other, 100);
+            let output = run(&mut other, vec![vec![[0.0; 2]; 2]; 1000]);
+            assert!(output.iter().zip(values.iter()).any(|(a, b)| a[0][0] != *b));
+        }
+    }
+
+    #[test]
+    fn delay_line_test() {
+        let mut line = DelayLine::new(8);
+        for i in 0..8 {
+            line.write(i as f64);
+        }
+        assert_eq!(line.read(1.0, Interpolation::Linear), 7.0);
+        assert_eq!(line.read(2.5, Interpolation::Linear), 5.5);
+        assert_eq!(line.read(8.0, Interpolation::Hermite), 0.0);
+        // hermite interpolation is exact for a ramp
+        assert!((line.read(3.25, Interpolation::Hermite) - 4.75).abs() < 1e-9);
+        // the delay is clamped to the capacity
+        assert_eq!(line.read(20.0, Interpolation::Linear), 0.0);
+        let mut allpass = 0.0;
+        for _ in 0..100 {
+            line.write(1.0);
+            allpass = line.read(2.5, Interpolation::Allpass);
+        }
+        assert!((allpass - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn delay_test() {
+        let mut delay = Delay::new(1.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut delay, 100);
+        delay.set_time(DelayTime::Tempo {
+            bpm: 1200.0,
+            beats: 1.0,
+        });
+        delay.set_feedback(0.5);
+        let mut inputs = vec![vec![[0.0; 2]; 2]; 20];
+        inputs[0][0] = [1.0, -1.0];
+        let output = run(&mut delay, inputs);
+        assert_eq!(output[0][0], [0.0, 0.0]);
+        assert_near(output[5][0][0], 1.0);
+        assert_near(output[5][0][1], -1.0);
+        assert_near(output[10][0][0], 0.5);
+        assert_near(output[15][0][0], 0.25);
+        assert_eq!(Processor::<[f32; 2]>::tail_length(&delay), 55);
+
+        // ping pong sends the echoes from side to side
+        delay.set_ping_pong(true);
+        delay.set_feedback(1.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut delay, 100);
+        let mut inputs = vec![vec![[0.0; 2]; 2]; 20];
+        inputs[0][0] = [1.0, 1.0];
+        let output = run(&mut delay, inputs);
+        assert_eq!(output[5][0][1], 0.0);
+        assert_near(output[5][0][0], 1.0);
+        assert_near(output[10][0][1], 0.999);
+        assert_eq!(output[10][0][0], 0.0);
+        assert_near(output[15][0][0], 0.998001);
+
+        // the time input is added to the delay time
+        let mut delay = Delay::new(1.0);
+        delay.set_time(DelayTime::Seconds(0.0));
+        delay.set_feedback_filter(Some((FilterType::Lowpass, 1000.0, 0.707)));
+        Processor::<[f32; 2]>::set_samplerate(&mut delay, 100);
+        let mut inputs = vec![vec![[0.0; 2], [0.03; 2]]; 5];
+        inputs[0][0] = [1.0; 2];
+        let output = run(&mut delay, inputs);
+        assert_near(output[3][0][0], 1.0);
+    }
+
+    // a frame for the signal input and one for the sidechain of a dynamics processor
+    fn signal(x: f32, sidechain: f32) -> Vec<[f32; 2]> {
+        vec![[x, -x], [sidechain; 2]]
+    }
+
+    #[test]
+    fn chorus_test() {
+        let inputs: Vec<Vec<[f32; 2]>> = (0..4800)
+            .map(|i| {
+                let x = (i as f32 * 0.05).sin();
+                vec![[x, x], [0.0; 2]]
+            })
+            .collect();
+        let mut chorus = Chorus::new();
+        chorus.set_spread(0.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut chorus, 48_000);
+        let output = run(&mut chorus, inputs.clone());
+        assert!(output.iter().all(|frame| frame[0][0] == frame[0][1]));
+        assert!(output[2000][0][0] != inputs[2000][0][0]);
+
+        // the spread moves the sides apart
+        chorus.set_spread(1.0);
+        let output = run(&mut chorus, inputs);
+        assert!(output[2000][0][0] != output[2000][0][1]);
+    }
+
+    #[test]
+    fn flanger_test() {
+        let mut inputs = vec![vec![[0.0; 2]; 2]; 200];
+        inputs[0][0] = [1.0, 1.0];
+        let mut flanger = Flanger::new();
+        flanger.set_depth(0.0);
+        flanger.set_feedback(0.5);
+        Processor::<[f32; 2]>::set_samplerate(&mut flanger, 48_000);
+        let output = run(&mut flanger, inputs.clone());
+        assert_near(output[0][0][0], 0.5);
+        assert_near(output[48][0][0], 0.5);
+        assert_near(output[96][0][1], 0.25);
+        assert_eq!(Processor::<[f32; 2]>::latency(&flanger), 0);
+
+        // through zero, the dry signal is delayed to the middle of the sweep
+        let mut flanger = Flanger::new();
+        flanger.set_through_zero(true);
+        Processor::<[f32; 2]>::set_samplerate(&mut flanger, 48_000);
+        assert_eq!(Processor::<[f32; 2]>::latency(&flanger), 96);
+        let output = run(&mut flanger, inputs.clone());
+        assert!(output[..90].iter().all(|frame| frame[0][0] == 0.0));
+        assert!(output[96][0][0] >= 0.5);
+
+        // without a sweep both copies meet
+        flanger.set_depth(0.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut flanger, 48_000);
+        let output = run(&mut flanger, inputs);
+        assert_near(output[48][0][0], 1.0);
+    }
+
+    #[test]
+    fn phaser_test() {
+        let mut phaser = Phaser::new();
+        phaser.set_depth(0.0);
+        phaser.set_mix(1.0);
+        phaser.set_frequency(1000.0);
+        assert!((sine_gain(&mut phaser, 300.0, 0) - 1.0).abs() < 0.01);
+        // the peak of a sampled sine at 3kHz can fall between two samples
+        let gain = sine_gain(&mut phaser, 3000.0, 0);
+        assert!(gain > 0.97 && gain < 1.01, "{}", gain);
+
+        // two stages shift by half a cycle at their frequency and cancel the dry signal
+        phaser.set_stages(2);
+        phaser.set_mix(0.5);
+        assert!(sine_gain(&mut phaser, 1000.0, 0) < 0.01);
+        assert!(sine_gain(&mut phaser, 100.0, 0) > 0.9);
+    }
+
+    #[test]
+    fn compressor_test() {
+        let mut compressor = Compressor::new(-20.0, 4.0, 0.001, 0.01);
+        assert_eq!(compressor.curve(-30.0), -30.0);
+        assert_eq!(compressor.curve(-12.0), -18.0);
+        compressor.set_knee(10.0);
+        assert_eq!(compressor.curve(-26.0), -26.0);
+        assert!((compressor.curve(-20.0) - -20.9375).abs() < 1e-9);
+        assert_eq!(compressor.curve(-14.0), -18.5);
+
+        // 0dB is 20dB over the threshold and comes out at -15dB, plus the makeup gain
+        let mut compressor = Compressor::new(-20.0, 4.0, 0.001, 0.01);
+        compressor.set_makeup(3.0);
+        Processor::<[f32; 2]>::set_samplerate(&mut compressor, 1000);
+        let output = run(&mut compressor, vec![signal(1.0, 0.0); 100]);
+        assert_near(output[99][0][0], 0.251189);
+        assert_near(output[99][0][1], -0.251189);
+
+        // the sidechain decides the gain
+        compressor.set_sidechain(true);
+        compressor.set_detection(Detection::Rms);
+        let output = run(&mut compressor, vec![signal(1.0, 0.0); 200]);
+        assert_near(output[199][0][0], 1.412538);
+    }
+
+    #[test]
+    fn expander_test() {
+        let mut expander = Expander::new(-20.0, 2.0, 0.0, 0.0);
+        let output = run(&mut expander, vec![signal(0.01, 0.0)]);
+        // -40dB is 20dB under the threshold and comes out at -60dB
+        assert_near(output[0][0][0], 0.001);
+        expander.set_range(-10.0);
+        let output = run(&mut expander, vec![signal(0.01, 0.0), signal(0.5, 0.0)]);
+        assert_near(output[0][0][0], 0.003162);
+        assert_near(output[1][0][0], 0.5);
+    }
+
+    #[test]
+    fn gate_test() {
+        let mut gate = Gate::new(-20.0, 0.0, 0.0);
+        gate.set_hysteresis(10.0);
+        gate.set_hold(0.0);
+        gate.set_range(-60.0);
+        // -26dB is between the thresholds, the gate keeps its state
+        let levels = [0.05, 0.2, 0.05, 0.01, 0.05];
+        let inputs = levels.iter().map(|&x| signal(x, 0.0)).collect();
+        let output = run(&mut gate, inputs);
+        assert_near(output[0][0][0], 0.00005);
+        assert_near(output[1][0][0], 0.2);
+        assert_near(output[2][0][0], 0.05);
+        assert_near(output[3][0][0], 0.00001);
+        assert_near(output[4][0][0], 0.00005);
+
+        // the gate stays open for the hold time
+        gate.set_hold(0.002);
+        Processor::<[f32; 2]>::set_samplerate(&mut gate, 1000);
+        let levels = [0.2, 0.01, 0.01, 0.01];
+        let inputs = levels.iter().map(|&x| signal(x, 0.0)).collect();
+        let output = run(&mut gate, inputs);
+        assert_near(output[2][0][0], 0.01);
+        assert_near(output[3][0][0], 0.00001);
+        assert!(!gate.is_open());
+    }
+
+    #[test]
+    fn limiter_test() {
+        let mut limiter = Limiter::new(-6.0, 0.004, 0.01);
+        Processor::<[f32; 2]>::set_samplerate(&mut limiter, 1000);
+        assert_eq!(Processor::<[f32; 2]>::latency(&limiter), 3);
+        let mut levels = vec![0.1; 20];
+        levels[10] = 2.0;
+        let inputs = levels.iter().map(|&x| signal(x, 0.0)).collect();
+        let output = run(&mut limiter, inputs);
+        let ceiling = 0.501187;
+        for i in 0..17 {
+            // the output is the input 3 frames ago, never above the ceiling
+            assert!(output[i + 3][0][0] <= ceiling + 1e-6);
+            let reduced = output[i + 3][0][0] < levels[i] * 0.999;
+            assert_eq!(reduced, (7..17).contains(&i), "frame {}", i);
+        }
+        assert_near(output[13][0][0], ceiling);
+        assert_near(output[13][0][1], -ceiling);
+        assert_eq!(output[2][0][0], 0.0);
+    }
+
+    // the energy of one channel of output 0 in a range of frames
+    fn energy(output: &BufferSet<[f32; 2]>, channel: usize, from: usize, to: usize) -> f32 {
+        output[from..to]
+            .iter()
+            .map(|frame| frame[0][channel] * frame[0][channel])
+            .sum()
+    }
+
+    #[test]
+    fn oversampler_test() {
+        assert!(Oversampler::new(3).is_err());
+        let latencies: Vec<usize> = [1, 2, 4, 8].iter().map(|&f| latency(f)).collect();
+        assert_eq!(latencies, vec![0, 31, 47, 55]);
+        for &factor in &[1, 2, 4, 8] {
+            let mut oversampler = Oversampler::new(factor).unwrap();
+            let mut calls = 0;
+            // a sine far below the Nyquist frequency only gets delayed
+            let sine = |i: usize| (i as f64 * 0.1).sin();
+            let output: Vec<f64> = (0..1000)
+                .map(|i| {
+                    oversampler.process(sine(i), |x| {
+                        calls += 1;
+                        x
+                    })
+                })
+                .collect();
+            assert_eq!(calls, 1000 * factor);
+            let latency = oversampler.latency();
+            for i in 200..1000 {
+                assert!((output[i] - sine(i - latency)).abs() < 1e-3, "{}", factor);
+            }
+        }
+    }
+
+    // the amplitude of `frequency` in the output of a processor for a sine at 15kHz
+    fn distortion_at<P>(processor: &mut P, frequency: f64) -> f64
+    where
+        P: Processor<[f32; 2]>,
+    {
+        processor.set_samplerate(48_000);
+        let inputs = (0..9600)
+            .map(|i| {
+                let x = (2.0 * ::std::f64::consts::PI * 15_000.0 * i as f64 / 48_000.0).sin();
+                vec![[x as f32; 2], [0.0; 2]]
+            })
+            .collect();
+        let outputs = run(processor, inputs);
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, frame) in outputs[4800..].iter().enumerate() {
+            let phase = 2.0 * ::std::f64::consts::PI * frequency * i as f64 / 48_000.0;
+            re += frame[0][0] as f64 * phase.cos();
+            im += frame[0][0] as f64 * phase.sin();
+        }
+        2.0 * (re * re + im * im).sqrt() / 4800.0
+    }
+
+    #[test]
+    fn waveshaper_test() {
+        let curves = [
+            distortion::Curve::Tanh,
+            distortion::Curve::SoftClip,
+            distortion::Curve::HardClip,
+            distortion::Curve::Foldback,
+            distortion::Curve::Wavefolder,
+            distortion::Curve::Tube,
+        ];
+        for curve in &curves {
+            assert!(curve.apply(0.0).abs() < 1e-9);
+            assert!(curve.apply(1.0) <= 1.0 && curve.apply(-1.0) >= -1.0);
+        }
+        assert_eq!(distortion::Curve::Foldback.apply(1.5), 0.5);
+
+        // the third harmonic of 15kHz is 45kHz, which aliases to 3kHz
+        let mut waveshaper = Waveshaper::new(distortion::Curve::HardClip);
+        waveshaper.set_drive(4.0);
+        waveshaper.set_oversampling(1).unwrap();
+        assert_eq!(Processor::<[f32; 2]>::latency(&waveshaper), 0);
+        let aliased = distortion_at(&mut waveshaper, 3000.0);
+        waveshaper.set_oversampling(8).unwrap();
+        assert_eq!(Processor::<[f32; 2]>::latency(&waveshaper), 55);
+        let oversampled = distortion_at(&mut waveshaper, 3000.0);
+        assert!(aliased > 0.1, "{}", aliased);
+        assert!(oversampled < aliased / 30.0, "{} {}", oversampled, aliased);
+    }
+
+    #[test]
+    fn oversampled_test() {
+        let mut waveshaper = Waveshaper::new(distortion::Curve::HardClip);
+        waveshaper.set_drive(4.0);
+        waveshaper.set_oversampling(1).unwrap();
+        let aliased = distortion_at(&mut waveshaper, 3000.0);
+        let mut oversampled = Oversampled::new(waveshaper, 8).unwrap();
+        let alias_free = distortion_at(&mut oversampled, 3000.0);
+        assert!(alias_free < aliased / 30.0, "{} {}", alias_free, aliased);
+
+        // a nested Graph gets the higher samplerate and a longer buffersize
+        let mut waveshaper = Waveshaper::new(distortion::Curve::HardClip);
+        waveshaper.set_drive(4.0);
+        waveshaper.set_oversampling(1).unwrap();
+        let mut graph = Graph::new(4 * 9600, 48_000);
+        let node = graph.add_processor(Box::new(waveshaper));
+        graph.set_input_amt(2);
+        graph.set_output_amt(1);
+        graph.connect_input(0, (node, 0)).unwrap();
+        graph.connect_input(1, (node, 1)).unwrap();
+        graph.connect_output(0, (node, 0)).unwrap();
+        let mut oversampled = Oversampled::new(graph, 4).unwrap();
+        let alias_free = distortion_at(&mut oversampled, 3000.0);
+        assert_eq!(oversampled.processor().samplerate(), 4 * 48_000);
+        assert_eq!(Processor::<[f32; 2]>::latency(&oversampled), 47);
+        assert!(alias_free < aliased / 30.0, "{} {}", alias_free, aliased);
+    }
+
+    #[test]
+    fn oversampled_latency_test() {
+        // a lookahead of 4 samples at the higher samplerate has a latency of 3,
+        // the output is delayed one more sample to get a whole number of samples
+        let limiter = Limiter::new(0.0, 4.0 / 96_000.0, 0.1);
+        let mut oversampled = Oversampled::new(limiter, 2).unwrap();
+        Processor::<[f32; 2]>::set_samplerate(&mut oversampled, 48_000);
+        let latency = Processor::<[f32; 2]>::latency(&oversampled);
+        assert_eq!(latency, 31 + 2);
+        let sine = |i: usize| 0.5 * (i as f32 * 0.05).sin();
+        let output = run(
+            &mut oversampled,
+            (0..1000).map(|i| vec![[sine(i); 2], [0.0; 2]]).collect(),
+        );
+        for i in 200..1000 {
+            assert!((output[i][0][0] - sine(i - latency)).abs() < 1e-3);
+        }
+    }
+
+    fn sine_at(frequency: f64, samplerate: f64, i: f64) -> f64 {
+        (2.0 * ::std::f64::consts::PI * frequency * i / samplerate).sin()
+    }
+
+    #[test]
+    fn resample_test() {
+        let input: Vec<f64> = (0..4410)
+            .map(|i| sine_at(1000.0, 44_100.0, i as f64))
+            .collect();
+        for &quality in &[Quality::Fast, Quality::Medium, Quality::Best] {
+            let output = resample(&input, 44_100, 48_000, quality).unwrap();
+            assert_eq!(output.len(), 4800);
+            for i in 100..4700 {
+                let expected = sine_at(1000.0, 48_000.0, i as f64);
+                assert!((output[i] - expected).abs() < 1e-3, "{:?} {}", quality, i);
+            }
+            let back = resample(&output, 48_000, 44_100, quality).unwrap();
+            assert_eq!(back.len(), 4410);
+            assert!((back[2000] - input[2000]).abs() < 1e-3);
+        }
+        // the same samplerate changes nothing
+        let output = resample(&input, 44_100, 44_100, Quality::Fast).unwrap();
+        assert!(output.iter().zip(&input).all(|(a, b)| (a - b).abs() < 1e-9));
+        assert!(Resampler::new(0, 44_100, Quality::Fast).is_err());
+    }
+
+    #[test]
+    fn resampled_test() {
+        // a Graph at 48kHz passing its input through, in a Graph at 44.1kHz
+        let mut graph = Graph::new(64, 48_000);
+        let node = graph.add_processor(Box::new(Mixer::new(1)));
+        graph.set_input_amt(1);
+        graph.set_output_amt(1);
+        graph.connect_input(0, (node, 0)).unwrap();
+        graph.connect_output(0, (node, 0)).unwrap();
+        let mut resampled = Resampled::graph(graph, Quality::Medium);
+        Processor::<[f32; 2]>::set_samplerate(&mut resampled, 44_100);
+        assert_eq!(resampled.processor().samplerate(), 48_000);
+        let latency = Processor::<[f32; 2]>::latency(&resampled);
+        assert!(latency > 64 && latency < 200, "{}", latency);
+        let sine = |i: usize| sine_at(1000.0, 44_100.0, i as f64) as f32;
+        let inputs = (0..2000).map(|i| vec![[sine(i); 2]]).collect();
+        let output = run(&mut resampled, inputs);
+        assert_eq!(output[0][0], [0.0; 2]);
+        for i in latency + 100..2000 {
+            assert!((output[i][0][0] - sine(i - latency)).abs() < 2e-3, "{}", i);
+        }
+
+        // a Processor without inputs runs at its own samplerate too
+        let frames = (0..4800)
+            .map(|i| [sine_at(1000.0, 48_000.0, i as f64) as f32; 2])
+            .collect();
+        let mut resampled = Resampled::new(
+            WavPlayer::from_frames(frames, 48_000),
+            48_000,
+            100,
+            Quality::Medium,
+        );
+        Processor::<[f32; 2]>::set_samplerate(&mut resampled, 44_100);
+        let latency = Processor::<[f32; 2]>::latency(&resampled);
+        let output = run(&mut resampled, vec![vec![]; 2000]);
+        for i in latency + 100..2000 {
+            assert!((output[i][0][0] - sine(i - latency)).abs() < 2e-3, "{}", i);
+        }
+    }
+
+    #[test]
+    fn wrapped_events_test() {
+        // 4ms of attack are 8 samples at the 2kHz of the wrapped Envelopes
+        let envelope = || {
+            let mut adsr = Envelope::adsr(0.004, 0.01, 0.5, 0.01);
+            adsr.set_curve(Curve::Linear);
+            adsr
+        };
+        let note = Event::new(1, EventKind::NoteOn { note: 60, velocity: 1.0 });
+        let oversampled = Oversampled::new(envelope(), 2).unwrap();
+        assert_eq!(Processor::<[f32; 2]>::input_type(&oversampled, 1), PortType::Event);
+        assert_eq!(Processor::<[f32; 2]>::output_type(&oversampled, 0), PortType::Control);
+        let resampled = Resampled::new(envelope(), 2000, 8, Quality::Fast);
+        assert_eq!(Processor::<[f32; 2]>::input_type(&resampled, 1), PortType::Event);
+        assert_eq!(Processor::<[f32; 2]>::output_type(&resampled, 0), PortType::Control);
+
+        let mut graph = Graph::<[f32; 2]>::new(4, 1000);
+        let source = graph.add_processor(Box::new(EventSource { events: vec![note] }));
+        let oversampled = graph.add_processor(Box::new(oversampled));
+        let resampled = graph.add_processor(Box::new(resampled));
+        graph.add_connection(&(source, 0), &(oversampled, 1)).unwrap();
+        graph.add_connection(&(source, 0), &(resampled, 1)).unwrap();
+        graph.set_output_amt(2);
+        graph.connect_output(0, (oversampled, 0)).unwrap();
+        graph.connect_output(1, (resampled, 0)).unwrap();
+        let inputs = vec![Vec::new(); 4];
+        let mut outputs = vec![vec![[0.0; 2]; 2]; 4];
+        graph.process(&inputs, &mut outputs);
+        assert_eq!(outputs, vec![vec![[0.0; 2]; 2]; 4]);
+        // the NoteOn at Frame 1 starts the attack at Frame 2 of the Oversampled,
+        // Frame 8 is the first one of the next block
+        graph.process(&inputs, &mut outputs);
+        let left: Vec<f32> = outputs.iter().map(|frames| frames[0][0]).collect();
+        assert_eq!(left, vec![0.21875, 0.4375, 0.65625, 0.875]);
+        // the Resampled starts its attack in its next block
+        let mut last = 0.0;
+        for _ in 0..4 {
+            graph.process(&inputs, &mut outputs);
+            assert!(outputs[3][1][0] >= last);
+            last = outputs[3][1][0];
+        }
+        assert!(last > 0.5, "{}", last);
+    }
+
+    #[test]
+    fn bitcrusher_test() {
+        let mut bitcrusher = Bitcrusher::new(3.0, 2.0);
+        let inputs = [0.1, 0.3, 0.2, -0.4, 0.9]
+            .iter()
+            .map(|&x| vec![[x; 2], [0.0; 2]])
+            .collect();
+        let output = run(&mut bitcrusher, inputs);
+        // 3 bits have 4 steps on each side, every sample is held twice
+        let expected = [0.0, 0.0, 0.25, 0.25, 1.0];
+        for (frame, &y) in output.iter().zip(&expected) {
+            assert_near(frame[0][0], y);
+        }
+    }
+
+    #[test]
+    fn reverb_test() {
+        let mut reverb = Reverb::new();
+        reverb.set_mix(1.0);
+        reverb.set_predelay(0.01);
+        Processor::<[f32; 2]>::set_samplerate(&mut reverb, 48_000);
+        let mut inputs = vec![vec![[0.0; 2]]; 48_000];
+        inputs[0][0] = [1.0, 0.0];
+        let output = run(&mut reverb, inputs);
+        // nothing during the pre-delay
+        assert_eq!(energy(&output, 0, 0, 480), 0.0);
+        // the reverb is decaying and both sides differ
+        let early = energy(&output, 0, 480, 12_480);
+        let late = energy(&output, 0, 36_000, 48_000);
+        assert!(early > 0.0 && late < early / 10.0, "{} {}", early, late);
+        assert!(output[10_000][0][0] != output[10_000][0][1]);
+        let tail = Processor::<[f32; 2]>::tail_length(&reverb);
+        assert!(tail > 48_000 && tail < 10 * 48_000, "{}", tail);
+
+        // no width makes both sides the same
+        reverb.set_width(0.0);
+        let mut inputs = vec![vec![[0.0; 2]]; 2000];
+        inputs[0][0] = [1.0, 0.0];
+        let output = run(&mut reverb, inputs);
+        assert!(output.iter().all(|frame| frame[0][0] == frame[0][1]));
+    }
+
+    // a reproducible noise signal
+    fn noise(len: usize, mut seed: u32) -> Vec<f64> {
+        (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as f64 / 32_768.0 - 1.0
+            })
+            .collect()
+    }
+
+    fn convolve(x: &[f64], h: &[f64]) -> Vec<f64> {
+        (0..x.len())
+            .map(|n| (0..h.len().min(n + 1)).map(|j| h[j] * x[n - j]).sum())
+            .collect()
+    }
+
+    #[test]
+    fn fft_test() {
+        let fft = Fft::new(16).unwrap();
+        let signal = noise(16, 1);
+        let mut data: Vec<Complex> = signal.iter().map(|&x| Complex::new(x, 0.0)).collect();
+        fft.forward(&mut data);
+        // compare bin 3 with the DFT
+        let bin = signal
+            .iter()
+            .enumerate()
+            .fold(Complex::zero(), |sum, (n, &x)| {
+                let angle = -2.0 * ::std::f64::consts::PI * 3.0 * n as f64 / 16.0;
+                sum + Complex::new(x * angle.cos(), x * angle.sin())
+            });
+        assert!((data[3] - bin).abs() < 1e-9);
+        fft.inverse(&mut data);
+        for (x, &y) in data.iter().zip(signal.iter()) {
+            assert!((x.re - y).abs() < 1e-9 && x.im.abs() < 1e-9);
+        }
+        assert!(Fft::new(12).is_err());
+    }
+
+    #[test]
+    fn convolver_test() {
+        let input = noise(500, 2);
+        for &len in &[10, 16, 300] {
+            let ir = noise(len, 3);
+            let mut convolver = Convolver::new(&ir, 16).unwrap();
+            assert_eq!(convolver.len() >= len, true);
+            let output: Vec<f64> = input.iter().map(|&x| convolver.process(x)).collect();
+            for (a, b) in output.iter().zip(convolve(&input, &ir).iter()) {
+                assert!((a - b).abs() < 1e-9, "{} is not {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn convolution_test() {
+        // true stereo: left goes to the right and right to the left, inverted
+        let ir = vec![vec![0.0], vec![1.0], vec![-1.0], vec![0.0]];
+        let mut convolution = Convolution::new(ir).unwrap();
+        Processor::<[f32; 2]>::set_samplerate(&mut convolution, 44_100);
+        let output = run(&mut convolution, vec![vec![[0.5, 0.25]]]);
+        assert_eq!(output[0][0], [-0.25, 0.5]);
+
+        // a mono impulse response is used for both channels, with half of the dry signal
+        let mut convolution = Convolution::new(vec![vec![0.0, 1.0]]).unwrap();
+        convolution.set_mix(0.5);
+        assert_eq!(Processor::<[f32; 2]>::tail_length(&convolution), 2);
+        let output = run(&mut convolution, vec![vec![[1.0, 0.5]], vec![[0.0; 2]]]);
+        assert_eq!(output[0][0], [0.5, 0.25]);
+        assert_eq!(output[1][0], [0.5, 0.25]);
+        assert!(Convolution::new(Vec::new()).is_err());
+    }
+}
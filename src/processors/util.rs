@@ -0,0 +1,552 @@
+//! Small building blocks: mixing, gain, panning and routing.
+
+extern crate sample;
+
+use graph::{BufferSet, FrameSet};
+use patch::{Params, Registry};
+use planar::PlanarBuffer;
+use processor::{PortInfo, PortKind, Processor};
+use self::sample::conv::Duplex;
+use self::sample::{Frame, Sample};
+use std::f64::consts::FRAC_PI_2;
+use super::{broadcast, control, map_f64};
+
+/// Converts decibels to a linear gain factor.
+pub fn db_to_gain(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// How a signal is distributed between two sides.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PanLaw {
+    /// Keeps the power constant, each side is at -3dB in the center.
+    ConstantPower,
+    /// Keeps the amplitude constant, each side is at -6dB in the center.
+    Linear,
+}
+
+impl PanLaw {
+    /// returns the gains of the two sides for a position from 0.0 to 1.0
+    fn gains(&self, position: f64) -> (f64, f64) {
+        let position = position.max(0.0).min(1.0);
+        match *self {
+            PanLaw::ConstantPower => {
+                let angle = position * FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            }
+            PanLaw::Linear => (1.0 - position, position),
+        }
+    }
+}
+
+/// Sums N inputs to one output, every input has its own gain.
+pub struct Mixer {
+    gains: Vec<f64>,
+}
+
+impl Mixer {
+    /// Create a Mixer with `inputs` inputs at unity gain
+    pub fn new(inputs: usize) -> Self {
+        Mixer {
+            gains: vec![1.0; inputs],
+        }
+    }
+
+    /// Create a Mixer with one input per gain
+    pub fn with_gains(gains: Vec<f64>) -> Self {
+        Mixer { gains: gains }
+    }
+
+    /// set the gain of an input
+    pub fn set_gain(&mut self, input: usize, gain: f64) {
+        self.gains[input] = gain;
+    }
+}
+
+impl<F> Processor<F> for Mixer
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, inputs: &BufferSet<F>, outputs: &mut BufferSet<F>) {
+        for i in 0..inputs.len() {
+            let gains = &self.gains;
+            outputs[i][0] = map_f64(F::equilibrium(), |channel, _| {
+                inputs[i]
+                    .iter()
+                    .zip(gains.iter())
+                    .map(|(frame, gain)| super::channel_f64(frame, channel) * gain)
+                    .sum()
+            });
+        }
+    }
+
+    fn process_planar(
+        &mut self,
+        inputs: &PlanarBuffer<F::Sample>,
+        outputs: &mut PlanarBuffer<F::Sample>,
+    ) {
+        for channel in 0..outputs.channels() {
+            let output = outputs.channel_mut(0, channel);
+            for y in output.iter_mut() {
+                *y = F::Sample::equilibrium();
+            }
+            for (input, gain) in self.gains.iter().enumerate().take(inputs.ports()) {
+                let input = inputs.channel(input, channel);
+                for (y, x) in output.iter_mut().zip(input) {
+                    let sum = y.to_sample::<f64>() + x.to_sample::<f64>() * gain;
+                    *y = sum.to_sample();
+                }
+            }
+        }
+    }
+
+    fn processes_planar(&self) -> bool {
+        true
+    }
+
+    fn inputs_amt(&self) -> usize {
+        self.gains.len()
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Multiplies the signal at input 0 with a gain.
+/// The value at input 1 is added to the gain, so an envelope
+/// connected there turns a Gain of 0.0 into an amplifier.
+pub struct Gain {
+    gain: f64,
+}
+
+impl Gain {
+    pub fn new(gain: f64) -> Self {
+        Gain { gain: gain }
+    }
+
+    /// Create a Gain from decibels
+    pub fn from_db(db: f64) -> Self {
+        Gain::new(db_to_gain(db))
+    }
+
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+    }
+}
+
+impl<F> Processor<F> for Gain
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let gain = self.gain + control(&inputs[1]);
+        outputs[0] = map_f64(inputs[0], |_, x| x * gain);
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("gain", PortKind::Modulation).with_description("added to the gain"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Moves the signal at input 0 between channel 0 (left) and channel 1 (right).
+/// The position goes from -1.0 (left) to 1.0 (right),
+/// the value at input 1 is added to it. Further channels are passed unchanged,
+/// so does a Frame with a single channel.
+pub struct Pan {
+    position: f64,
+    law: PanLaw,
+}
+
+impl Pan {
+    pub fn new(position: f64, law: PanLaw) -> Self {
+        Pan {
+            position: position,
+            law: law,
+        }
+    }
+
+    pub fn set_position(&mut self, position: f64) {
+        self.position = position;
+    }
+}
+
+impl<F> Processor<F> for Pan
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        if F::n_channels() < 2 {
+            outputs[0] = inputs[0];
+            return;
+        }
+        let position = self.position + control(&inputs[1]);
+        let (left, right) = self.law.gains((position + 1.0) / 2.0);
+        outputs[0] = map_f64(inputs[0], |channel, x| match channel {
+            0 => x * left,
+            1 => x * right,
+            _ => x,
+        });
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("in", PortKind::Main),
+            _ => PortInfo::new("position", PortKind::Modulation)
+                .with_description("added to the position, from -1.0 (left) to 1.0 (right)"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        2
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Copies its one input to N outputs.
+pub struct Splitter {
+    outputs: usize,
+}
+
+impl Splitter {
+    pub fn new(outputs: usize) -> Self {
+        Splitter { outputs: outputs }
+    }
+}
+
+impl<F> Processor<F> for Splitter
+where
+    F: Frame,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        for output in outputs.iter_mut() {
+            *output = inputs[0];
+        }
+    }
+
+    fn process_planar(
+        &mut self,
+        inputs: &PlanarBuffer<F::Sample>,
+        outputs: &mut PlanarBuffer<F::Sample>,
+    ) {
+        for output in 0..outputs.ports() {
+            outputs.copy_port(output, inputs, 0);
+        }
+    }
+
+    fn processes_planar(&self) -> bool {
+        true
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        self.outputs
+    }
+}
+
+/// Outputs the same Frame all the time.
+pub struct Constant<F> {
+    value: F,
+}
+
+impl<F> Constant<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    pub fn new(value: F) -> Self {
+        Constant { value: value }
+    }
+
+    /// Create a Constant with the same value on every channel
+    pub fn from_value(value: f64) -> Self {
+        Constant::new(broadcast(value))
+    }
+}
+
+impl<F> Processor<F> for Constant<F>
+where
+    F: Frame,
+{
+    fn frame_process(&mut self, _inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        outputs[0] = self.value;
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        0
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Inverts the polarity of its input.
+pub struct Invert {}
+
+impl<F> Processor<F> for Invert
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        outputs[0] = map_f64(inputs[0], |_, x| -x);
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Adds a constant offset to its input.
+pub struct DcOffset {
+    offset: f64,
+}
+
+impl DcOffset {
+    pub fn new(offset: f64) -> Self {
+        DcOffset { offset: offset }
+    }
+}
+
+impl<F> Processor<F> for DcOffset
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let offset = self.offset;
+        outputs[0] = map_f64(inputs[0], |_, x| x + offset);
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Passes its input on, or outputs silence while muted.
+pub struct Mute {
+    muted: bool,
+}
+
+impl Mute {
+    pub fn new(muted: bool) -> Self {
+        Mute { muted: muted }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+}
+
+impl<F> Processor<F> for Mute
+where
+    F: Frame,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        outputs[0] = if self.muted {
+            F::equilibrium()
+        } else {
+            inputs[0]
+        };
+    }
+
+    fn input_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("in", PortKind::Main)
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        1
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Fades between input 0 and input 1.
+/// At a position of 0.0 only input 0 is heard, at 1.0 only input 1.
+/// The value at input 2 is added to the position.
+pub struct Crossfade {
+    position: f64,
+    law: PanLaw,
+}
+
+impl Crossfade {
+    pub fn new(position: f64, law: PanLaw) -> Self {
+        Crossfade {
+            position: position,
+            law: law,
+        }
+    }
+
+    pub fn set_position(&mut self, position: f64) {
+        self.position = position;
+    }
+}
+
+impl<F> Processor<F> for Crossfade
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn frame_process(&mut self, inputs: &FrameSet<F>, outputs: &mut FrameSet<F>) {
+        let (a, b) = self.law.gains(self.position + control(&inputs[2]));
+        let other = &inputs[1];
+        outputs[0] = map_f64(inputs[0], |channel, x| {
+            x * a + super::channel_f64(other, channel) * b
+        });
+    }
+
+    fn input_info(&self, input: usize) -> PortInfo {
+        match input {
+            0 => PortInfo::new("a", PortKind::Main),
+            1 => PortInfo::new("b", PortKind::Main),
+            _ => PortInfo::new("position", PortKind::Modulation)
+                .with_description("added to the position, from 0.0 (a) to 1.0 (b)"),
+        }
+    }
+
+    fn output_info(&self, _: usize) -> PortInfo {
+        PortInfo::new("out", PortKind::Main)
+    }
+
+    fn inputs_amt(&self) -> usize {
+        3
+    }
+
+    fn outputs_amt(&self) -> usize {
+        1
+    }
+}
+
+/// Parse the name of a pan law as written in patches.
+pub fn parse_pan_law(name: &str) -> Result<PanLaw, String> {
+    match name {
+        "constant_power" => Ok(PanLaw::ConstantPower),
+        "linear" => Ok(PanLaw::Linear),
+        _ => Err(format!("unknown pan law '{}'", name)),
+    }
+}
+
+/// Add the processors of this module to a registry:
+///
+/// * `mixer(inputs: 2, gain0: 1, gain1: 1, ...)`
+/// * `gain(gain: 1)` or `gain(db: 0)`
+/// * `pan(position: 0, law: constant_power)`, the law can also be `linear`
+/// * `splitter(outputs: 2)`
+/// * `constant(value: 0)`
+/// * `invert()`
+/// * `dc_offset(offset: 0)`
+/// * `mute(muted: 0)`
+/// * `crossfade(position: 0, law: constant_power)`
+pub fn register<F>(registry: &mut Registry<F>)
+where
+    F: Frame + 'static,
+    F::Sample: Duplex<f64>,
+{
+    registry.register("mixer", |params: &Params| {
+        let inputs = params.number("inputs", 2.0)? as usize;
+        let mut gains = Vec::new();
+        for i in 0..inputs {
+            gains.push(params.number(&format!("gain{}", i), 1.0)?);
+        }
+        Ok(Box::new(Mixer::with_gains(gains)) as Box<Processor<F>>)
+    });
+    registry.register("gain", |params: &Params| {
+        let gain = match params.get("db") {
+            Some(_) => db_to_gain(params.number("db", 0.0)?),
+            None => params.number("gain", 1.0)?,
+        };
+        Ok(Box::new(Gain::new(gain)) as Box<Processor<F>>)
+    });
+    registry.register("pan", |params: &Params| {
+        let law = parse_pan_law(&params.text("law", "constant_power")?)?;
+        let position = params.number("position", 0.0)?;
+        Ok(Box::new(Pan::new(position, law)) as Box<Processor<F>>)
+    });
+    registry.register("splitter", |params: &Params| {
+        let outputs = params.number("outputs", 2.0)? as usize;
+        Ok(Box::new(Splitter::new(outputs)) as Box<Processor<F>>)
+    });
+    registry.register("constant", |params: &Params| {
+        let value = params.number("value", 0.0)?;
+        Ok(Box::new(Constant::<F>::from_value(value)) as Box<Processor<F>>)
+    });
+    registry.register("invert", |_: &Params| {
+        Ok(Box::new(Invert {}) as Box<Processor<F>>)
+    });
+    registry.register("dc_offset", |params: &Params| {
+        let offset = params.number("offset", 0.0)?;
+        Ok(Box::new(DcOffset::new(offset)) as Box<Processor<F>>)
+    });
+    registry.register("mute", |params: &Params| {
+        let muted = params.number("muted", 0.0)? != 0.0;
+        Ok(Box::new(Mute::new(muted)) as Box<Processor<F>>)
+    });
+    registry.register("crossfade", |params: &Params| {
+        let law = parse_pan_law(&params.text("law", "constant_power")?)?;
+        let position = params.number("position", 0.0)?;
+        Ok(Box::new(Crossfade::new(position, law)) as Box<Processor<F>>)
+    });
+}
@@ -0,0 +1,287 @@
+//! Kernels for summing, copying and scaling buffers of samples.
+//!
+//! A Graph mixes every connection with these kernels. For f32 and f64 they
+//! use AVX when the CPU supports it, which is detected at runtime,
+//! all other sample types and CPUs use scalar loops.
+//! Copying uses `copy_from_slice`, which is a vectorized memcpy already.
+
+extern crate sample;
+
+use self::sample::conv::Duplex;
+use self::sample::{Sample, I24, I48, U24, U48};
+use std::ops::{AddAssign, Mul, MulAssign};
+
+/// A sample type the kernels work on. The slices of one call should have
+/// the same length, only as many samples as the shorter one has are used.
+pub trait Kernel: Sample + Duplex<f64> {
+    /// Add `source` to `dest`.
+    fn add(dest: &mut [Self], source: &[Self]);
+
+    /// Add `source` multiplied with `gain` to `dest`.
+    fn add_scaled(dest: &mut [Self], source: &[Self], gain: f64);
+
+    /// Multiply every sample of `buffer` with `gain`.
+    fn scale(buffer: &mut [Self], gain: f64);
+
+    /// Add `source` to `dest` with a gain that starts at `from` and changes
+    /// by `step` every sample, the first sample gets `from + step`.
+    fn add_ramp(dest: &mut [Self], source: &[Self], from: f64, step: f64);
+
+    /// Copy `source` to `dest`.
+    fn copy(dest: &mut [Self], source: &[Self]) {
+        let len = dest.len().min(source.len());
+        dest[..len].copy_from_slice(&source[..len]);
+    }
+}
+
+/// Adds `source` to `dest` one sample at a time, like the kernels without AVX.
+pub fn add_scalar<S>(dest: &mut [S], source: &[S])
+where
+    S: Sample,
+{
+    for (x, &y) in dest.iter_mut().zip(source) {
+        *x = x.add_amp(y.to_signed_sample());
+    }
+}
+
+fn add_scaled_scalar<S>(dest: &mut [S], source: &[S], gain: f64)
+where
+    S: Sample + Duplex<f64>,
+{
+    for (x, &y) in dest.iter_mut().zip(source) {
+        *x = (x.to_sample::<f64>() + y.to_sample::<f64>() * gain).to_sample();
+    }
+}
+
+fn add_ramp_scalar<S>(dest: &mut [S], source: &[S], from: f64, step: f64)
+where
+    S: Sample + Duplex<f64>,
+{
+    let mut gain = from;
+    for (x, &y) in dest.iter_mut().zip(source) {
+        gain += step;
+        *x = (x.to_sample::<f64>() + y.to_sample::<f64>() * gain).to_sample();
+    }
+}
+
+fn scale_scalar<S>(buffer: &mut [S], gain: f64)
+where
+    S: Sample + Duplex<f64>,
+{
+    for x in buffer.iter_mut() {
+        *x = (x.to_sample::<f64>() * gain).to_sample();
+    }
+}
+
+macro_rules! scalar_kernel {
+    ($($t:ty)*) => {
+        $(
+            impl Kernel for $t {
+                fn add(dest: &mut [Self], source: &[Self]) {
+                    add_scalar(dest, source);
+                }
+
+                fn add_scaled(dest: &mut [Self], source: &[Self], gain: f64) {
+                    add_scaled_scalar(dest, source, gain);
+                }
+
+                fn scale(buffer: &mut [Self], gain: f64) {
+                    scale_scalar(buffer, gain);
+                }
+
+                fn add_ramp(dest: &mut [Self], source: &[Self], from: f64, step: f64) {
+                    add_ramp_scalar(dest, source, from, step);
+                }
+            }
+        )*
+    };
+}
+
+scalar_kernel!(i8 i16 I24 i32 I48 i64 u8 u16 U24 u32 U48 u64);
+
+/// The loops the f32 and f64 kernels use without AVX, public for the benchmarks.
+#[doc(hidden)]
+pub mod fallback {
+    use super::{AddAssign, Mul, MulAssign};
+
+    pub fn add<T>(dest: &mut [T], source: &[T])
+    where
+        T: Copy + AddAssign,
+    {
+        for (x, &y) in dest.iter_mut().zip(source) {
+            *x += y;
+        }
+    }
+
+    pub fn add_scaled<T>(dest: &mut [T], source: &[T], gain: T)
+    where
+        T: Copy + AddAssign + Mul<Output = T>,
+    {
+        for (x, &y) in dest.iter_mut().zip(source) {
+            *x += y * gain;
+        }
+    }
+
+    pub fn scale<T>(buffer: &mut [T], gain: T)
+    where
+        T: Copy + MulAssign,
+    {
+        for x in buffer.iter_mut() {
+            *x *= gain;
+        }
+    }
+}
+
+// true if the AVX kernels can be used
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_avx() -> bool {
+    is_x86_feature_detected!("avx")
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn has_avx() -> bool {
+    false
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    // one module per float type, `$lanes` samples fit in a register
+    macro_rules! avx_kernels {
+        ($name:ident, $t:ty, $lanes:expr, $load:ident, $store:ident, $add:ident, $mul:ident, $set1:ident) => {
+            pub mod $name {
+                use super::*;
+
+                #[target_feature(enable = "avx")]
+                pub unsafe fn add(dest: &mut [$t], source: &[$t]) {
+                    let len = dest.len().min(source.len());
+                    let (d, s) = (dest.as_mut_ptr(), source.as_ptr());
+                    let mut i = 0;
+                    while i + $lanes <= len {
+                        let sum = $add($load(d.add(i)), $load(s.add(i)));
+                        $store(d.add(i), sum);
+                        i += $lanes;
+                    }
+                    while i < len {
+                        *d.add(i) += *s.add(i);
+                        i += 1;
+                    }
+                }
+
+                #[target_feature(enable = "avx")]
+                pub unsafe fn add_scaled(dest: &mut [$t], source: &[$t], gain: $t) {
+                    let len = dest.len().min(source.len());
+                    let (d, s) = (dest.as_mut_ptr(), source.as_ptr());
+                    let gains = $set1(gain);
+                    let mut i = 0;
+                    while i + $lanes <= len {
+                        let sum = $add($load(d.add(i)), $mul($load(s.add(i)), gains));
+                        $store(d.add(i), sum);
+                        i += $lanes;
+                    }
+                    while i < len {
+                        *d.add(i) += *s.add(i) * gain;
+                        i += 1;
+                    }
+                }
+
+                #[target_feature(enable = "avx")]
+                pub unsafe fn scale(buffer: &mut [$t], gain: $t) {
+                    let len = buffer.len();
+                    let b = buffer.as_mut_ptr();
+                    let gains = $set1(gain);
+                    let mut i = 0;
+                    while i + $lanes <= len {
+                        $store(b.add(i), $mul($load(b.add(i)), gains));
+                        i += $lanes;
+                    }
+                    while i < len {
+                        *b.add(i) *= gain;
+                        i += 1;
+                    }
+                }
+            }
+        };
+    }
+
+    avx_kernels!(
+        f32s,
+        f32,
+        8,
+        _mm256_loadu_ps,
+        _mm256_storeu_ps,
+        _mm256_add_ps,
+        _mm256_mul_ps,
+        _mm256_set1_ps
+    );
+    avx_kernels!(
+        f64s,
+        f64,
+        4,
+        _mm256_loadu_pd,
+        _mm256_storeu_pd,
+        _mm256_add_pd,
+        _mm256_mul_pd,
+        _mm256_set1_pd
+    );
+}
+
+macro_rules! float_kernel {
+    ($t:ident, $avx:ident) => {
+        impl Kernel for $t {
+            fn add(dest: &mut [Self], source: &[Self]) {
+                #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                {
+                    if has_avx() {
+                        // the CPU supports AVX, which was checked right before
+                        unsafe { avx::$avx::add(dest, source) };
+                        return;
+                    }
+                }
+                fallback::add(dest, source);
+            }
+
+            fn add_scaled(dest: &mut [Self], source: &[Self], gain: f64) {
+                let gain = gain as $t;
+                #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                {
+                    if has_avx() {
+                        unsafe { avx::$avx::add_scaled(dest, source, gain) };
+                        return;
+                    }
+                }
+                fallback::add_scaled(dest, source, gain);
+            }
+
+            fn scale(buffer: &mut [Self], gain: f64) {
+                let gain = gain as $t;
+                #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                {
+                    if has_avx() {
+                        unsafe { avx::$avx::scale(buffer, gain) };
+                        return;
+                    }
+                }
+                fallback::scale(buffer, gain);
+            }
+
+            // ramps are short, the scalar loop is fast enough
+            fn add_ramp(dest: &mut [Self], source: &[Self], from: f64, step: f64) {
+                add_ramp_scalar(dest, source, from, step);
+            }
+        }
+    };
+}
+
+float_kernel!(f32, f32s);
+float_kernel!(f64, f64s);
+
+/// returns true if the f32 and f64 kernels use AVX on this CPU
+pub fn accelerated() -> bool {
+    has_avx()
+}
+
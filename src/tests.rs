@@ -3,9 +3,13 @@ extern crate sample;
 #[cfg(test)]
 mod tests {
 
-    use super::super::graph::Graph;
+    use super::super::graph::{Connection, Graph};
+    use super::super::graph::render;
     use super::super::graph::BufferSet;
-    use super::super::processor::Processor;
+    use super::super::planar::PlanarBuffer;
+    use super::super::processor::{Event, EventKind, PortInfo, PortKind, PortType, Processor};
+    use super::super::simd::{self, Kernel};
+    use super::super::builder::GraphBuilder;
 
     struct TestProcessor {}
 
@@ -26,6 +30,168 @@ mod tests {
     }
 
 
+    // delays its input by one frame
+    struct DelayProcessor {
+        last: [f32; 2],
+    }
+
+    impl super::super::processor::Processor<[f32; 2]> for DelayProcessor {
+        fn process(&mut self, inputs: &BufferSet<[f32; 2]>, outputs: &mut BufferSet<[f32; 2]>) {
+            for i in 0..inputs.len() {
+                outputs[i][0] = self.last;
+                self.last = inputs[i][0];
+            }
+        }
+        fn tail_length(&self) -> usize {
+            1
+        }
+        fn inputs_amt(&self) -> usize {
+            1
+        }
+        fn outputs_amt(&self) -> usize {
+            1
+        }
+    }
+
+    // delays by the length of its buffer and reports it as latency
+    struct LatencyProcessor {
+        buffer: Vec<[f32; 2]>,
+    }
+
+    impl super::super::processor::Processor<[f32; 2]> for LatencyProcessor {
+        fn process(&mut self, inputs: &BufferSet<[f32; 2]>, outputs: &mut BufferSet<[f32; 2]>) {
+            for i in 0..inputs.len() {
+                self.buffer.push(inputs[i][0]);
+                outputs[i][0] = self.buffer.remove(0);
+            }
+        }
+        fn latency(&self) -> usize {
+            self.buffer.len()
+        }
+        fn inputs_amt(&self) -> usize {
+            1
+        }
+        fn outputs_amt(&self) -> usize {
+            1
+        }
+    }
+
+    // swaps the two channels of its input, only works on planar buffers
+    struct SwapProcessor {}
+
+    impl super::super::processor::Processor<[f32; 2]> for SwapProcessor {
+        fn process_planar(
+            &mut self,
+            inputs: &PlanarBuffer<f32>,
+            outputs: &mut PlanarBuffer<f32>,
+        ) {
+            outputs.channel_mut(0, 0).copy_from_slice(inputs.channel(0, 1));
+            outputs.channel_mut(0, 1).copy_from_slice(inputs.channel(0, 0));
+        }
+        fn processes_planar(&self) -> bool {
+            true
+        }
+        fn inputs_amt(&self) -> usize {
+            1
+        }
+        fn outputs_amt(&self) -> usize {
+            1
+        }
+    }
+
+    // output 0 is a control output counting the blocks,
+    // output 1 sends that count as an event at the second Frame
+    struct CounterProcessor {
+        count: f32,
+    }
+
+    impl super::super::processor::Processor<[f32; 2]> for CounterProcessor {
+        fn process(&mut self, _: &BufferSet<[f32; 2]>, outputs: &mut BufferSet<[f32; 2]>) {
+            self.count += 1.0;
+            outputs[0][0] = [self.count; 2];
+        }
+        fn output_type(&self, output: usize) -> PortType {
+            if output == 0 {
+                PortType::Control
+            } else {
+                PortType::Event
+            }
+        }
+        fn take_events(&mut self, _: usize, events: &mut Vec<Event>) {
+            events.push(Event::new(1, EventKind::Value(self.count as f64)));
+        }
+        fn inputs_amt(&self) -> usize {
+            0
+        }
+        fn outputs_amt(&self) -> usize {
+            2
+        }
+    }
+
+    // input 0 takes events, which are written to output 0 at their time,
+    // input 1 is a control input, its value is written to every Frame of output 1
+    struct EventProcessor {
+        events: Vec<Event>,
+    }
+
+    impl super::super::processor::Processor<[f32; 2]> for EventProcessor {
+        fn process(&mut self, inputs: &BufferSet<[f32; 2]>, outputs: &mut BufferSet<[f32; 2]>) {
+            let control = inputs.first().map_or([0.0; 2], |frame| frame[1]);
+            for i in 0..inputs.len() {
+                // only the first Frame holds the control value
+                assert_eq!(inputs[i][1], if i == 0 { control } else { [0.0; 2] });
+                outputs[i] = vec![[0.0; 2], control];
+            }
+            for event in self.events.drain(..) {
+                if let EventKind::Value(value) = event.kind {
+                    outputs[event.time][0][0] += value as f32;
+                }
+            }
+        }
+        fn input_type(&self, input: usize) -> PortType {
+            if input == 0 {
+                PortType::Event
+            } else {
+                PortType::Control
+            }
+        }
+        fn handle_events(&mut self, _: usize, events: &[Event]) {
+            self.events.extend_from_slice(events);
+        }
+        fn inputs_amt(&self) -> usize {
+            2
+        }
+        fn outputs_amt(&self) -> usize {
+            2
+        }
+    }
+
+    // adds a sidechain to its input, the sidechain is 0.5 while it is unconnected
+    struct SidechainProcessor {}
+
+    impl super::super::processor::Processor<[f32; 2]> for SidechainProcessor {
+        fn process(&mut self, inputs: &BufferSet<[f32; 2]>, outputs: &mut BufferSet<[f32; 2]>) {
+            for i in 0..inputs.len() {
+                let (input, sidechain) = (inputs[i][0], inputs[i][1]);
+                outputs[i][0] = [input[0] + sidechain[0], input[1] + sidechain[1]];
+            }
+        }
+        fn input_info(&self, input: usize) -> PortInfo {
+            match input {
+                0 => PortInfo::new("in", PortKind::Main),
+                _ => PortInfo::new("sidechain", PortKind::Sidechain).with_default(0.5),
+            }
+        }
+        fn output_info(&self, _: usize) -> PortInfo {
+            PortInfo::new("out", PortKind::Main)
+        }
+        fn inputs_amt(&self) -> usize {
+            2
+        }
+        fn outputs_amt(&self) -> usize {
+            1
+        }
+    }
 
     #[test]
     fn cyclic_graph_test_1() {
@@ -230,4 +396,407 @@ mod tests {
             _ => {}
         }
     }
+
+    #[test]
+    fn builder_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(1)
+            .node("n1", TestProcessor {})
+            .node("n2", TestProcessor {})
+            .node("n3", TestProcessor {})
+            .connect("n1:0", "n3:0")
+            .connect("n2", "n3")
+            .input(0, "n1:0")
+            .input(1, "n2:0")
+            .output(0, "n3:0")
+            .build()
+            .unwrap();
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.1, 0.2], [0.3, 0.4]]];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0, 0.0]]];
+        Processor::process(&mut graph, &input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer[0][0], [0.1 + 0.3, 0.2 + 0.4]);
+    }
+
+    #[test]
+    fn builder_error_test() {
+        let result = GraphBuilder::<[f32; 2]>::new()
+            .node("n1", TestProcessor {})
+            .connect("n1:0", "n2:0")
+            .build();
+        assert_eq!(result.err(), Some("node 'n2' does not exist".to_string()));
+        let result = GraphBuilder::<[f32; 2]>::new()
+            .node("n1", TestProcessor {})
+            .node("n2", TestProcessor {})
+            .connect("n1", "n2")
+            .connect("n2", "n1")
+            .build();
+        assert_eq!(result.err(), Some("n2 -> n1: Cycle detected".to_string()));
+    }
+
+    #[test]
+    fn graph_macro_test() {
+        let mut graph = graph! {
+            frame: [f32; 2],
+            buffersize: 1,
+            samplerate: 41_000,
+            nodes {
+                n1 = TestProcessor {},
+                n2 = TestProcessor {},
+            }
+            connections {
+                n1.0 -> n2.0,
+            }
+            inputs(1) {
+                0 -> n1.0,
+            }
+            outputs(1) {
+                n2.0 -> 0,
+            }
+        }.unwrap();
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[4.1, 6.2]]];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0, 0.0]]];
+        Processor::process(&mut graph, &input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer, input_buffer);
+    }
+
+    #[test]
+    fn graph_macro_error_test() {
+        // TestProcessor has one input, port 1 is only found missing at run time
+        let result = graph! {
+            frame: [f32; 2],
+            buffersize: 1,
+            samplerate: 41_000,
+            nodes {
+                n1 = TestProcessor {},
+                n2 = TestProcessor {},
+            }
+            connections {
+                n1.0 -> n2.1,
+            }
+            inputs(0) {}
+            outputs(0) {}
+        };
+        assert_eq!(
+            result.err(),
+            Some("n1.0 -> n2.1: Destination Port does not Exist".to_string())
+        );
+        let result = graph! {
+            frame: [f32; 2],
+            buffersize: 1,
+            samplerate: 41_000,
+            nodes {
+                n1 = TestProcessor {},
+            }
+            connections {}
+            inputs(1) {
+                0 -> n1.3,
+            }
+            outputs(0) {}
+        };
+        assert_eq!(
+            result.err(),
+            Some("input 0 -> n1.3: port 3 does not exist on node 0".to_string())
+        );
+    }
+
+    #[test]
+    fn render_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(2)
+            .node("pass", TestProcessor {})
+            .node("delay1", DelayProcessor { last: [0.0; 2] })
+            .node("delay2", DelayProcessor { last: [0.0; 2] })
+            .connect("pass", "delay1")
+            .connect("delay1", "delay2")
+            .connect("pass", "delay2")
+            .input(0, "pass")
+            .output(0, "delay2")
+            .build()
+            .unwrap();
+        assert_eq!(graph.tail_length(), 2);
+        let input = (1..10).map(|i| [i as f32, 0.0]);
+        let output = render(&mut graph, input, 3);
+        // input 4 and later is never read, the tails make up two more frames
+        let expected: Vec<f32> = vec![0.0, 1.0, 3.0, 5.0, 3.0];
+        assert_eq!(output.iter().map(|frame| frame[0]).collect::<Vec<f32>>(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffersize of 0")]
+    fn render_empty_blocks_test() {
+        // without the check rendering would never end
+        let mut graph = Graph::<[f32; 2]>::new(0, 1000);
+        render(&mut graph, vec![[0.0; 2]], 1);
+    }
+
+    #[test]
+    fn latency_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(2)
+            .node("pass", TestProcessor {})
+            .node("latent", LatencyProcessor { buffer: vec![[0.0; 2]; 3] })
+            .node("delay", DelayProcessor { last: [0.0; 2] })
+            .connect("pass", "latent")
+            .connect("latent", "delay")
+            .connect("pass", "delay")
+            .input(0, "pass")
+            .output(0, "delay")
+            .build()
+            .unwrap();
+        assert_eq!(graph.latency(), 3);
+        let input = (1..6).map(|i| [i as f32, 0.0]);
+        let output = render(&mut graph, input, 5);
+        // the latency of the longest path is dropped from the start,
+        // the tail of the delay is kept
+        let expected: Vec<f32> = vec![3.0, 5.0, 7.0, 3.0, 4.0, 5.0];
+        assert_eq!(output.iter().map(|frame| frame[0]).collect::<Vec<f32>>(), expected);
+    }
+
+    #[test]
+    fn unconnected_processor_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(1)
+            .node("n1", TestProcessor {})
+            .input(0, "n1")
+            .output(0, "n1")
+            .build()
+            .unwrap();
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.5, 0.25]]];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0, 0.0]]];
+        Processor::process(&mut graph, &input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer, input_buffer);
+        assert!(graph.get_dot_string().contains("p0 [label=\"n1\", shape=box];"));
+    }
+
+    #[test]
+    fn planar_buffer_test() {
+        let interleaved: BufferSet<[f32; 2]> =
+            vec![vec![[1.0, 2.0], [5.0, 6.0]], vec![[3.0, 4.0], [7.0, 8.0]]];
+        let mut planar = PlanarBuffer::from_interleaved(&interleaved);
+        assert_eq!((planar.ports(), planar.channels(), planar.samples()), (2, 2, 2));
+        assert_eq!(planar.channel(0, 0), &[1.0, 3.0]);
+        assert_eq!(planar.channel(1, 1), &[6.0, 8.0]);
+        assert_eq!(planar.port(0), &[1.0, 3.0, 2.0, 4.0]);
+        assert_eq!(planar.frame::<[f32; 2]>(1, 1), [7.0, 8.0]);
+        assert_eq!(planar.to_interleaved::<[f32; 2]>(), interleaved);
+        planar.channel_mut(0, 1)[1] = 0.5;
+        let other = planar.clone();
+        planar.add_port(1, &other, 0);
+        assert_eq!(planar.channel(1, 1), &[8.0, 8.5]);
+        planar.reset(1, 3);
+        assert_eq!(planar.port(0), &[0.0; 6]);
+    }
+
+    #[test]
+    fn planar_processor_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(2)
+            .node("swap", SwapProcessor {})
+            .node("pass", TestProcessor {})
+            .connect("swap", "pass")
+            .input(0, "swap")
+            .output(0, "pass")
+            .build()
+            .unwrap();
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.5, 0.25]], vec![[1.0, 0.0]]];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0, 0.0]]; 2];
+        Processor::process(&mut graph, &input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer, vec![vec![[0.25, 0.5]], vec![[0.0, 1.0]]]);
+        // a Graph in a Graph gets the planar buffers directly
+        let inputs = PlanarBuffer::from_interleaved(&input_buffer);
+        let mut outputs = PlanarBuffer::new(1, 2, 2);
+        graph.process_planar(&inputs, &mut outputs);
+        assert_eq!(outputs.to_interleaved::<[f32; 2]>(), output_buffer);
+    }
+
+    #[test]
+    fn simd_test() {
+        // 19 samples use the vector loop and the remainder
+        let source: Vec<f32> = (0..19).map(|i| i as f32 * 0.25).collect();
+        let mut dest = vec![1.0f32; 19];
+        let mut expected = dest.clone();
+        simd::add_scalar(&mut expected, &source);
+        f32::add(&mut dest, &source);
+        assert_eq!(dest, expected);
+        f32::scale(&mut dest, 2.0);
+        f32::add_scaled(&mut dest, &source, -2.0);
+        assert_eq!(dest, vec![2.0; 19]);
+        let source: Vec<f64> = (0..7).map(|i| i as f64).collect();
+        let mut dest = vec![0.5f64; 7];
+        f64::add_scaled(&mut dest, &source, 0.5);
+        assert_eq!(dest[6], 3.5);
+        f64::copy(&mut dest, &source);
+        assert_eq!(dest, source);
+        // without AVX the float kernels give the same results
+        let mut scalar = vec![0.5f64; 7];
+        simd::fallback::add_scaled(&mut scalar, &source, 0.5);
+        let mut kernel = vec![0.5f64; 7];
+        f64::add_scaled(&mut kernel, &source, 0.5);
+        assert_eq!(scalar, kernel);
+        // other samples use the scalar kernels
+        let mut dest = vec![100i16, -100];
+        i16::add(&mut dest, &[2, -2]);
+        i16::scale(&mut dest, 0.5);
+        assert_eq!(dest, vec![51, -51]);
+    }
+
+    #[test]
+    fn connection_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(4)
+            .samplerate(1000)
+            .node("a", TestProcessor {})
+            .node("b", TestProcessor {})
+            .connect("a", "b")
+            .input(0, "a")
+            .output(0, "b")
+            .build()
+            .unwrap();
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[1.0, -1.0]]; 4];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0, 0.0]]; 4];
+        let mut connection = Connection::with_gain(0.5);
+        connection.inverted = true;
+        graph.add_connection_with((0, 0), (1, 0), connection).unwrap();
+        assert_eq!(graph.connection((0, 0), (1, 0)), Some(connection));
+        graph.process(&input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer[3], vec![[-0.5, 0.5]]);
+        // muting ramps down over the smoothing time of 4ms, 4 samples at 1 kHz
+        connection.muted = true;
+        graph.set_connection_smoothing(0.004);
+        graph.set_connection((0, 0), (1, 0), connection).unwrap();
+        graph.process(&input_buffer, &mut output_buffer);
+        let left: Vec<f32> = output_buffer.iter().map(|frames| frames[0][0]).collect();
+        assert_eq!(left, vec![-0.375, -0.25, -0.125, 0.0]);
+        graph.process(&input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer[0], vec![[0.0, 0.0]]);
+        assert!(graph.set_connection((1, 0), (0, 0), connection).is_err());
+    }
+
+    #[test]
+    fn port_type_test() {
+        let mut graph = Graph::<[f32; 2]>::new(4, 1000);
+        let counter = graph.add_processor(Box::new(CounterProcessor { count: 0.0 }));
+        let events = graph.add_processor(Box::new(EventProcessor { events: Vec::new() }));
+        let audio = graph.add_processor(Box::new(TestProcessor {}));
+        graph.set_input_amt(1);
+        graph.set_output_amt(3);
+        // events and signals do not mix
+        assert!(graph.add_connection(&(counter, 1), &(audio, 0)).is_err());
+        assert!(graph.add_connection(&(counter, 0), &(events, 0)).is_err());
+        assert!(graph.send_event((events, 1), Event::new(0, EventKind::Value(1.0))).is_err());
+
+        graph.add_connection(&(counter, 0), &(audio, 0)).unwrap();
+        graph.add_connection(&(counter, 1), &(events, 0)).unwrap();
+        graph.connect_input(0, (events, 1)).unwrap();
+        graph.connect_output(0, (audio, 0)).unwrap();
+        // a graph port carries either events or signals
+        assert!(graph.connect_input(0, (events, 0)).is_err());
+        assert!(graph.connect_output(0, (counter, 1)).is_err());
+        assert_eq!(graph.input_type(0), PortType::Audio);
+        graph.connect_output(1, (events, 0)).unwrap();
+        graph.connect_output(2, (events, 1)).unwrap();
+        graph.send_event((events, 0), Event::new(3, EventKind::Value(10.0))).unwrap();
+
+        let input_buffer: Vec<Vec<[f32; 2]>> = (0..4).map(|i| vec![[i as f32; 2]]).collect();
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0; 2]; 3]; 4];
+        graph.process(&input_buffer, &mut output_buffer);
+        let column = |output: &Vec<Vec<[f32; 2]>>, port: usize| -> Vec<f32> {
+            output.iter().map(|frames| frames[port][0]).collect()
+        };
+        // the control value of the first block is constant
+        assert_eq!(column(&output_buffer, 0), vec![1.0; 4]);
+        // events of the counter and sent ones arrive in the same block
+        assert_eq!(column(&output_buffer, 1), vec![0.0, 1.0, 0.0, 10.0]);
+        // the audio input of the control input is averaged
+        assert_eq!(column(&output_buffer, 2), vec![1.5; 4]);
+
+        graph.process(&input_buffer, &mut output_buffer);
+        // control outputs are interpolated from the last block's value
+        assert_eq!(column(&output_buffer, 0), vec![1.25, 1.5, 1.75, 2.0]);
+        assert_eq!(column(&output_buffer, 1), vec![0.0, 2.0, 0.0, 0.0]);
+
+        // a control output is added to a control input with the gain of the connection
+        graph
+            .add_connection_with((counter, 0), (events, 1), Connection::with_gain(0.5))
+            .unwrap();
+        graph.process(&input_buffer, &mut output_buffer);
+        assert_eq!(column(&output_buffer, 2), vec![3.0; 4]);
+    }
+
+    #[test]
+    fn nested_events_test() {
+        // the inner graph writes the events of its input to its audio output
+        // and passes the events of its counter to its second output
+        let mut inner = Graph::<[f32; 2]>::new(4, 1000);
+        let events = inner.add_processor(Box::new(EventProcessor { events: Vec::new() }));
+        let counter = inner.add_processor(Box::new(CounterProcessor { count: 0.0 }));
+        inner.set_input_amt(1);
+        inner.set_output_amt(2);
+        inner.connect_input(0, (events, 0)).unwrap();
+        inner.connect_output(0, (events, 0)).unwrap();
+        inner.connect_output(1, (counter, 1)).unwrap();
+        assert_eq!(inner.input_type(0), PortType::Event);
+        assert_eq!(inner.output_type(0), PortType::Audio);
+        assert_eq!(inner.output_type(1), PortType::Event);
+
+        let mut graph = Graph::<[f32; 2]>::new(4, 1000);
+        let counter = graph.add_processor(Box::new(CounterProcessor { count: 10.0 }));
+        let inner = graph.add_processor(Box::new(inner));
+        let events = graph.add_processor(Box::new(EventProcessor { events: Vec::new() }));
+        graph.set_output_amt(2);
+        graph.add_connection(&(counter, 1), &(inner, 0)).unwrap();
+        graph.add_connection(&(inner, 1), &(events, 0)).unwrap();
+        assert!(graph.add_connection(&(inner, 0), &(events, 0)).is_err());
+        graph.connect_output(0, (inner, 0)).unwrap();
+        graph.connect_output(1, (events, 0)).unwrap();
+        graph.send_event((inner, 0), Event::new(2, EventKind::Value(5.0))).unwrap();
+
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![Vec::new(); 4];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0; 2]; 2]; 4];
+        graph.process(&input_buffer, &mut output_buffer);
+        let column = |output: &Vec<Vec<[f32; 2]>>, port: usize| -> Vec<f32> {
+            output.iter().map(|frames| frames[port][0]).collect()
+        };
+        // events reach the inner graph and leave it in the same block
+        assert_eq!(column(&output_buffer, 0), vec![0.0, 11.0, 5.0, 0.0]);
+        assert_eq!(column(&output_buffer, 1), vec![0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn port_info_test() {
+        let mut graph = GraphBuilder::<[f32; 2]>::new()
+            .buffersize(1)
+            .node("a", TestProcessor {})
+            .node("comp", SidechainProcessor {})
+            .input(0, "comp:in")
+            .output(0, "comp:out")
+            .build()
+            .unwrap();
+        assert_eq!(graph.input_port("comp:sidechain"), Ok((1, 1)));
+        assert_eq!(graph.output_port("a"), Ok((0, 0)));
+        assert_eq!(graph.input_index(0, "0"), Some(0));
+        assert_eq!(
+            graph.input_port("comp:key"),
+            Err("node 'comp' has no input named 'key'".to_string())
+        );
+        assert!(graph.connect_named("b:0", "comp:sidechain").is_err());
+        // an index is only a name if the port exists
+        assert_eq!(graph.input_index(1, "1"), Some(1));
+        assert_eq!(graph.input_index(1, "2"), None);
+        assert_eq!(graph.output_index(0, "1"), None);
+        assert_eq!(
+            graph.input_port("comp:99"),
+            Err("node 'comp' has no input named '99'".to_string())
+        );
+
+        let input_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.25, 0.0]]];
+        let mut output_buffer: Vec<Vec<[f32; 2]>> = vec![vec![[0.0, 0.0]]];
+        // the unconnected sidechain holds its default
+        graph.process(&input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer[0][0], [0.75, 0.5]);
+        graph.connect_named("a:0", "comp:sidechain").unwrap();
+        graph.connect_input_named(0, "a").unwrap();
+        graph.process(&input_buffer, &mut output_buffer);
+        assert_eq!(output_buffer[0][0], [0.5, 0.0]);
+    }
 }